@@ -0,0 +1,388 @@
+//! An implementation of [Befunge-93].
+//!
+//! The playfield is an 80x25 torus, and the source may not be larger than that. Every cell of
+//! the playfield and the stack holds a 64-bit signed integer with wrapping arithmetic, so that
+//! `p` can store any value and `g` reads it back unchanged. Characters other than the
+//! Befunge-93 commands, including values written by `p` that are not characters, do nothing.
+//!
+//! The parts left undefined by the specification behave as follows:
+//!
+//! - Popping from an empty stack gives 0.
+//! - `/` and `%` by zero stop the program with an error. Both round towards zero.
+//! - `p` and `g` outside the playfield stop the program with an error.
+//! - `&` skips everything up to the next decimal number, optionally preceded by `-`, and reads
+//!   it, leaving the rest of the line in the input. `~` reads a single byte. Both push -1 at
+//!   EOF.
+//! - `,` writes the lowest byte of the value, and `.` writes the number followed by a space.
+//! - `?` uses a pseudo-random generator, which can be seeded through [`Config::seed`] for
+//!   reproducible runs.
+//!
+//! [Befunge-93]: https://esolangs.org/wiki/Befunge
+
+use std::io::{self, BufRead, Write};
+use std::time::Duration;
+use thiserror::Error;
+
+use crate::budget::{Budget, LimitExceeded};
+use crate::input::{read_byte, read_number};
+use crate::rng::Rng;
+use crate::{Language, Options};
+
+/// Width of the playfield.
+pub const WIDTH: usize = 80;
+/// Height of the playfield.
+pub const HEIGHT: usize = 25;
+
+/// Error enum for Befunge-93.
+#[derive(Error, Debug)]
+pub enum Error {
+    /// A line of the source is wider than the playfield.
+    #[error("line {line} is {width} characters wide, but the playfield is only {WIDTH}")]
+    LineTooWide {
+        /// 1-based line number.
+        line: usize,
+        /// Width of the line in characters.
+        width: usize,
+    },
+    /// The source has more lines than the playfield. Contains the number of lines.
+    #[error("source has {0} lines, but the playfield has only {HEIGHT}")]
+    TooManyLines(usize),
+    /// `/` or `%` with a divisor of zero.
+    #[error("division by zero at ({x}, {y})")]
+    DivisionByZero {
+        /// Column of the command.
+        x: usize,
+        /// Row of the command.
+        y: usize,
+    },
+    /// `p` or `g` with coordinates outside the playfield.
+    #[error("access to ({x}, {y}) outside the playfield")]
+    OutOfPlayfield {
+        /// The column given to the command.
+        x: i64,
+        /// The row given to the command.
+        y: i64,
+    },
+    /// The program ran past [`Config::max_steps`] or [`Config::timeout`].
+    /// Contains the number of steps executed so far.
+    #[error("execution limit exceeded after {0} steps")]
+    LimitExceeded(u64),
+    /// I/O error, which may occur during I/O operations.
+    #[error("unexpected I/O error")]
+    IoError(#[from] io::Error),
+}
+
+impl From<LimitExceeded> for Error {
+    fn from(LimitExceeded(steps): LimitExceeded) -> Self {
+        Error::LimitExceeded(steps)
+    }
+}
+
+/// Settings of the Befunge-93 interpreter.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Config {
+    /// Maximum number of steps to execute, where each step executes one cell of the
+    /// playfield. `None` means unlimited.
+    pub max_steps: Option<u64>,
+    /// Wall-clock time limit of a single run. `None` means unlimited.
+    pub timeout: Option<Duration>,
+    /// Seed of the random directions of `?`. `None` picks a different seed on every run.
+    pub seed: Option<u64>,
+}
+
+/// Befunge-93 interpreter.
+pub fn run<I: BufRead, O: Write>(source: &str, input: &mut I, output: &mut O) -> Result<(), Error> {
+    run_with_config(source, input, output, &Config::default())
+}
+
+/// Befunge-93 interpreter with a custom configuration.
+pub fn run_with_config<I: BufRead, O: Write>(
+    source: &str,
+    input: &mut I,
+    output: &mut O,
+    config: &Config,
+) -> Result<(), Error> {
+    Program::compile_with_config(source, config)?.run(input, output)
+}
+
+/// The [`Language`] implementation for Befunge-93.
+///
+/// Compiles with the default [`Config`], except for the limits taken from [`Options`].
+#[derive(Debug, Clone, Copy)]
+pub struct Befunge93;
+
+impl Language for Befunge93 {
+    fn name(&self) -> &'static str {
+        "befunge93"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["bf93"]
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["b93", "bf93"]
+    }
+
+    fn compile(
+        &self,
+        source: &str,
+        options: &Options,
+    ) -> Result<Box<dyn crate::Program>, crate::Error> {
+        let config = Config {
+            max_steps: options.max_steps,
+            timeout: options.timeout,
+            ..Config::default()
+        };
+        let program = Program::compile_with_config(source, &config)
+            .map_err(|error| crate::Error::compile(self.name(), error))?;
+        Ok(Box::new(program))
+    }
+}
+
+/// A Befunge-93 program loaded into its playfield.
+///
+/// Each run starts from a fresh copy of the playfield, so changes made by `p` do not carry
+/// over to the next run.
+#[derive(Debug, Clone)]
+pub struct Program {
+    playfield: Vec<i64>,
+    config: Config,
+}
+
+impl Program {
+    /// Loads the source code with the default [`Config`].
+    pub fn compile(source: &str) -> Result<Self, Error> {
+        Self::compile_with_config(source, &Config::default())
+    }
+
+    /// Loads the source code with a custom configuration.
+    ///
+    /// Lines may end with `\n` or `\r\n`, and short lines are padded with spaces.
+    pub fn compile_with_config(source: &str, config: &Config) -> Result<Self, Error> {
+        let mut playfield = vec![b' ' as i64; WIDTH * HEIGHT];
+        let lines: Vec<&str> = source.lines().collect();
+        if lines.len() > HEIGHT {
+            return Err(Error::TooManyLines(lines.len()));
+        }
+        for (y, line) in lines.into_iter().enumerate() {
+            let width = line.chars().count();
+            if width > WIDTH {
+                return Err(Error::LineTooWide { line: y + 1, width });
+            }
+            for (x, c) in line.chars().enumerate() {
+                playfield[y * WIDTH + x] = c as i64;
+            }
+        }
+        Ok(Self {
+            playfield,
+            config: config.clone(),
+        })
+    }
+
+    /// The configuration the program was compiled with.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Runs the program from the start.
+    pub fn run<I: BufRead, O: Write>(&self, input: &mut I, output: &mut O) -> Result<(), Error> {
+        let mut playfield = self.playfield.clone();
+        let mut stack: Vec<i64> = vec![];
+        let mut budget = Budget::new(self.config.max_steps, self.config.timeout);
+        let mut rng = Rng::new(self.config.seed);
+        let (mut x, mut y) = (0usize, 0usize);
+        let (mut dx, mut dy) = (1isize, 0isize);
+        let mut string_mode = false;
+        let pop = |stack: &mut Vec<i64>| stack.pop().unwrap_or(0);
+        loop {
+            budget.spend(1)?;
+            let value = playfield[y * WIDTH + x];
+            if string_mode {
+                if value == b'"' as i64 {
+                    string_mode = false;
+                } else {
+                    stack.push(value);
+                }
+            } else if let Ok(command) = u8::try_from(value) {
+                match command {
+                    b'0'..=b'9' => stack.push((command - b'0') as i64),
+                    b'+' | b'-' | b'*' | b'/' | b'%' | b'`' => {
+                        let b = pop(&mut stack);
+                        let a = pop(&mut stack);
+                        let result = match command {
+                            b'+' => a.wrapping_add(b),
+                            b'-' => a.wrapping_sub(b),
+                            b'*' => a.wrapping_mul(b),
+                            b'/' | b'%' if b == 0 => return Err(Error::DivisionByZero { x, y }),
+                            b'/' => a.wrapping_div(b),
+                            b'%' => a.wrapping_rem(b),
+                            _ => (a > b) as i64,
+                        };
+                        stack.push(result);
+                    }
+                    b'!' => {
+                        let a = pop(&mut stack);
+                        stack.push((a == 0) as i64);
+                    }
+                    b'>' => (dx, dy) = (1, 0),
+                    b'<' => (dx, dy) = (-1, 0),
+                    b'^' => (dx, dy) = (0, -1),
+                    b'v' => (dx, dy) = (0, 1),
+                    b'?' => (dx, dy) = [(1, 0), (-1, 0), (0, -1), (0, 1)][rng.below(4)],
+                    b'_' => {
+                        (dx, dy) = if pop(&mut stack) == 0 {
+                            (1, 0)
+                        } else {
+                            (-1, 0)
+                        }
+                    }
+                    b'|' => {
+                        (dx, dy) = if pop(&mut stack) == 0 {
+                            (0, 1)
+                        } else {
+                            (0, -1)
+                        }
+                    }
+                    b'"' => string_mode = true,
+                    b':' => {
+                        let a = pop(&mut stack);
+                        stack.extend([a, a]);
+                    }
+                    b'\\' => {
+                        let b = pop(&mut stack);
+                        let a = pop(&mut stack);
+                        stack.extend([b, a]);
+                    }
+                    b'$' => {
+                        stack.pop();
+                    }
+                    b'.' => write!(output, "{} ", pop(&mut stack))?,
+                    b',' => output.write_all(&[pop(&mut stack) as u8])?,
+                    b'#' => (x, y) = step(x, y, dx, dy),
+                    b'p' | b'g' => {
+                        let py = pop(&mut stack);
+                        let px = pop(&mut stack);
+                        let index = match (usize::try_from(px), usize::try_from(py)) {
+                            (Ok(px), Ok(py)) if px < WIDTH && py < HEIGHT => py * WIDTH + px,
+                            _ => return Err(Error::OutOfPlayfield { x: px, y: py }),
+                        };
+                        if command == b'p' {
+                            playfield[index] = pop(&mut stack);
+                        } else {
+                            stack.push(playfield[index]);
+                        }
+                    }
+                    b'&' => stack.push(read_number(input)?.unwrap_or(-1)),
+                    b'~' => stack.push(read_byte(input)?.map_or(-1, |byte| byte as i64)),
+                    b'@' => break,
+                    _ => (),
+                }
+            }
+            (x, y) = step(x, y, dx, dy);
+        }
+        output.flush()?;
+        Ok(())
+    }
+}
+
+impl crate::Program for Program {
+    fn run(
+        &self,
+        mut input: &mut dyn BufRead,
+        mut output: &mut dyn Write,
+    ) -> Result<(), crate::Error> {
+        Program::run(self, &mut input, &mut output)
+            .map_err(|error| crate::Error::runtime(Befunge93.name(), error))
+    }
+}
+
+/// Moves one cell in the direction, wrapping around the edges of the playfield.
+fn step(x: usize, y: usize, dx: isize, dy: isize) -> (usize, usize) {
+    (
+        (x as isize + dx).rem_euclid(WIDTH as isize) as usize,
+        (y as isize + dy).rem_euclid(HEIGHT as isize) as usize,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_str(source: &str, input: &str) -> (Result<(), Error>, String) {
+        let config = Config {
+            max_steps: Some(100_000),
+            ..Config::default()
+        };
+        let mut stdout: Vec<u8> = vec![];
+        let res = run_with_config(source, &mut input.as_bytes(), &mut stdout, &config);
+        (res, String::from_utf8(stdout).unwrap())
+    }
+
+    #[test]
+    fn test_befunge93() {
+        // hello world from the esolangs wiki, with string mode and wrapping around
+        let code = "\"!dlroW ,olleH\">:#,_@";
+        let (res, stdout) = run_str(code, "");
+        assert!(res.is_ok());
+        assert_eq!(stdout, "Hello, World!");
+
+        // factorial of a number read with `&`, with arithmetic and `|`
+        let code = "&>:1-:v v *_$.@\n ^    _$>\\:^";
+        let (res, stdout) = run_str(code, "  x5\n");
+        assert!(res.is_ok());
+        assert_eq!(stdout, "120 ");
+
+        // self-modification: write `@` over the `x`, then read it back
+        let code = "v\n>\"@\"92p92g,v\n         x <";
+        let (res, stdout) = run_str(code, "");
+        assert!(res.is_ok());
+        assert_eq!(stdout, "@");
+
+        // `~` at EOF, negative numbers with `&`, `\`, `` ` ``, `!` and `%`
+        let (res, stdout) = run_str("~.&.&.~.9 7%.23\\`.0!.@", "a-12 3");
+        assert!(res.is_ok());
+        assert_eq!(stdout, "97 -12 3 -1 2 1 1 ");
+
+        // `?` either prints or wraps around to `@`, and up and down come back to `?`
+        let mut outputs = vec![];
+        for seed in 0..16 {
+            let config = Config {
+                seed: Some(seed),
+                ..Config::default()
+            };
+            let mut runs = [vec![], vec![]];
+            for stdout in &mut runs {
+                let res = run_with_config("1?.@", &mut &b""[..], stdout, &config);
+                assert!(res.is_ok());
+            }
+            assert_eq!(runs[0], runs[1]);
+            outputs.push(runs[0].clone());
+        }
+        assert!(outputs.contains(&b"1 ".to_vec()));
+        assert!(outputs.contains(&vec![]));
+    }
+
+    #[test]
+    fn test_befunge93_errors() {
+        let (res, _) = run_str("10/@", "");
+        assert!(matches!(res, Err(Error::DivisionByZero { x: 2, y: 0 })));
+        let (res, _) = run_str("09-0g@", "");
+        assert!(matches!(res, Err(Error::OutOfPlayfield { x: -9, y: 0 })));
+        let (res, _) = run_str(&"\n".repeat(26), "");
+        assert!(matches!(res, Err(Error::TooManyLines(26))));
+        let (res, _) = run_str(&format!("@\n{}", " ".repeat(81)), "");
+        assert!(matches!(
+            res,
+            Err(Error::LineTooWide { line: 2, width: 81 })
+        ));
+
+        let config = Config {
+            max_steps: Some(1000),
+            ..Config::default()
+        };
+        let mut stdout: Vec<u8> = vec![];
+        let res = run_with_config(">", &mut &b""[..], &mut stdout, &config);
+        assert!(matches!(res, Err(Error::LimitExceeded(1001))));
+    }
+}
@@ -0,0 +1,116 @@
+//! Configuration of the Brainfuck interpreter.
+
+use std::time::Duration;
+
+/// Configuration for [`run_with_config`](super::run_with_config).
+///
+/// The default configuration matches [`run`](super::run): a cyclic tape of 65536 cells,
+/// 8-bit cells, wrapping arithmetic, `,` leaving the cell unchanged on EOF, no extensions, and
+/// no limits on execution.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    /// Initial number of cells on the tape. A tape always has at least one cell.
+    pub tape_len: usize,
+    /// Width of each cell.
+    pub cell_width: CellWidth,
+    /// What happens when `+` or `-` would take a cell out of its range.
+    pub overflow: Overflow,
+    /// What happens when the pointer moves past either end of the tape.
+    pub tape: TapeKind,
+    /// What `,` does when the input is exhausted.
+    pub eof: Eof,
+    /// Extensions to the language.
+    pub dialect: Dialect,
+    /// Maximum number of steps to execute before stopping with
+    /// [`Error::LimitExceeded`](super::Error::LimitExceeded).
+    ///
+    /// A step is a single bytecode instruction or a jump between basic blocks.
+    /// Since the bytecode is optimized, this is usually far fewer than the number of
+    /// source commands executed. The limit is checked once per basic block, so a run may
+    /// go slightly past it.
+    pub max_steps: Option<u64>,
+    /// Wall-clock time after which the program is stopped with
+    /// [`Error::LimitExceeded`](super::Error::LimitExceeded).
+    pub timeout: Option<Duration>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            tape_len: 65536,
+            cell_width: CellWidth::U8,
+            overflow: Overflow::Wrap,
+            tape: TapeKind::Cyclic,
+            eof: Eof::Unchanged,
+            dialect: Dialect::default(),
+            max_steps: None,
+            timeout: None,
+        }
+    }
+}
+
+/// Width of a single cell.
+///
+/// `,` stores the input byte as-is, and `.` writes the lowest 8 bits of the cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CellWidth {
+    /// 8-bit cells.
+    U8,
+    /// 16-bit cells.
+    U16,
+    /// 32-bit cells.
+    U32,
+    /// 64-bit cells.
+    U64,
+}
+
+/// Behavior of `+` and `-` at the boundaries of the cell range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overflow {
+    /// Wrap around, so that `0 - 1` is the maximum value and vice versa.
+    Wrap,
+    /// Stop the program with [`Error::CellOutOfRange`](super::Error::CellOutOfRange).
+    Error,
+    /// Clamp the value to the cell range.
+    Saturate,
+}
+
+/// Behavior of `<` and `>` at the ends of the tape.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TapeKind {
+    /// The tape wraps around at both ends.
+    Cyclic,
+    /// Moving past either end stops the program with
+    /// [`Error::TapeOutOfRange`](super::Error::TapeOutOfRange).
+    Bounded,
+    /// The tape grows to the right on demand.
+    /// Moving left of the first cell is still an error.
+    Growable,
+}
+
+/// Behavior of `,` when there is no more input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Eof {
+    /// Leave the current cell unchanged.
+    #[default]
+    Unchanged,
+    /// Store 0 in the current cell.
+    Zero,
+    /// Store -1 in the current cell, i.e. the maximum value of the cell (255 for 8-bit cells).
+    MinusOne,
+    /// Stop the program with [`Error::UnexpectedEof`](super::Error::UnexpectedEof).
+    Error,
+}
+
+/// Common extensions to Brainfuck, all disabled by default.
+///
+/// When an extension is disabled, its character is a comment as usual.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Dialect {
+    /// `#` writes the cells around the pointer to the diagnostic output, which is stderr unless
+    /// given to [`Program::run_with_diagnostics`](super::Program::run_with_diagnostics).
+    pub tape_dump: bool,
+    /// The first `!` ends the code, and the rest of the source is read by `,` before the actual
+    /// input.
+    pub inline_input: bool,
+}
@@ -0,0 +1,407 @@
+//! A step debugger for Brainfuck programs.
+//!
+//! Unlike [`Program`](super::Program), the debugger does not optimize the source at all, so that
+//! every step executes exactly one source command and the position of execution can always be
+//! mapped back to the source.
+
+use std::collections::BTreeSet;
+use std::io::{BufRead, Write};
+
+use super::tape::{format_cells, Cell, Tape};
+use super::{getc, putc, split_inline_input, CellWidth, Config, Eof, Error, SyntaxError};
+use crate::budget::Budget;
+
+/// Why [`Debugger::step`] or [`Debugger::resume`] returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// A single command was executed.
+    Step,
+    /// Execution reached a breakpoint. Contains the source offset of the command that is
+    /// about to be executed.
+    Breakpoint(usize),
+    /// A `#` command was executed. Contains its source offset.
+    DebugChar(usize),
+    /// A watched cell was changed by the last command.
+    Watchpoint {
+        /// Position of the cell on the tape.
+        cell: usize,
+        /// Value of the cell before the command.
+        old: u64,
+        /// Value of the cell after the command.
+        new: u64,
+    },
+    /// The program ran to completion.
+    Finished,
+}
+
+/// A single source command, with jump targets resolved.
+#[derive(Debug, Clone, Copy)]
+enum Op {
+    Inc,
+    Dec,
+    Left,
+    Right,
+    Getc,
+    Putc,
+    /// `[`, with the index of the matching `]`.
+    Open(usize),
+    /// `]`, with the index of the matching `[`.
+    Close(usize),
+    /// `#`, which does nothing except stopping the debugger.
+    Debug,
+}
+
+/// Width-independent view of a [`Tape`], so that the debugger is not generic over cells.
+trait AnyTape {
+    fn ptr(&self) -> usize;
+    fn len(&self) -> usize;
+    fn cell(&self, index: usize) -> Option<u64>;
+    fn add(&mut self, delta: i64) -> Result<(), Error>;
+    fn shift(&mut self, delta: isize) -> Result<(), Error>;
+    fn store(&mut self, value: u64) -> Result<(), Error>;
+    fn is_zero(&self) -> bool;
+}
+
+impl<C: Cell> AnyTape for Tape<C> {
+    fn ptr(&self) -> usize {
+        Tape::ptr(self)
+    }
+
+    fn len(&self) -> usize {
+        Tape::len(self)
+    }
+
+    fn cell(&self, index: usize) -> Option<u64> {
+        Tape::cell(self, index).map(Cell::to_u64)
+    }
+
+    fn add(&mut self, delta: i64) -> Result<(), Error> {
+        Tape::add(self, 0, delta)
+    }
+
+    fn shift(&mut self, delta: isize) -> Result<(), Error> {
+        Tape::shift(self, delta)
+    }
+
+    fn store(&mut self, value: u64) -> Result<(), Error> {
+        self.set(0, C::from_u64(value))
+    }
+
+    fn is_zero(&self) -> bool {
+        Tape::is_zero(self)
+    }
+}
+
+/// Executes a Brainfuck program one command at a time.
+///
+/// The debugger owns the input and output of the program. Between steps, the position of
+/// execution, the pointer and the tape can be inspected. Execution can be stopped at
+/// breakpoints on source offsets, at `#` commands, and when a watched cell changes.
+///
+/// [`Config::max_steps`] is honored with every source command counting as one step, but
+/// [`Config::timeout`] is ignored since the time is mostly spent waiting for the user.
+/// `#` always stops the debugger, regardless of [`Dialect::tape_dump`](super::Dialect::tape_dump).
+///
+/// ```
+/// use esobox::brainfuck::{Config, Debugger, StopReason};
+///
+/// let mut output = vec![];
+/// let mut debugger = Debugger::new("++#>+.", &Config::default(), &b""[..], &mut output).unwrap();
+/// assert_eq!(debugger.resume().unwrap(), StopReason::DebugChar(2));
+/// assert_eq!(debugger.cell(0), Some(2));
+/// assert_eq!(debugger.resume().unwrap(), StopReason::Finished);
+/// assert_eq!(debugger.pointer(), 1);
+/// ```
+pub struct Debugger<I, O> {
+    ops: Vec<(usize, Op)>,
+    ip: usize,
+    tape: Box<dyn AnyTape>,
+    inline_input: Vec<u8>,
+    eof: Eof,
+    budget: Budget,
+    steps: u64,
+    breakpoints: BTreeSet<usize>,
+    watchpoints: BTreeSet<usize>,
+    input: I,
+    output: O,
+}
+
+impl<I: BufRead, O: Write> Debugger<I, O> {
+    /// Prepares the source code for debugging, stopped before the first command.
+    pub fn new(source: &str, config: &Config, input: I, output: O) -> Result<Self, Error> {
+        let (code, inline_input) = split_inline_input(source, config);
+        let mut ops: Vec<(usize, Op)> = vec![];
+        let mut stack = vec![];
+        for (pos, c) in code.char_indices() {
+            let op = match c {
+                '+' => Op::Inc,
+                '-' => Op::Dec,
+                '<' => Op::Left,
+                '>' => Op::Right,
+                ',' => Op::Getc,
+                '.' => Op::Putc,
+                '#' => Op::Debug,
+                '[' => {
+                    stack.push((ops.len(), pos));
+                    Op::Open(0)
+                }
+                ']' => {
+                    let (open, _) = stack
+                        .pop()
+                        .ok_or_else(|| Error::SyntaxError(SyntaxError::new(source, pos, ']')))?;
+                    ops[open].1 = Op::Open(ops.len());
+                    Op::Close(open)
+                }
+                _ => continue,
+            };
+            ops.push((pos, op));
+        }
+        if let Some(&(_, pos)) = stack.first() {
+            return Err(Error::SyntaxError(SyntaxError::new(source, pos, '[')));
+        }
+        let tape: Box<dyn AnyTape> = match config.cell_width {
+            CellWidth::U8 => Box::new(Tape::<u8>::new(config)),
+            CellWidth::U16 => Box::new(Tape::<u16>::new(config)),
+            CellWidth::U32 => Box::new(Tape::<u32>::new(config)),
+            CellWidth::U64 => Box::new(Tape::<u64>::new(config)),
+        };
+        Ok(Self {
+            ops,
+            ip: 0,
+            tape,
+            // reversed so that the next byte can be popped off the end
+            inline_input: inline_input.iter().rev().copied().collect(),
+            eof: config.eof,
+            budget: Budget::new(config.max_steps, None),
+            steps: 0,
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeSet::new(),
+            input,
+            output,
+        })
+    }
+
+    /// Source offset of the command to be executed next, or `None` if the program has finished.
+    pub fn position(&self) -> Option<usize> {
+        self.ops.get(self.ip).map(|&(pos, _)| pos)
+    }
+
+    /// Whether the program has finished.
+    pub fn is_finished(&self) -> bool {
+        self.ip >= self.ops.len()
+    }
+
+    /// Number of commands executed so far.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Position of the pointer on the tape.
+    pub fn pointer(&self) -> usize {
+        self.tape.ptr()
+    }
+
+    /// Current number of cells on the tape.
+    pub fn tape_len(&self) -> usize {
+        self.tape.len()
+    }
+
+    /// Value of the cell at an absolute position, or `None` if it is past the end of the tape.
+    pub fn cell(&self, index: usize) -> Option<u64> {
+        self.tape.cell(index)
+    }
+
+    /// Formats the cells within `radius` of the pointer, in the same format as the `#` tape dump.
+    pub fn snapshot(&self, radius: usize) -> String {
+        format_cells(self.tape.ptr(), radius, self.tape.len(), |index| {
+            self.tape.cell(index).expect("index is within the tape")
+        })
+    }
+
+    /// Output written by the program so far.
+    pub fn output(&self) -> &O {
+        &self.output
+    }
+
+    /// Sets a breakpoint before the command at the source offset.
+    ///
+    /// Returns `false` if there was already a breakpoint there. Offsets that are not commands
+    /// are accepted but never reached.
+    pub fn add_breakpoint(&mut self, offset: usize) -> bool {
+        self.breakpoints.insert(offset)
+    }
+
+    /// Removes the breakpoint at the source offset, returning whether there was one.
+    pub fn remove_breakpoint(&mut self, offset: usize) -> bool {
+        self.breakpoints.remove(&offset)
+    }
+
+    /// Source offsets of all breakpoints, in ascending order.
+    pub fn breakpoints(&self) -> impl Iterator<Item = usize> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    /// Stops execution whenever the cell at the absolute position changes.
+    ///
+    /// Returns `false` if the cell was already watched.
+    pub fn add_watchpoint(&mut self, cell: usize) -> bool {
+        self.watchpoints.insert(cell)
+    }
+
+    /// Stops watching the cell, returning whether it was watched.
+    pub fn remove_watchpoint(&mut self, cell: usize) -> bool {
+        self.watchpoints.remove(&cell)
+    }
+
+    /// Positions of all watched cells, in ascending order.
+    pub fn watchpoints(&self) -> impl Iterator<Item = usize> + '_ {
+        self.watchpoints.iter().copied()
+    }
+
+    /// Executes a single command.
+    ///
+    /// If the command fails, the debugger stays before it, so the same error is returned when
+    /// stepping again.
+    pub fn step(&mut self) -> Result<StopReason, Error> {
+        let Some(&(pos, op)) = self.ops.get(self.ip) else {
+            return Ok(StopReason::Finished);
+        };
+        self.budget.spend(1)?;
+        let ptr = self.tape.ptr();
+        let watched = match op {
+            Op::Inc | Op::Dec | Op::Getc if self.watchpoints.contains(&ptr) => self.tape.cell(ptr),
+            _ => None,
+        };
+        let mut next = self.ip + 1;
+        match op {
+            Op::Inc => self.tape.add(1)?,
+            Op::Dec => self.tape.add(-1)?,
+            Op::Left => self.tape.shift(-1)?,
+            Op::Right => self.tape.shift(1)?,
+            Op::Getc => match self.next_byte()? {
+                Some(byte) => self.tape.store(byte as u64)?,
+                None => match self.eof {
+                    Eof::Unchanged => (),
+                    Eof::Zero => self.tape.store(0)?,
+                    Eof::MinusOne => self.tape.store(u64::MAX)?,
+                    Eof::Error => return Err(Error::UnexpectedEof),
+                },
+            },
+            Op::Putc => {
+                let value = self.tape.cell(ptr).expect("pointer is on the tape");
+                putc(&mut self.output, value as u8)?;
+                self.output.flush()?;
+            }
+            Op::Open(close) => {
+                if self.tape.is_zero() {
+                    next = close + 1;
+                }
+            }
+            Op::Close(open) => {
+                if !self.tape.is_zero() {
+                    next = open + 1;
+                }
+            }
+            Op::Debug => (),
+        }
+        self.ip = next;
+        self.steps += 1;
+        if let Some(old) = watched {
+            let new = self.tape.cell(ptr).expect("pointer is on the tape");
+            if new != old {
+                return Ok(StopReason::Watchpoint {
+                    cell: ptr,
+                    old,
+                    new,
+                });
+            }
+        }
+        Ok(match op {
+            Op::Debug => StopReason::DebugChar(pos),
+            _ if self.is_finished() => StopReason::Finished,
+            _ => StopReason::Step,
+        })
+    }
+
+    /// Reads the next input byte, starting with the input after `!`.
+    fn next_byte(&mut self) -> Result<Option<u8>, Error> {
+        match self.inline_input.pop() {
+            Some(byte) => Ok(Some(byte)),
+            None => getc(&mut self.input),
+        }
+    }
+
+    /// Runs until a breakpoint, a `#` command, a watched cell change, or the end of the program.
+    ///
+    /// At least one command is executed, so resuming from a breakpoint moves past it.
+    pub fn resume(&mut self) -> Result<StopReason, Error> {
+        loop {
+            match self.step()? {
+                StopReason::Step => (),
+                reason => return Ok(reason),
+            }
+            if let Some(pos) = self.position() {
+                if self.breakpoints.contains(&pos) {
+                    return Ok(StopReason::Breakpoint(pos));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_debugger() {
+        let code = "++[>+++<-]#>.";
+        let mut output: Vec<u8> = vec![];
+        let mut debugger = Debugger::new(code, &Config::default(), &b""[..], &mut output).unwrap();
+        assert_eq!(debugger.position(), Some(0));
+        assert_eq!(debugger.step().unwrap(), StopReason::Step);
+        assert_eq!(debugger.step().unwrap(), StopReason::Step);
+        assert_eq!(debugger.cell(0), Some(2));
+
+        // stops before the breakpoint on every iteration
+        assert!(debugger.add_breakpoint(3));
+        assert_eq!(debugger.resume().unwrap(), StopReason::Breakpoint(3));
+        assert_eq!(debugger.pointer(), 0);
+        assert_eq!(debugger.resume().unwrap(), StopReason::Breakpoint(3));
+        assert_eq!(debugger.cell(1), Some(3));
+        assert!(debugger.remove_breakpoint(3));
+
+        assert!(debugger.add_watchpoint(0));
+        assert_eq!(
+            debugger.resume().unwrap(),
+            StopReason::Watchpoint {
+                cell: 0,
+                old: 1,
+                new: 0
+            }
+        );
+        assert_eq!(debugger.resume().unwrap(), StopReason::DebugChar(10));
+        assert_eq!(debugger.cell(1), Some(6));
+        assert_eq!(debugger.resume().unwrap(), StopReason::Finished);
+        assert!(debugger.is_finished());
+        assert_eq!(debugger.position(), None);
+        assert_eq!(debugger.steps(), 20);
+        assert_eq!(debugger.step().unwrap(), StopReason::Finished);
+        drop(debugger);
+        assert_eq!(output, b"\x06");
+
+        let config = Config {
+            eof: Eof::Error,
+            ..Config::default()
+        };
+        let mut debugger = Debugger::new("+,", &config, &b""[..], vec![]).unwrap();
+        assert_eq!(debugger.step().unwrap(), StopReason::Step);
+        for _ in 0..2 {
+            assert!(matches!(debugger.step(), Err(Error::UnexpectedEof)));
+            assert_eq!(debugger.position(), Some(1));
+        }
+
+        let res = Debugger::new("[", &Config::default(), &b""[..], vec![]);
+        assert!(matches!(res, Err(Error::SyntaxError(_))));
+    }
+}
@@ -0,0 +1,318 @@
+//! Pretty-printing and minification of Brainfuck source code.
+//!
+//! Both keep the behavior of the program under the given [`Config`]: [`pretty`] only changes
+//! whitespace, and [`minify`] only removes what can never make a difference. With
+//! [`Dialect::inline_input`](super::Dialect::inline_input), the input after `!` is kept as-is.
+
+use super::{into_basic_blocks, is_command, split_inline_input, Config, Error, Overflow, TapeKind};
+
+/// Indentation of each loop level.
+const INDENT: &str = "    ";
+
+/// Innermost loops without comments up to this many characters stay on one line.
+const INLINE_LOOP_LEN: usize = 16;
+
+/// Pretty-prints a Brainfuck program.
+///
+/// Loops are laid out with `[` and `]` on their own lines, except for comments following them,
+/// and the body indented. Short innermost loops such as `[->+<]` stay inline. Comments are
+/// kept with leading and trailing whitespace trimmed, and line breaks between commands are
+/// preserved, with runs of blank lines collapsed into one.
+pub fn pretty(source: &str, config: &Config) -> Result<String, Error> {
+    let (code, inline_input) = split_inline_input(source, config);
+    into_basic_blocks(code, config)?;
+    let mut printer = Printer::default();
+    let mut chars = code.char_indices().peekable();
+    while let Some((pos, c)) = chars.next() {
+        if !is_command(c, config) {
+            // a comment extends up to the next command
+            let mut end = code.len();
+            while let Some(&(next, c)) = chars.peek() {
+                if is_command(c, config) {
+                    end = next;
+                    break;
+                }
+                chars.next();
+            }
+            printer.comment(&code[pos..end]);
+            continue;
+        }
+        match c {
+            '[' => match inline_loop(&code[pos..], config) {
+                Some(len) => {
+                    printer.code(&code[pos..pos + len]);
+                    while chars.next_if(|&(next, _)| next < pos + len).is_some() {}
+                }
+                None => printer.open(),
+            },
+            ']' => printer.close(),
+            _ => printer.code(&code[pos..pos + 1]),
+        }
+    }
+    printer.flush();
+    let mut out = printer.out;
+    out.truncate(out.trim_end().len());
+    out.push('\n');
+    if config.dialect.inline_input && code.len() < source.len() {
+        out.push('!');
+        out.push_str(&String::from_utf8_lossy(inline_input));
+    }
+    Ok(out)
+}
+
+/// If `code` starts with an innermost loop that is short and has no comments, returns its length.
+fn inline_loop(code: &str, config: &Config) -> Option<usize> {
+    for (pos, c) in code.char_indices().skip(1).take(INLINE_LOOP_LEN - 1) {
+        match c {
+            ']' => return Some(pos + 1),
+            '[' => return None,
+            c if !is_command(c, config) => return None,
+            _ => (),
+        }
+    }
+    None
+}
+
+/// Lays out lines of code and comments.
+#[derive(Default)]
+struct Printer {
+    out: String,
+    /// The line being built, without indentation.
+    line: String,
+    /// Loop depth of the line being built.
+    line_depth: usize,
+    /// Whether the line ends with a comment.
+    after_comment: bool,
+    /// Whether the line is a bracket, which may only be followed by a comment.
+    after_bracket: bool,
+    /// Number of line breaks in the source since the last printed line.
+    newlines: usize,
+    depth: usize,
+}
+
+impl Printer {
+    fn code(&mut self, code: &str) {
+        if self.after_bracket {
+            self.flush();
+        }
+        if self.line.is_empty() {
+            self.line_depth = self.depth;
+        } else if self.after_comment {
+            self.line.push(' ');
+        }
+        self.line.push_str(code);
+        self.after_comment = false;
+    }
+
+    fn comment(&mut self, comment: &str) {
+        for (i, part) in comment.split('\n').enumerate() {
+            if i > 0 {
+                self.end_line();
+            }
+            let part = part.trim();
+            if part.is_empty() {
+                continue;
+            }
+            if self.line.is_empty() {
+                self.line_depth = self.depth;
+            } else {
+                self.line.push(' ');
+            }
+            self.line.push_str(part);
+            self.after_comment = true;
+        }
+    }
+
+    fn open(&mut self) {
+        self.flush();
+        self.code("[");
+        self.after_bracket = true;
+        self.depth += 1;
+    }
+
+    fn close(&mut self) {
+        self.flush();
+        self.depth -= 1;
+        self.code("]");
+        self.after_bracket = true;
+    }
+
+    /// Handles a line break in the source.
+    fn end_line(&mut self) {
+        if !self.line.is_empty() {
+            self.flush();
+            self.newlines = 1;
+            return;
+        }
+        self.newlines += 1;
+        if self.newlines == 2 && !self.out.is_empty() {
+            self.out.push('\n');
+        }
+    }
+
+    fn flush(&mut self) {
+        if self.line.is_empty() {
+            return;
+        }
+        for _ in 0..self.line_depth {
+            self.out.push_str(INDENT);
+        }
+        self.out.push_str(&self.line);
+        self.out.push('\n');
+        self.line.clear();
+        self.after_comment = false;
+        self.after_bracket = false;
+        self.newlines = 0;
+    }
+}
+
+/// Removes everything from a Brainfuck program that cannot affect its behavior.
+///
+/// Comments are removed, and so are adjacent pairs of commands that cancel each other out,
+/// and loops that can never be entered because they start right after another loop or at the
+/// start of the program. `+-` and `-+` are only removed under [`Overflow::Wrap`], `<>` only on
+/// a cyclic tape, and `><` on a cyclic or growable tape, since these may otherwise stop the
+/// program with an error.
+///
+/// The result is a single line with no trailing newline, followed by the input after `!` if
+/// [`Dialect::inline_input`](super::Dialect::inline_input) is enabled.
+pub fn minify(source: &str, config: &Config) -> Result<String, Error> {
+    let (code, inline_input) = split_inline_input(source, config);
+    into_basic_blocks(code, config)?;
+    let commands: Vec<char> = code.chars().filter(|&c| is_command(c, config)).collect();
+    let mut matching = vec![0; commands.len()];
+    let mut stack = vec![];
+    for (i, &c) in commands.iter().enumerate() {
+        match c {
+            '[' => stack.push(i),
+            ']' => {
+                let open = stack.pop().expect("brackets are balanced");
+                matching[open] = i;
+            }
+            _ => (),
+        }
+    }
+
+    let wrap = config.overflow == Overflow::Wrap;
+    let cyclic = config.tape == TapeKind::Cyclic;
+    let growable = config.tape == TapeKind::Growable;
+    let mut out: Vec<char> = vec![];
+    let mut i = 0;
+    while i < commands.len() {
+        let c = commands[i];
+        // the current cell is always zero here, since everything before is either nothing
+        // or cancelled out, or ends with a loop
+        if c == '[' && matches!(out.last(), None | Some(']')) {
+            i = matching[i] + 1;
+            continue;
+        }
+        match (out.last(), c) {
+            (Some('+'), '-') | (Some('-'), '+') if wrap => {
+                out.pop();
+            }
+            (Some('<'), '>') if cyclic => {
+                out.pop();
+            }
+            (Some('>'), '<') if cyclic || growable => {
+                out.pop();
+            }
+            _ => out.push(c),
+        }
+        i += 1;
+    }
+
+    let mut out: String = out.into_iter().collect();
+    if config.dialect.inline_input && code.len() < source.len() {
+        out.push('!');
+        out.push_str(&String::from_utf8_lossy(inline_input));
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::brainfuck::{run_with_config, Dialect};
+
+    #[test]
+    fn test_pretty() {
+        let code = "set up\n++++++++[>++++[>++>+++<<-]>+<<-]  count\n\n\n\n>>.  print\n";
+        let expected = "\
+set up
+++++++++
+[
+    >++++[>++>+++<<-]>+<<-
+] count
+
+>>. print
+";
+        let formatted = pretty(code, &Config::default()).unwrap();
+        assert_eq!(formatted, expected);
+        assert_eq!(pretty(&formatted, &Config::default()).unwrap(), formatted);
+
+        // comments can follow brackets on the same line
+        let code = "+++[->+<]>[ loop\n-[-]]";
+        assert_eq!(
+            pretty(code, &Config::default()).unwrap(),
+            "+++[->+<]>\n[ loop\n    -[-]\n]\n"
+        );
+
+        let config = Config {
+            dialect: Dialect {
+                tape_dump: false,
+                inline_input: true,
+            },
+            ..Config::default()
+        };
+        assert_eq!(pretty(",[.,]!a b\n", &config).unwrap(), ",[.,]\n!a b\n");
+        assert!(matches!(
+            pretty("]", &Config::default()),
+            Err(Error::SyntaxError(_))
+        ));
+    }
+
+    #[test]
+    fn test_minify() {
+        let config = Config::default();
+        assert_eq!(minify("a+-b<>c><d", &config).unwrap(), "");
+        assert_eq!(minify("++--+>+<<>", &config).unwrap(), "+>+<");
+        assert_eq!(
+            minify("[comment] +[-][dead][dead]>+-[dead]<.", &config).unwrap(),
+            "+[-]>[]<."
+        );
+        assert_eq!(minify("+[-]+-[dead]", &config).unwrap(), "+[-]");
+
+        let config = Config {
+            overflow: Overflow::Saturate,
+            tape: TapeKind::Growable,
+            ..Config::default()
+        };
+        assert_eq!(minify("+-<>><", &config).unwrap(), "+-<>");
+        let config = Config {
+            overflow: Overflow::Error,
+            tape: TapeKind::Bounded,
+            ..Config::default()
+        };
+        assert_eq!(minify("+-<>><", &config).unwrap(), "+-<>><");
+
+        // same behavior on a real program
+        let code = "+[[-<]-[->]<-]<.<<<<.>>>>-.<<-.<.>>.<<<+++.>>>---.<++.";
+        let minified = minify(&format!("[{}]comment{}<>", code, code), &config).unwrap();
+        assert_eq!(minified, format!("{}<>", code));
+        let config = Config::default();
+        let minified = minify(&format!("[{}]comment{}<>", code, code), &config).unwrap();
+        assert_eq!(minified, code);
+        let mut stdout: Vec<u8> = vec![];
+        assert!(run_with_config(&minified, &mut &b""[..], &mut stdout, &config).is_ok());
+        assert_eq!(stdout, b"brainfuck");
+
+        let config = Config {
+            dialect: Dialect {
+                tape_dump: true,
+                inline_input: true,
+            },
+            ..Config::default()
+        };
+        assert_eq!(minify("+#-!+-", &config).unwrap(), "+#-!+-");
+    }
+}
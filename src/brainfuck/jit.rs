@@ -0,0 +1,505 @@
+//! Native code generation for x86-64 Linux, enabled by the `jit` feature.
+//!
+//! Each basic block is translated into machine code in an executable `mmap`ed buffer.
+//! I/O instructions call back into Rust, so the JIT works with any `BufRead` and `Write`.
+//!
+//! Only the fast path is supported: wrapping cells and a cyclic tape whose length is a power
+//! of two, with no execution limits and no `#` tape dumps. [`compile`] returns `None` for
+//! anything else, and the program falls back to the bytecode interpreter.
+//!
+//! Register usage inside the generated code:
+//!
+//! - `rbx`: base address of the tape
+//! - `r12`: the pointer, as a cell index
+//! - `r13`: tape length minus one, used as a mask to wrap the pointer around
+//! - `r14`: pointer to the [`Context`] passed to the callbacks
+
+use std::any::Any;
+use std::io::{BufRead, Write};
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+use std::{fmt, ptr};
+
+use super::tape::Cell;
+use super::{getc, putc, BasicBlock, CellWidth, Cmd, Config, Eof, Error, Overflow, TapeKind};
+
+/// Executable machine code of a whole program.
+pub(crate) struct Code {
+    memory: *mut u8,
+    len: usize,
+}
+
+// The code is never modified after it is made executable.
+unsafe impl Send for Code {}
+unsafe impl Sync for Code {}
+
+impl fmt::Debug for Code {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Code")
+            .field("memory", &self.memory)
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+impl Drop for Code {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.memory as *mut libc::c_void, self.len);
+        }
+    }
+}
+
+/// Entry point of the generated code. Returns 0 on success, nonzero if a callback failed.
+type EntryPoint = unsafe extern "sysv64" fn(tape: *mut u8, ctx: *mut Context) -> u64;
+
+/// State shared with the I/O callbacks during a run.
+struct Context<'a> {
+    input: &'a mut dyn BufRead,
+    output: &'a mut dyn Write,
+    eof: Eof,
+    error: Option<Error>,
+    panic: Option<Box<dyn Any + Send>>,
+}
+
+impl Context<'_> {
+    /// Runs a callback body, recording any error or panic so that the generated code can
+    /// return early. Unwinding through the generated code is not allowed.
+    fn guard(&mut self, f: impl FnOnce(&mut Self) -> Result<(), Error>) -> u64 {
+        match catch_unwind(AssertUnwindSafe(|| f(self))) {
+            Ok(Ok(())) => 0,
+            Ok(Err(error)) => {
+                self.error = Some(error);
+                1
+            }
+            Err(payload) => {
+                self.panic = Some(payload);
+                1
+            }
+        }
+    }
+}
+
+unsafe extern "sysv64" fn getc_callback<C: Cell>(ctx: *mut Context, cell: *mut C) -> u64 {
+    let ctx = &mut *ctx;
+    ctx.guard(|ctx| {
+        match getc(&mut ctx.input)? {
+            Some(byte) => *cell = C::from_u64(byte as u64),
+            None => match ctx.eof {
+                Eof::Unchanged => (),
+                Eof::Zero => *cell = C::default(),
+                Eof::MinusOne => *cell = C::from_u64(u64::MAX),
+                Eof::Error => return Err(Error::UnexpectedEof),
+            },
+        }
+        Ok(())
+    })
+}
+
+unsafe extern "sysv64" fn putc_callback<C: Cell>(ctx: *mut Context, cell: *mut C) -> u64 {
+    let ctx = &mut *ctx;
+    ctx.guard(|ctx| putc(&mut ctx.output, (*cell).to_u64() as u8))
+}
+
+impl Code {
+    /// Runs the code on a fresh tape.
+    pub(crate) fn run<I: BufRead, O: Write>(
+        &self,
+        input: &mut I,
+        output: &mut O,
+        config: &Config,
+    ) -> Result<(), Error> {
+        match config.cell_width {
+            CellWidth::U8 => self.run_with::<u8>(input, output, config),
+            CellWidth::U16 => self.run_with::<u16>(input, output, config),
+            CellWidth::U32 => self.run_with::<u32>(input, output, config),
+            CellWidth::U64 => self.run_with::<u64>(input, output, config),
+        }
+    }
+
+    fn run_with<C: Cell>(
+        &self,
+        input: &mut dyn BufRead,
+        output: &mut dyn Write,
+        config: &Config,
+    ) -> Result<(), Error> {
+        let mut tape = vec![C::default(); config.tape_len];
+        let mut ctx = Context {
+            input,
+            output,
+            eof: config.eof,
+            error: None,
+            panic: None,
+        };
+        let entry: EntryPoint = unsafe { std::mem::transmute(self.memory) };
+        let status = unsafe { entry(tape.as_mut_ptr() as *mut u8, &mut ctx) };
+        if let Some(payload) = ctx.panic {
+            resume_unwind(payload);
+        }
+        match ctx.error {
+            Some(error) => Err(error),
+            None => {
+                debug_assert_eq!(status, 0);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Compiles the program into machine code, or returns `None` if the configuration is not
+/// supported by the JIT or executable memory cannot be allocated.
+pub(crate) fn compile(basic_blocks: &[BasicBlock], config: &Config) -> Option<Code> {
+    if config.overflow != Overflow::Wrap
+        || config.tape != TapeKind::Cyclic
+        || !config.tape_len.is_power_of_two()
+        || config.max_steps.is_some()
+        || config.timeout.is_some()
+    {
+        return None;
+    }
+    let bytes = match config.cell_width {
+        CellWidth::U8 => Assembler::<u8>::new(config).assemble(basic_blocks)?,
+        CellWidth::U16 => Assembler::<u16>::new(config).assemble(basic_blocks)?,
+        CellWidth::U32 => Assembler::<u32>::new(config).assemble(basic_blocks)?,
+        CellWidth::U64 => Assembler::<u64>::new(config).assemble(basic_blocks)?,
+    };
+    unsafe {
+        let len = bytes.len();
+        let memory = libc::mmap(
+            ptr::null_mut(),
+            len,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+            -1,
+            0,
+        );
+        if memory == libc::MAP_FAILED {
+            return None;
+        }
+        let code = Code {
+            memory: memory as *mut u8,
+            len,
+        };
+        ptr::copy_nonoverlapping(bytes.as_ptr(), code.memory, len);
+        if libc::mprotect(memory, len, libc::PROT_READ | libc::PROT_EXEC) != 0 {
+            return None;
+        }
+        Some(code)
+    }
+}
+
+/// Jump targets inside the generated code.
+#[derive(Clone, Copy)]
+enum Label {
+    Block(usize),
+    Success,
+    Failure,
+}
+
+const RAX: u8 = 0;
+const RCX: u8 = 1;
+const RSI: u8 = 6;
+const R12: u8 = 12;
+
+/// Emits machine code for cells of type `C`.
+struct Assembler<C> {
+    code: Vec<u8>,
+    /// Positions of rel32 operands and their targets.
+    fixups: Vec<(usize, Label)>,
+    mask: u64,
+    width: CellWidth,
+    cell: std::marker::PhantomData<C>,
+}
+
+impl<C: Cell> Assembler<C> {
+    fn new(config: &Config) -> Self {
+        Self {
+            code: vec![],
+            fixups: vec![],
+            mask: config.tape_len as u64 - 1,
+            width: config.cell_width,
+            cell: std::marker::PhantomData,
+        }
+    }
+
+    fn assemble(mut self, basic_blocks: &[BasicBlock]) -> Option<Vec<u8>> {
+        self.prologue();
+        let mut block_starts = Vec::with_capacity(basic_blocks.len());
+        for bb in basic_blocks {
+            block_starts.push(self.code.len());
+            for &instr in &bb.instrs {
+                self.instr(instr)?;
+            }
+            let target = |next: Option<usize>| next.map_or(Label::Success, Label::Block);
+            // cmp cell, 0; jne jnz; jmp jz
+            self.cell_op(&[0x80], &[0x83], 7, R12);
+            self.code.push(0);
+            self.jump(&[0x0F, 0x85], target(bb.jnz));
+            self.jump(&[0xE9], target(bb.jz));
+        }
+        let success = self.code.len();
+        self.code.extend_from_slice(&[0x31, 0xC0]); // xor eax, eax
+        self.epilogue();
+        let failure = self.code.len();
+        self.code.extend_from_slice(&[0xB8, 1, 0, 0, 0]); // mov eax, 1
+        self.epilogue();
+        for &(pos, label) in &self.fixups {
+            let target = match label {
+                Label::Block(bb) => block_starts[bb],
+                Label::Success => success,
+                Label::Failure => failure,
+            };
+            let rel = target as i64 - (pos as i64 + 4);
+            self.code[pos..pos + 4].copy_from_slice(&(rel as i32).to_le_bytes());
+        }
+        Some(self.code)
+    }
+
+    fn prologue(&mut self) {
+        self.code.extend_from_slice(&[
+            0x53, // push rbx
+            0x41, 0x54, // push r12
+            0x41, 0x55, // push r13
+            0x41, 0x56, // push r14
+            0x41, 0x57, // push r15
+            0x48, 0x89, 0xFB, // mov rbx, rdi
+            0x49, 0x89, 0xF6, // mov r14, rsi
+            0x45, 0x31, 0xE4, // xor r12d, r12d
+            0x49, 0xBD, // mov r13, imm64
+        ]);
+        self.code.extend_from_slice(&self.mask.to_le_bytes());
+    }
+
+    fn epilogue(&mut self) {
+        self.code.extend_from_slice(&[
+            0x41, 0x5F, // pop r15
+            0x41, 0x5E, // pop r14
+            0x41, 0x5D, // pop r13
+            0x41, 0x5C, // pop r12
+            0x5B, // pop rbx
+            0xC3, // ret
+        ]);
+    }
+
+    fn jump(&mut self, opcode: &[u8], label: Label) {
+        self.code.extend_from_slice(opcode);
+        self.fixups.push((self.code.len(), label));
+        self.code.extend_from_slice(&[0; 4]);
+    }
+
+    fn scale(&self) -> u8 {
+        match self.width {
+            CellWidth::U8 => 0,
+            CellWidth::U16 => 1,
+            CellWidth::U32 => 2,
+            CellWidth::U64 => 3,
+        }
+    }
+
+    /// Emits an instruction operating on the cell `[rbx + index * size]`.
+    ///
+    /// `op8` is the opcode for 8-bit cells and `op` for wider cells; `reg` is the ModRM reg
+    /// field, either a register or an opcode extension.
+    fn cell_op(&mut self, op8: &[u8], op: &[u8], reg: u8, index: u8) {
+        if self.width == CellWidth::U8 {
+            self.mem_op(false, false, op8, reg, index);
+        } else {
+            let wide = self.width == CellWidth::U64;
+            self.mem_op(self.width == CellWidth::U16, wide, op, reg, index);
+        }
+    }
+
+    /// Emits an instruction with a `[rbx + index * size]` memory operand.
+    fn mem_op(&mut self, prefix16: bool, rex_w: bool, opcode: &[u8], reg: u8, index: u8) {
+        if prefix16 {
+            self.code.push(0x66);
+        }
+        let rex = 0x40 | (rex_w as u8) << 3 | (reg >> 3) << 2 | (index >> 3) << 1;
+        if rex != 0x40 {
+            self.code.push(rex);
+        }
+        self.code.extend_from_slice(opcode);
+        self.code.push((reg & 7) << 3 | 0b100);
+        self.code.push(self.scale() << 6 | (index & 7) << 3 | 3);
+    }
+
+    /// Puts the index of the cell at `offset` into a register and returns that register.
+    fn index(&mut self, offset: isize) -> Option<u8> {
+        if offset == 0 {
+            return Some(R12);
+        }
+        let offset = i32::try_from(offset).ok()?;
+        self.code.extend_from_slice(&[0x49, 0x8D, 0x84, 0x24]); // lea rax, [r12 + disp32]
+        self.code.extend_from_slice(&offset.to_le_bytes());
+        self.code.extend_from_slice(&[0x4C, 0x21, 0xE8]); // and rax, r13
+        Some(RAX)
+    }
+
+    /// Emits an immediate operand of the cell width (at most 32 bits).
+    fn cell_imm(&mut self, value: u64) {
+        match self.width {
+            CellWidth::U8 => self.code.push(value as u8),
+            CellWidth::U16 => self.code.extend_from_slice(&(value as u16).to_le_bytes()),
+            CellWidth::U32 | CellWidth::U64 => {
+                self.code.extend_from_slice(&(value as u32).to_le_bytes())
+            }
+        }
+    }
+
+    /// Adds the value to the cell at `index`, wrapping around.
+    fn add_imm(&mut self, index: u8, delta: i64) {
+        let fits_imm32 = i32::try_from(delta).is_ok();
+        if self.width == CellWidth::U64 && !fits_imm32 {
+            self.code.extend_from_slice(&[0x48, 0xB9]); // mov rcx, imm64
+            self.code.extend_from_slice(&delta.to_le_bytes());
+            self.cell_op(&[0x00], &[0x01], RCX, index); // add cell, rcx
+        } else {
+            self.cell_op(&[0x80], &[0x81], 0, index); // add cell, imm
+            self.cell_imm(delta as u64);
+        }
+    }
+
+    fn call(&mut self, index: u8, callback: usize) {
+        self.mem_op(false, true, &[0x8D], RSI, index); // lea rsi, cell
+        self.code.extend_from_slice(&[0x4C, 0x89, 0xF7]); // mov rdi, r14
+        self.code.extend_from_slice(&[0x48, 0xB8]); // mov rax, imm64
+        self.code
+            .extend_from_slice(&(callback as u64).to_le_bytes());
+        self.code.extend_from_slice(&[0xFF, 0xD0]); // call rax
+        self.code.extend_from_slice(&[0x48, 0x85, 0xC0]); // test rax, rax
+        self.jump(&[0x0F, 0x85], Label::Failure); // jnz failure
+    }
+
+    fn shift(&mut self, delta: isize) -> Option<()> {
+        let delta = i32::try_from(delta).ok()?;
+        self.code.extend_from_slice(&[0x49, 0x81, 0xC4]); // add r12, imm32
+        self.code.extend_from_slice(&delta.to_le_bytes());
+        self.code.extend_from_slice(&[0x4D, 0x21, 0xEC]); // and r12, r13
+        Some(())
+    }
+
+    fn instr(&mut self, instr: Cmd) -> Option<()> {
+        match instr {
+            Cmd::Add(offset, delta) => {
+                let index = self.index(offset)?;
+                self.add_imm(index, delta);
+            }
+            Cmd::Move(delta) => self.shift(delta)?,
+            Cmd::Getc(offset) => {
+                let index = self.index(offset)?;
+                self.call(index, getc_callback::<C> as *const () as usize);
+            }
+            Cmd::Putc(offset) => {
+                let index = self.index(offset)?;
+                self.call(index, putc_callback::<C> as *const () as usize);
+            }
+            // tape dumps are rare enough to be left to the interpreter
+            Cmd::Dump(_) => return None,
+            Cmd::SetZero(offset) => {
+                let index = self.index(offset)?;
+                self.cell_op(&[0xC6], &[0xC7], 0, index); // mov cell, 0
+                self.cell_imm(0);
+            }
+            Cmd::MulAdd { offset, factor } => {
+                // load the current cell into rcx, zero-extended
+                match self.width {
+                    CellWidth::U8 => self.mem_op(false, false, &[0x0F, 0xB6], RCX, R12),
+                    CellWidth::U16 => self.mem_op(false, false, &[0x0F, 0xB7], RCX, R12),
+                    CellWidth::U32 => self.mem_op(false, false, &[0x8B], RCX, R12),
+                    CellWidth::U64 => self.mem_op(false, true, &[0x8B], RCX, R12),
+                }
+                if let Ok(factor) = i32::try_from(factor) {
+                    self.code.extend_from_slice(&[0x48, 0x69, 0xC9]); // imul rcx, rcx, imm32
+                    self.code.extend_from_slice(&factor.to_le_bytes());
+                } else {
+                    self.code.extend_from_slice(&[0x48, 0xBA]); // mov rdx, imm64
+                    self.code.extend_from_slice(&factor.to_le_bytes());
+                    self.code.extend_from_slice(&[0x48, 0x0F, 0xAF, 0xCA]); // imul rcx, rdx
+                }
+                let index = self.index(offset)?;
+                self.cell_op(&[0x00], &[0x01], RCX, index); // add cell, rcx
+            }
+            Cmd::ScanRight(stride) | Cmd::ScanLeft(stride) => {
+                let stride = isize::try_from(stride).ok()?;
+                let delta = if let Cmd::ScanLeft(_) = instr {
+                    -stride
+                } else {
+                    stride
+                };
+                // loop: cmp cell, 0; je done; add r12, delta; and r12, r13; jmp loop
+                let start = self.code.len();
+                self.cell_op(&[0x80], &[0x83], 7, R12);
+                self.code.push(0);
+                self.code.extend_from_slice(&[0x74, 0]); // je rel8, patched below
+                let je_pos = self.code.len() - 1;
+                self.shift(delta)?;
+                let back = start as i64 - (self.code.len() as i64 + 5);
+                self.code.push(0xE9); // jmp rel32
+                self.code.extend_from_slice(&(back as i32).to_le_bytes());
+                self.code[je_pos] = (self.code.len() - je_pos - 1) as u8;
+            }
+        }
+        Some(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+
+    #[test]
+    fn test_jit() {
+        // from https://codegolf.stackexchange.com/a/108062/78410
+        let code = "+[[-<]-[->]<-]<.<<<<.>>>>-.<<-.<.>>.<<<+++.>>>---.<++.";
+        let program = Program::compile(code).unwrap();
+        assert!(program.jit.is_some());
+        let mut stdout: Vec<u8> = vec![];
+        assert!(program.run(&mut &b""[..], &mut stdout).is_ok());
+        assert_eq!(stdout, b"brainfuck");
+
+        // the last program stops on EOF only when it reads -1
+        let codes = [
+            (
+                Eof::Unchanged,
+                ">,[>,]<[.<]++++++++[>++++++++<-]>+.[-]++++[<+++>-]<[>,.<-]",
+            ),
+            (
+                Eof::Zero,
+                ">,[>,]<[.<]++++++++[>++++++++<-]>+.[-]++++[<+++>-]<[>,.<-]",
+            ),
+            (Eof::MinusOne, ",+[-.>+++[<++++>-]<.[-],+]"),
+        ];
+        for cell_width in [
+            CellWidth::U8,
+            CellWidth::U16,
+            CellWidth::U32,
+            CellWidth::U64,
+        ] {
+            for (eof, code) in codes {
+                let config = Config {
+                    cell_width,
+                    eof,
+                    ..Config::default()
+                };
+                let jit = Program::compile_with_config(code, &config).unwrap();
+                assert!(jit.jit.is_some());
+                let interpreted = Program {
+                    jit: None,
+                    ..jit.clone()
+                };
+                let mut expected: Vec<u8> = vec![];
+                let mut actual: Vec<u8> = vec![];
+                assert!(interpreted.run(&mut &b"esobox"[..], &mut expected).is_ok());
+                assert!(jit.run(&mut &b"esobox"[..], &mut actual).is_ok());
+                assert_eq!(actual, expected);
+            }
+        }
+
+        let config = Config {
+            eof: Eof::Error,
+            ..Config::default()
+        };
+        let program = Program::compile_with_config(",,", &config).unwrap();
+        let res = program.run(&mut &b"a"[..], &mut vec![]);
+        assert!(matches!(res, Err(Error::UnexpectedEof)));
+    }
+}
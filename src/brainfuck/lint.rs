@@ -0,0 +1,449 @@
+//! Static checks for common mistakes in Brainfuck programs.
+//!
+//! The checks follow the values of cells through straight-line code, starting from the
+//! all-zero tape at the start of the program and the zero cell after each loop. Anything that
+//! depends on input or on the number of loop iterations is treated as unknown, so a lint is
+//! only reported when the mistake is certain for the given [`Config`].
+
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+use std::ops::Range;
+
+use super::{
+    into_basic_blocks, is_command, line_column, split_inline_input, CellWidth, Config, Error,
+    Overflow, TapeKind,
+};
+
+/// The kind of mistake found by [`lint`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LintKind {
+    /// A loop that is never entered because the current cell is always zero, such as a loop
+    /// right at the start of the program or right after another loop.
+    UnreachableLoop,
+    /// `[-]` or `[+]` on a cell that is always zero.
+    RedundantClear,
+    /// Two adjacent commands that cancel each other out, such as `+-` or `<>`. As in
+    /// [`minify`](super::format::minify), `+-` and `-+` are only reported under
+    /// [`Overflow::Wrap`], `<>` only on a cyclic tape, and `><` on a cyclic or growable tape,
+    /// since the pair may otherwise stop the program with an error.
+    CancellingPair,
+    /// `[]` on a cell that is never zero, which loops forever.
+    InfiniteLoop,
+    /// A character next to Brainfuck commands that is a command in another dialect, and is
+    /// ignored here.
+    ForeignCommand,
+}
+
+impl LintKind {
+    /// A short identifier for the kind, used in the output of `esobox lint`.
+    pub fn name(self) -> &'static str {
+        match self {
+            LintKind::UnreachableLoop => "unreachable-loop",
+            LintKind::RedundantClear => "redundant-clear",
+            LintKind::CancellingPair => "cancelling-pair",
+            LintKind::InfiniteLoop => "infinite-loop",
+            LintKind::ForeignCommand => "foreign-command",
+        }
+    }
+}
+
+/// A mistake found by [`lint`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lint {
+    /// The kind of mistake.
+    pub kind: LintKind,
+    /// Byte range of the offending code in the source.
+    pub span: Range<usize>,
+    /// 1-based line number of the start of the span.
+    pub line: usize,
+    /// 1-based column of the start of the span, counted in characters.
+    pub column: usize,
+    /// Human-readable description.
+    pub message: String,
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {} [{}]",
+            self.line,
+            self.column,
+            self.message,
+            self.kind.name()
+        )
+    }
+}
+
+/// Characters used as commands by other Brainfuck dialects, and what they do there.
+const FOREIGN_COMMANDS: &[(char, &str)] = &[
+    ('#', "dumps the tape in some interpreters"),
+    (
+        '!',
+        "separates the code from its input in some interpreters",
+    ),
+    ('@', "ends the program in Extended Brainfuck"),
+    (
+        '$',
+        "stores the cell into the register in Extended Brainfuck",
+    ),
+    ('~', "shifts the cell right in Extended Brainfuck"),
+    ('(', "starts a procedure in pbrain"),
+    (')', "ends a procedure in pbrain"),
+    (':', "calls a procedure in pbrain"),
+];
+
+/// What is known about the value of a cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Value {
+    Known(u64),
+    NonZero,
+    Unknown,
+}
+
+/// What is known about the tape, relative to the pointer at some point.
+struct Knowledge<'a> {
+    config: &'a Config,
+    cells: HashMap<isize, Value>,
+    /// Value of the cells not in `cells`.
+    rest: Value,
+    ptr: isize,
+}
+
+impl<'a> Knowledge<'a> {
+    fn new(config: &'a Config, rest: Value) -> Self {
+        Self {
+            config,
+            cells: HashMap::new(),
+            rest,
+            ptr: 0,
+        }
+    }
+
+    fn get(&self) -> Value {
+        self.cells.get(&self.ptr).copied().unwrap_or(self.rest)
+    }
+
+    fn set(&mut self, value: Value) {
+        self.cells.insert(self.ptr, value);
+    }
+
+    fn shift(&mut self, delta: isize) {
+        self.ptr += delta;
+        if self.config.tape == TapeKind::Cyclic {
+            self.ptr = self.ptr.rem_euclid(self.config.tape_len.max(1) as isize);
+        }
+    }
+
+    fn add(&mut self, delta: i64) {
+        let max = match self.config.cell_width {
+            CellWidth::U8 => u8::MAX as u64,
+            CellWidth::U16 => u16::MAX as u64,
+            CellWidth::U32 => u32::MAX as u64,
+            CellWidth::U64 => u64::MAX,
+        };
+        let Value::Known(value) = self.get() else {
+            self.set(Value::Unknown);
+            return;
+        };
+        let sum = value as i128 + delta as i128;
+        let value = match self.config.overflow {
+            Overflow::Wrap => Value::Known(sum.rem_euclid(max as i128 + 1) as u64),
+            Overflow::Saturate => Value::Known(sum.clamp(0, max as i128) as u64),
+            // the program stops here anyway
+            Overflow::Error if !(0..=max as i128).contains(&sum) => Value::Unknown,
+            Overflow::Error => Value::Known(sum as u64),
+        };
+        self.set(value);
+    }
+}
+
+/// Checks a Brainfuck program for common mistakes.
+///
+/// Returns the lints in source order, or an error if the program does not compile.
+pub fn lint(source: &str, config: &Config) -> Result<Vec<Lint>, Error> {
+    let (code, _) = split_inline_input(source, config);
+    into_basic_blocks(code, config)?;
+    let commands: Vec<(usize, char)> = code
+        .char_indices()
+        .filter(|&(_, c)| is_command(c, config))
+        .collect();
+    let mut matching = vec![0; commands.len()];
+    let mut stack = vec![];
+    for (i, &(_, c)) in commands.iter().enumerate() {
+        match c {
+            '[' => stack.push(i),
+            ']' => {
+                let open = stack.pop().expect("brackets are balanced");
+                matching[open] = i;
+            }
+            _ => (),
+        }
+    }
+
+    let mut lints = vec![];
+    let mut report = |kind, span: Range<usize>, message: String| {
+        let (line, column) = line_column(source, span.start);
+        lints.push(Lint {
+            kind,
+            span,
+            line,
+            column,
+            message,
+        });
+    };
+
+    let mut known = Knowledge::new(config, Value::Known(0));
+    let mut i = 0;
+    while i < commands.len() {
+        let (pos, c) = commands[i];
+        match c {
+            '+' => known.add(1),
+            '-' => known.add(-1),
+            '<' => known.shift(-1),
+            '>' => known.shift(1),
+            ',' => known.set(Value::Unknown),
+            '[' => {
+                let close = matching[i];
+                let end = commands[close].0 + 1;
+                match known.get() {
+                    Value::Known(0) => {
+                        if close == i + 2 && matches!(commands[i + 1].1, '+' | '-') {
+                            report(
+                                LintKind::RedundantClear,
+                                pos..end,
+                                "clearing a cell that is already zero".to_string(),
+                            );
+                        } else {
+                            report(
+                                LintKind::UnreachableLoop,
+                                pos..end,
+                                "loop is never entered because the cell is always zero here"
+                                    .to_string(),
+                            );
+                        }
+                        // the loop is skipped, and everything else stays the same
+                        i = close + 1;
+                        continue;
+                    }
+                    Value::Known(_) | Value::NonZero if close == i + 1 => report(
+                        LintKind::InfiniteLoop,
+                        pos..end,
+                        "empty loop on a cell that is never zero here runs forever".to_string(),
+                    ),
+                    _ => (),
+                }
+                // later iterations may start from any state
+                known = Knowledge::new(config, Value::Unknown);
+                known.set(Value::NonZero);
+            }
+            ']' => {
+                known = Knowledge::new(config, Value::Unknown);
+                known.set(Value::Known(0));
+            }
+            _ => (),
+        }
+        i += 1;
+    }
+
+    let wrap = config.overflow == Overflow::Wrap;
+    let cyclic = config.tape == TapeKind::Cyclic;
+    let growable = config.tape == TapeKind::Growable;
+    let mut i = 0;
+    while i + 1 < commands.len() {
+        let ((pos, a), (next, b)) = (commands[i], commands[i + 1]);
+        let cancelling = match (a, b) {
+            ('+', '-') | ('-', '+') => wrap,
+            ('<', '>') => cyclic,
+            ('>', '<') => cyclic || growable,
+            _ => false,
+        };
+        if cancelling {
+            report(
+                LintKind::CancellingPair,
+                pos..next + 1,
+                format!("`{}` and `{}` cancel each other out", a, b),
+            );
+            i += 2;
+        } else {
+            i += 1;
+        }
+    }
+
+    let bytes = code.as_bytes();
+    for (pos, c) in code.char_indices() {
+        let Some(&(_, meaning)) = FOREIGN_COMMANDS.iter().find(|&&(f, _)| f == c) else {
+            continue;
+        };
+        if is_command(c, config) {
+            continue;
+        }
+        let next_to_command = [pos.checked_sub(1), Some(pos + 1)]
+            .into_iter()
+            .flatten()
+            .filter_map(|i| bytes.get(i))
+            .any(|&b| is_command(b as char, config));
+        if next_to_command {
+            report(
+                LintKind::ForeignCommand,
+                pos..pos + 1,
+                format!("`{}` is ignored, but {}", c, meaning),
+            );
+        }
+    }
+
+    lints.sort_by_key(|lint| (lint.span.start, lint.span.end));
+    Ok(lints)
+}
+
+/// Formats the lints as a JSON array of objects with the fields `kind`, `offset`, `length`,
+/// `line`, `column` and `message`.
+pub fn to_json(lints: &[Lint]) -> String {
+    let mut out = String::from("[");
+    for (i, lint) in lints.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        write!(
+            out,
+            r#"{{"kind":"{}","offset":{},"length":{},"line":{},"column":{},"message":"#,
+            lint.kind.name(),
+            lint.span.start,
+            lint.span.len(),
+            lint.line,
+            lint.column
+        )
+        .expect("writing to a String cannot fail");
+        write_json_string(&mut out, &lint.message);
+        out.push('}');
+    }
+    out.push(']');
+    out
+}
+
+fn write_json_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                write!(out, "\\u{:04x}", c as u32).expect("writing to a String cannot fail")
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::brainfuck::Dialect;
+
+    fn kinds(source: &str, config: &Config) -> Vec<(LintKind, Range<usize>)> {
+        lint(source, config)
+            .unwrap()
+            .into_iter()
+            .map(|lint| (lint.kind, lint.span))
+            .collect()
+    }
+
+    #[test]
+    fn test_lint() {
+        let config = Config::default();
+        assert_eq!(
+            kinds("[comment]>[-]+[-][>]", &config),
+            vec![
+                (LintKind::UnreachableLoop, 0..9),
+                (LintKind::RedundantClear, 10..13),
+                (LintKind::UnreachableLoop, 17..20),
+            ]
+        );
+        assert_eq!(
+            kinds("+[]-[[]>+<-]", &config),
+            vec![
+                (LintKind::InfiniteLoop, 1..3),
+                (LintKind::InfiniteLoop, 5..7),
+            ]
+        );
+        // nothing is known after input, or inside and after loops
+        assert!(kinds(",[]+[-]>[-<+>]<[]", &config).is_empty());
+        assert_eq!(
+            kinds("+ -\n><>", &config),
+            vec![
+                (LintKind::CancellingPair, 0..3),
+                (LintKind::CancellingPair, 4..6),
+            ]
+        );
+        // the pairs can stop the program with an error under other settings
+        let saturate = Config {
+            overflow: Overflow::Saturate,
+            tape: TapeKind::Growable,
+            ..Config::default()
+        };
+        assert_eq!(
+            kinds("+-><<>", &saturate),
+            vec![(LintKind::CancellingPair, 2..4)]
+        );
+        let bounded = Config {
+            tape: TapeKind::Bounded,
+            ..Config::default()
+        };
+        assert_eq!(
+            kinds("+-><<>", &bounded),
+            vec![(LintKind::CancellingPair, 0..2)]
+        );
+
+        // 256 is zero in an 8-bit wrapping cell
+        let mut code = "+".repeat(256);
+        code.push_str("[]");
+        assert_eq!(
+            kinds(&code, &config),
+            vec![(LintKind::UnreachableLoop, 256..258)]
+        );
+        let config16 = Config {
+            cell_width: CellWidth::U16,
+            ..Config::default()
+        };
+        assert_eq!(
+            kinds(&code, &config16),
+            vec![(LintKind::InfiniteLoop, 256..258)]
+        );
+
+        let lints = lint("+++#.\n-- comment! (not code)", &config).unwrap();
+        assert_eq!(lints.len(), 1);
+        assert_eq!(lints[0].kind, LintKind::ForeignCommand);
+        assert_eq!((lints[0].line, lints[0].column), (1, 4));
+        assert_eq!(
+            lints[0].to_string(),
+            "line 1, column 4: `#` is ignored, but dumps the tape in some interpreters \
+             [foreign-command]"
+        );
+        let config = Config {
+            dialect: Dialect {
+                tape_dump: true,
+                inline_input: true,
+            },
+            ..Config::default()
+        };
+        assert!(kinds("+++#.!+-[]", &config).is_empty());
+        assert!(matches!(lint("[", &config), Err(Error::SyntaxError(_))));
+    }
+
+    #[test]
+    fn test_to_json() {
+        let lints = lint("[\"quoted\"]\n+-", &Config::default()).unwrap();
+        assert_eq!(
+            to_json(&lints),
+            r#"[{"kind":"unreachable-loop","offset":0,"length":10,"line":1,"column":1,"message":"loop is never entered because the cell is always zero here"},{"kind":"cancelling-pair","offset":11,"length":2,"line":2,"column":1,"message":"`+` and `-` cancel each other out"}]"#
+        );
+        assert_eq!(to_json(&[]), "[]");
+        let mut out = String::new();
+        write_json_string(&mut out, "a\"b\\c\nd\u{1}");
+        assert_eq!(out, r#""a\"b\\c\nd\u0001""#);
+    }
+}
@@ -1,57 +1,389 @@
 //! An implementation of [Brainfuck].
 //!
-//! This implementation uses a cyclic memory tape of fixed length (65536) with 8-bit wrapping cells.
-//! On EOF, `,` command does not modify the current cell.
+//! By default, this implementation uses a cyclic memory tape of fixed length (65536) with 8-bit
+//! wrapping cells. The tape length, cell width, and behavior at the boundaries of the tape and
+//! the cells can be changed through [`Config`] and [`run_with_config`].
+//! On EOF, `,` command does not modify the current cell by default; see [`Eof`] for the
+//! alternatives. The `#` and `!` extensions can be enabled through [`Dialect`].
+//!
+//! [`run`] and [`run_with_config`] compile the source on every call. To run the same source
+//! many times, compile it once into a [`Program`] instead. To step through a program and
+//! inspect the tape, use a [`Debugger`]. To find out where a program spends its time, use a
+//! [`Profiler`].
 //!
 //! Since optimizing Brainfuck is a well-studied area and there are various extremely
 //! performant implementations out there, this one mostly serves as a practice implementation
-//! of a naive bytecode interpreter.
+//! of a simple bytecode interpreter. The bytecode folds runs of `+-<>`, tracks pointer
+//! offsets within each basic block, and turns common loops such as `[-]`, `[->+<]` and `[>]`
+//! into single instructions, as long as doing so cannot change the behavior under the given
+//! [`Config`].
 //!
 //! [Brainfuck]: https://esolangs.org/wiki/Brainfuck
 
-use std::io::{self, BufRead, Write};
+use std::fmt;
+use std::io::{self, BufRead, Read, Write};
+use std::ops::Range;
 use thiserror::Error;
 
+use crate::budget::{Budget, LimitExceeded};
+use crate::{Language, Options};
+
+mod config;
+mod debugger;
+pub mod format;
+#[cfg(all(feature = "jit", target_arch = "x86_64", target_os = "linux"))]
+mod jit;
+pub mod lint;
+mod profiler;
+mod tape;
+pub mod transpile;
+
+pub use config::{CellWidth, Config, Dialect, Eof, Overflow, TapeKind};
+pub use debugger::{Debugger, StopReason};
+pub use profiler::{BlockProfile, LoopProfile, Profiler};
+use tape::{Cell, Tape};
+
 /// Error enum for Brainfuck.
 #[derive(Error, Debug)]
 pub enum Error {
     /// Syntax error; the source code contains unmatched brackets.
-    #[error("unmatched bracket `{0}`")]
-    SyntaxError(char),
+    #[error("{0}")]
+    SyntaxError(SyntaxError),
+    /// The pointer moved past the end of a bounded tape, or left of the first cell of a
+    /// growable tape. Contains the position the pointer tried to reach.
+    #[error("pointer moved out of the tape to position {0}")]
+    TapeOutOfRange(isize),
+    /// A cell went out of its range under [`Overflow::Error`]. Contains the cell position.
+    #[error("cell at position {0} went out of range")]
+    CellOutOfRange(usize),
+    /// `,` was executed at EOF under [`Eof::Error`].
+    #[error("unexpected end of input")]
+    UnexpectedEof,
+    /// The program ran past [`Config::max_steps`] or [`Config::timeout`].
+    /// Contains the number of steps executed so far.
+    #[error("execution limit exceeded after {0} steps")]
+    LimitExceeded(u64),
     /// I/O error, which may occur during I/O operations.
     #[error("unexpected I/O error")]
     IoError(#[from] io::Error),
 }
 
-const MEMORY_SIZE: usize = 65536;
+impl From<LimitExceeded> for Error {
+    fn from(LimitExceeded(steps): LimitExceeded) -> Self {
+        Error::LimitExceeded(steps)
+    }
+}
+
+/// Location of an unmatched bracket.
+///
+/// For an unmatched `]`, this is the first `]` without a matching `[`.
+/// For an unmatched `[`, this is the outermost `[` that is never closed.
+///
+/// The `Display` implementation shows the offending source line with a caret under the bracket.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxError {
+    /// The unmatched bracket, either `[` or `]`.
+    pub bracket: char,
+    /// Byte offset of the bracket in the source.
+    pub offset: usize,
+    /// 1-based line number of the bracket.
+    pub line: usize,
+    /// 1-based column of the bracket, counted in characters.
+    pub column: usize,
+    /// The source line containing the bracket, without the line terminator.
+    pub source_line: String,
+}
+
+impl SyntaxError {
+    fn new(source: &str, offset: usize, bracket: char) -> Self {
+        let SourcePosition {
+            line,
+            column,
+            source_line,
+        } = SourcePosition::new(source, offset);
+        Self {
+            bracket,
+            offset,
+            line,
+            column,
+            source_line,
+        }
+    }
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "unmatched bracket `{}` at line {}, column {}",
+            self.bracket, self.line, self.column
+        )?;
+        writeln!(f, "{} |", " ".repeat(self.line.to_string().len()))?;
+        let position = SourcePosition {
+            line: self.line,
+            column: self.column,
+            source_line: self.source_line.clone(),
+        };
+        write!(f, "{}", position)
+    }
+}
+
+/// The line and column of a byte offset in the source.
+///
+/// The `Display` implementation shows the source line with a caret under the column.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourcePosition {
+    /// 1-based line number.
+    pub line: usize,
+    /// 1-based column, counted in characters.
+    pub column: usize,
+    /// The source line containing the offset, without the line terminator.
+    pub source_line: String,
+}
+
+impl SourcePosition {
+    /// Locates a byte offset in the source. `offset` must be at a character boundary.
+    pub fn new(source: &str, offset: usize) -> Self {
+        let line_start = source[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[offset..]
+            .find('\n')
+            .map_or(source.len(), |i| offset + i);
+        let (line, column) = line_column(source, offset);
+        Self {
+            line,
+            column,
+            source_line: source[line_start..line_end]
+                .trim_end_matches('\r')
+                .to_string(),
+        }
+    }
+}
+
+impl fmt::Display for SourcePosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let line_no = self.line.to_string();
+        // keep tabs so that the caret lines up with the source line
+        let indent: String = self
+            .source_line
+            .chars()
+            .take(self.column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        writeln!(f, "{} | {}", line_no, self.source_line)?;
+        write!(f, "{} | {}^", " ".repeat(line_no.len()), indent)
+    }
+}
+
+/// Returns the 1-based line and column of the byte offset, counting columns in characters.
+pub(crate) fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let line_start = source[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let line = source[..offset].matches('\n').count() + 1;
+    (line, source[line_start..offset].chars().count() + 1)
+}
 
 /// Brainfuck interpreter.
 pub fn run<I: BufRead, O: Write>(source: &str, input: &mut I, output: &mut O) -> Result<(), Error> {
-    let basic_blocks = into_basic_blocks(source)?;
+    run_with_config(source, input, output, &Config::default())
+}
+
+/// Brainfuck interpreter with a custom configuration.
+pub fn run_with_config<I: BufRead, O: Write>(
+    source: &str,
+    input: &mut I,
+    output: &mut O,
+    config: &Config,
+) -> Result<(), Error> {
+    Program::compile_with_config(source, config)?.run(input, output)
+}
+
+/// The [`Language`] implementation for Brainfuck.
+///
+/// Compiles with the default [`Config`], except for the settings taken from [`Options`].
+#[derive(Debug, Clone, Copy)]
+pub struct Brainfuck;
+
+impl Language for Brainfuck {
+    fn name(&self) -> &'static str {
+        "brainfuck"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["bf"]
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["bf", "b"]
+    }
+
+    fn compile(
+        &self,
+        source: &str,
+        options: &Options,
+    ) -> Result<Box<dyn crate::Program>, crate::Error> {
+        let config = Config {
+            eof: options.eof,
+            dialect: options.dialect,
+            max_steps: options.max_steps,
+            timeout: options.timeout,
+            ..Config::default()
+        };
+        let program = Program::compile_with_config(source, &config)
+            .map_err(|error| crate::Error::compile(self.name(), error))?;
+        Ok(Box::new(program))
+    }
+}
+
+/// A compiled Brainfuck program.
+///
+/// Compiling once and running many times avoids parsing the source on every run.
+/// The program is immutable and each run starts with a fresh tape, so a single program can be
+/// shared between threads.
+///
+/// With the `jit` feature on x86-64 Linux, the program is also compiled into native code
+/// when the configuration allows it.
+#[derive(Debug, Clone)]
+pub struct Program {
+    basic_blocks: ByteCodeProgram,
+    inline_input: Vec<u8>,
+    config: Config,
+    #[cfg(all(feature = "jit", target_arch = "x86_64", target_os = "linux"))]
+    jit: Option<std::sync::Arc<jit::Code>>,
+}
+
+impl Program {
+    /// Compiles the source code with the default [`Config`].
+    pub fn compile(source: &str) -> Result<Self, Error> {
+        Self::compile_with_config(source, &Config::default())
+    }
+
+    /// Compiles the source code with a custom configuration.
+    pub fn compile_with_config(source: &str, config: &Config) -> Result<Self, Error> {
+        let (code, inline_input) = split_inline_input(source, config);
+        let basic_blocks = into_basic_blocks(code, config)?;
+        Ok(Self {
+            #[cfg(all(feature = "jit", target_arch = "x86_64", target_os = "linux"))]
+            jit: jit::compile(&basic_blocks, config).map(std::sync::Arc::new),
+            basic_blocks,
+            inline_input: inline_input.to_vec(),
+            config: config.clone(),
+        })
+    }
+
+    /// The configuration the program was compiled with.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Runs the program from the start. Tape dumps from `#` are written to stderr.
+    pub fn run<I: BufRead, O: Write>(&self, input: &mut I, output: &mut O) -> Result<(), Error> {
+        self.run_with_diagnostics(input, output, &mut io::stderr())
+    }
+
+    /// Runs the program from the start, writing tape dumps from `#` to `diagnostics`.
+    pub fn run_with_diagnostics<I: BufRead, O: Write, D: Write>(
+        &self,
+        input: &mut I,
+        output: &mut O,
+        diagnostics: &mut D,
+    ) -> Result<(), Error> {
+        let mut input = (&self.inline_input[..]).chain(input);
+        #[cfg(all(feature = "jit", target_arch = "x86_64", target_os = "linux"))]
+        if let Some(code) = &self.jit {
+            return code.run(&mut input, output, &self.config);
+        }
+        execute_with_config(
+            &self.basic_blocks,
+            &mut input,
+            output,
+            diagnostics,
+            &mut (),
+            &self.config,
+        )
+    }
+}
+
+impl crate::Program for Program {
+    fn run(
+        &self,
+        mut input: &mut dyn BufRead,
+        mut output: &mut dyn Write,
+    ) -> Result<(), crate::Error> {
+        Program::run(self, &mut input, &mut output)
+            .map_err(|error| crate::Error::runtime(Brainfuck.name(), error))
+    }
+}
+
+/// Runs [`execute`] with the cell type given by the configuration.
+fn execute_with_config<I: BufRead, O: Write, D: Write, B: BlockObserver + ?Sized>(
+    basic_blocks: &ByteCodeProgram,
+    input: &mut I,
+    output: &mut O,
+    diagnostics: &mut D,
+    observer: &mut B,
+    config: &Config,
+) -> Result<(), Error> {
+    let (bbs, diag) = (basic_blocks, diagnostics);
+    match config.cell_width {
+        CellWidth::U8 => execute::<u8, _, _, _, _>(bbs, input, output, diag, observer, config),
+        CellWidth::U16 => execute::<u16, _, _, _, _>(bbs, input, output, diag, observer, config),
+        CellWidth::U32 => execute::<u32, _, _, _, _>(bbs, input, output, diag, observer, config),
+        CellWidth::U64 => execute::<u64, _, _, _, _>(bbs, input, output, diag, observer, config),
+    }
+}
+
+fn execute<C: Cell, I: BufRead, O: Write, D: Write, B: BlockObserver + ?Sized>(
+    basic_blocks: &ByteCodeProgram,
+    input: &mut I,
+    output: &mut O,
+    diagnostics: &mut D,
+    observer: &mut B,
+    config: &Config,
+) -> Result<(), Error> {
     let mut bb_no = 0usize;
-    let mut memory = vec![0u8; MEMORY_SIZE];
-    let mut ptr = 0usize;
+    let mut tape = Tape::<C>::new(config);
+    let mut budget = Budget::new(config.max_steps, config.timeout);
     loop {
+        observer.enter(bb_no);
         let BasicBlock { instrs, jz, jnz } = &basic_blocks[bb_no];
+        budget.spend(instrs.len() as u64 + 1)?;
         for &instr in instrs {
             match instr {
-                Cmd::Inc => memory[ptr] = memory[ptr].wrapping_add(1),
-                Cmd::Dec => memory[ptr] = memory[ptr].wrapping_sub(1),
-                Cmd::Left => {
-                    ptr = ptr.wrapping_sub(1) % MEMORY_SIZE;
+                Cmd::Add(offset, delta) => tape.add(offset, delta)?,
+                Cmd::Move(delta) => tape.shift(delta)?,
+                Cmd::Getc(offset) => match getc(input)? {
+                    Some(byte) => tape.set(offset, C::from_u64(byte as u64))?,
+                    None => match config.eof {
+                        Eof::Unchanged => (),
+                        Eof::Zero => tape.set(offset, C::default())?,
+                        Eof::MinusOne => tape.set(offset, C::from_u64(u64::MAX))?,
+                        Eof::Error => return Err(Error::UnexpectedEof),
+                    },
+                },
+                Cmd::Putc(offset) => putc(output, tape.get(offset)?.to_u64() as u8)?,
+                Cmd::Dump(offset) => {
+                    let center = tape.index(offset)?;
+                    writeln!(diagnostics, "{}", tape.snapshot(center, DUMP_RADIUS))?;
                 }
-                Cmd::Right => {
-                    ptr = (ptr + 1) % MEMORY_SIZE;
+                Cmd::SetZero(offset) => tape.set(offset, C::default())?,
+                Cmd::MulAdd { offset, factor } => {
+                    let value = tape.get(0)?.to_u64();
+                    tape.add(offset, value.wrapping_mul(factor as u64) as i64)?;
                 }
-                Cmd::Getc => {
-                    if let Some(byte) = getc(input)? {
-                        memory[ptr] = byte;
+                Cmd::ScanRight(stride) => {
+                    while !tape.is_zero() {
+                        budget.spend(1)?;
+                        tape.shift(stride as isize)?;
+                    }
+                }
+                Cmd::ScanLeft(stride) => {
+                    while !tape.is_zero() {
+                        budget.spend(1)?;
+                        tape.shift(-(stride as isize))?;
                     }
                 }
-                Cmd::Putc => putc(output, memory[ptr])?,
             }
         }
-        if let &Some(next_bb) = if memory[ptr] == 0 { jz } else { jnz } {
+        if let &Some(next_bb) = if tape.is_zero() { jz } else { jnz } {
             bb_no = next_bb;
         } else {
             break;
@@ -60,17 +392,52 @@ pub fn run<I: BufRead, O: Write>(source: &str, input: &mut I, output: &mut O) ->
     Ok(())
 }
 
-#[derive(Debug, Clone, Copy)]
+/// Gets notified of every basic block executed by [`execute`].
+trait BlockObserver {
+    fn enter(&mut self, bb_no: usize);
+}
+
+/// Observes nothing, for normal runs.
+impl BlockObserver for () {
+    #[inline(always)]
+    fn enter(&mut self, _bb_no: usize) {}
+}
+
+/// Counts the executions of each basic block.
+impl BlockObserver for [u64] {
+    #[inline]
+    fn enter(&mut self, bb_no: usize) {
+        self[bb_no] += 1;
+    }
+}
+
+/// Number of cells on each side of the pointer shown by `#`.
+const DUMP_RADIUS: usize = 8;
+
+/// A bytecode instruction. Offsets are relative to the pointer at the time of execution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Cmd {
-    Inc,
-    Dec,
-    Left,
-    Right,
-    Getc,
-    Putc,
+    /// Add a value to the cell at the offset.
+    Add(isize, i64),
+    /// Move the pointer.
+    Move(isize),
+    /// Read a byte into the cell at the offset.
+    Getc(isize),
+    /// Write the cell at the offset.
+    Putc(isize),
+    /// Write the cells around the offset to the diagnostic output.
+    Dump(isize),
+    /// Set the cell at the offset to zero.
+    SetZero(isize),
+    /// Add the current cell times `factor` to the cell at `offset`, wrapping around.
+    MulAdd { offset: isize, factor: i64 },
+    /// Move the pointer right by the stride until it reaches a zero cell.
+    ScanRight(usize),
+    /// Move the pointer left by the stride until it reaches a zero cell.
+    ScanLeft(usize),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct BasicBlock {
     instrs: Vec<Cmd>,
     jz: Option<usize>,
@@ -79,65 +446,235 @@ struct BasicBlock {
 
 type ByteCodeProgram = Vec<BasicBlock>;
 
-fn into_basic_blocks(source: &str) -> Result<ByteCodeProgram, Error> {
-    let mut bbno_stack = vec![]; // stores ids right before `[`
+/// Accumulates the instructions of a basic block, folding them on the fly.
+struct BlockBuilder<'a> {
+    config: &'a Config,
+    instrs: Vec<Cmd>,
+    offset: isize,
+}
+
+impl<'a> BlockBuilder<'a> {
+    fn new(config: &'a Config) -> Self {
+        Self {
+            config,
+            instrs: vec![],
+            offset: 0,
+        }
+    }
+
+    fn add(&mut self, delta: i64) {
+        // folding `+` and `-` together is only exact if intermediate values may wrap around
+        let can_cancel = self.config.overflow == Overflow::Wrap;
+        if let Some(Cmd::Add(offset, last)) = self.instrs.last_mut() {
+            if *offset == self.offset && (can_cancel || (*last > 0) == (delta > 0)) {
+                *last += delta;
+                if *last == 0 {
+                    self.instrs.pop();
+                }
+                return;
+            }
+        }
+        self.instrs.push(Cmd::Add(self.offset, delta));
+    }
+
+    fn shift(&mut self, delta: isize) {
+        // deferring pointer moves is only exact if the pointer can never go out of the tape
+        if self.config.tape == TapeKind::Cyclic {
+            self.offset += delta;
+            return;
+        }
+        if let Some(Cmd::Move(last)) = self.instrs.last_mut() {
+            if (*last > 0) == (delta > 0) {
+                *last += delta;
+                return;
+            }
+        }
+        self.instrs.push(Cmd::Move(delta));
+    }
+
+    fn push(&mut self, instr: Cmd) {
+        self.instrs.push(instr);
+    }
+
+    /// Lowers the block into equivalent instructions if it is the body of a recognized loop.
+    fn lower_loop(&self) -> Option<Vec<Cmd>> {
+        let wrap = self.config.overflow == Overflow::Wrap;
+        match (&self.instrs[..], self.offset) {
+            (&[], stride) | (&[Cmd::Move(stride)], 0) if stride != 0 => Some(vec![if stride > 0 {
+                Cmd::ScanRight(stride.unsigned_abs())
+            } else {
+                Cmd::ScanLeft(stride.unsigned_abs())
+            }]),
+            ([Cmd::Add(0, -1)], 0) => Some(vec![Cmd::SetZero(0)]),
+            ([Cmd::Add(0, 1)], 0) if wrap => Some(vec![Cmd::SetZero(0)]),
+            (instrs, 0) if wrap => {
+                let mut deltas: Vec<(isize, i64)> = vec![];
+                for instr in instrs {
+                    let &Cmd::Add(offset, delta) = instr else {
+                        return None;
+                    };
+                    match deltas.iter_mut().find(|(o, _)| *o == offset) {
+                        Some((_, total)) => *total += delta,
+                        None => deltas.push((offset, delta)),
+                    }
+                }
+                // the counter runs `value` times when decremented, and `-value` times when
+                // incremented, which gives the same result as negating every factor
+                let sign = match deltas.iter().find(|(o, _)| *o == 0) {
+                    Some((_, -1)) => 1,
+                    Some((_, 1)) => -1,
+                    _ => return None,
+                };
+                let mut lowered: Vec<Cmd> = deltas
+                    .into_iter()
+                    .filter(|&(offset, delta)| offset != 0 && delta != 0)
+                    .map(|(offset, delta)| Cmd::MulAdd {
+                        offset,
+                        factor: sign * delta,
+                    })
+                    .collect();
+                lowered.push(Cmd::SetZero(0));
+                Some(lowered)
+            }
+            _ => None,
+        }
+    }
+
+    fn finish(&mut self) -> Vec<Cmd> {
+        if self.offset != 0 {
+            self.instrs.push(Cmd::Move(self.offset));
+            self.offset = 0;
+        }
+        std::mem::take(&mut self.instrs)
+    }
+}
+
+fn into_basic_blocks(source: &str, config: &Config) -> Result<ByteCodeProgram, Error> {
+    build_basic_blocks(source, config, None)
+}
+
+/// Where the basic blocks and loops come from in the source, recorded for profiling.
+#[derive(Debug, Clone, Default)]
+struct SourceMap {
+    /// Byte range of the source covered by each basic block, including the bracket ending it.
+    blocks: Vec<Range<usize>>,
+    /// Offsets of `[` and `]` of each loop, and the id of the block ending at the `[`.
+    /// The loop body starts at the next block.
+    loops: Vec<(usize, usize, usize)>,
+}
+
+/// Compiles the source into basic blocks, filling in `source_map` if given.
+///
+/// Loops are never lowered when building a source map, so that every command in a block runs
+/// exactly once each time the block is executed.
+fn build_basic_blocks(
+    source: &str,
+    config: &Config,
+    mut source_map: Option<&mut SourceMap>,
+) -> Result<ByteCodeProgram, Error> {
+    let mut bbno_stack = vec![]; // stores ids right before `[` and the offset of `[`
     let mut basic_blocks = vec![];
-    let mut cur_basic_block = vec![];
+    let mut cur_basic_block = BlockBuilder::new(config);
     let mut cur_bb_id = 0usize;
-    for c in source.chars() {
+    let mut cur_bb_start = 0usize;
+    for (pos, c) in source.char_indices() {
         match c {
-            '+' => cur_basic_block.push(Cmd::Inc),
-            '-' => cur_basic_block.push(Cmd::Dec),
-            '<' => cur_basic_block.push(Cmd::Left),
-            '>' => cur_basic_block.push(Cmd::Right),
-            ',' => cur_basic_block.push(Cmd::Getc),
-            '.' => cur_basic_block.push(Cmd::Putc),
+            '+' => cur_basic_block.add(1),
+            '-' => cur_basic_block.add(-1),
+            '<' => cur_basic_block.shift(-1),
+            '>' => cur_basic_block.shift(1),
+            ',' => cur_basic_block.push(Cmd::Getc(cur_basic_block.offset)),
+            '.' => cur_basic_block.push(Cmd::Putc(cur_basic_block.offset)),
+            '#' if config.dialect.tape_dump => {
+                cur_basic_block.push(Cmd::Dump(cur_basic_block.offset))
+            }
             '[' => {
                 // starts next basic block
                 // jnz target is always bb+1; handle jz target when `]` is found
                 let bb = BasicBlock {
-                    instrs: cur_basic_block,
+                    instrs: cur_basic_block.finish(),
                     jz: None,
                     jnz: Some(cur_bb_id + 1),
                 };
                 basic_blocks.push(bb);
-                bbno_stack.push(cur_bb_id);
+                bbno_stack.push((cur_bb_id, pos));
+                if let Some(map) = source_map.as_deref_mut() {
+                    map.blocks.push(cur_bb_start..pos + 1);
+                }
                 cur_bb_id += 1;
-                cur_basic_block = vec![];
+                cur_bb_start = pos + 1;
             }
             ']' => {
                 // starts next basic block
                 // jz target is bb+1; jnz target is popped+1; jz of popped is bb+1
-                let popped = bbno_stack.pop().ok_or(Error::SyntaxError(']'))?;
+                let (popped, open) = bbno_stack
+                    .pop()
+                    .ok_or_else(|| Error::SyntaxError(SyntaxError::new(source, pos, ']')))?;
+                if popped + 1 == cur_bb_id && source_map.is_none() {
+                    // innermost loop; replace it with equivalent instructions if possible
+                    if let Some(lowered) = cur_basic_block.lower_loop() {
+                        let before = basic_blocks.pop().expect("block before the loop");
+                        cur_basic_block.instrs = before.instrs;
+                        cur_basic_block.offset = 0;
+                        for instr in lowered {
+                            cur_basic_block.push(instr);
+                        }
+                        cur_bb_id = popped;
+                        continue;
+                    }
+                }
                 let bb = BasicBlock {
-                    instrs: cur_basic_block,
+                    instrs: cur_basic_block.finish(),
                     jz: Some(cur_bb_id + 1),
                     jnz: Some(popped + 1),
                 };
                 basic_blocks.push(bb);
                 basic_blocks[popped].jz = Some(cur_bb_id + 1);
+                if let Some(map) = source_map.as_deref_mut() {
+                    map.blocks.push(cur_bb_start..pos + 1);
+                    map.loops.push((open, pos, popped));
+                }
                 cur_bb_id += 1;
-                cur_basic_block = vec![];
+                cur_bb_start = pos + 1;
             }
             _ => (),
         }
     }
-    if !bbno_stack.is_empty() {
-        return Err(Error::SyntaxError('['));
+    if let Some(&(_, pos)) = bbno_stack.first() {
+        return Err(Error::SyntaxError(SyntaxError::new(source, pos, '[')));
     }
     let bb = BasicBlock {
-        instrs: cur_basic_block,
+        instrs: cur_basic_block.finish(),
         jz: None,
         jnz: None,
     };
     basic_blocks.push(bb);
+    if let Some(map) = source_map {
+        map.blocks.push(cur_bb_start..source.len());
+    }
     Ok(basic_blocks)
 }
 
+/// Whether the character is a command rather than a comment.
+fn is_command(c: char, config: &Config) -> bool {
+    matches!(c, '+' | '-' | '<' | '>' | ',' | '.' | '[' | ']')
+        || (c == '#' && config.dialect.tape_dump)
+}
+
+/// Splits the source into the code and the input after `!`, if enabled by the dialect.
+fn split_inline_input<'a>(source: &'a str, config: &Config) -> (&'a str, &'a [u8]) {
+    match source.find('!') {
+        Some(pos) if config.dialect.inline_input => (&source[..pos], &source.as_bytes()[pos + 1..]),
+        _ => (source, &[]),
+    }
+}
+
 fn getc<I: BufRead>(input: &mut I) -> Result<Option<u8>, Error> {
     let buf = input.fill_buf()?;
-    let value = buf.get(0).copied();
-    input.consume(1);
+    let value = buf.first().copied();
+    if value.is_some() {
+        input.consume(1);
+    }
     Ok(value)
 }
 
@@ -149,6 +686,7 @@ fn putc<O: Write>(output: &mut O, byte: u8) -> Result<(), Error> {
 #[cfg(test)]
 mod tests {
     use std::io::BufReader;
+    use std::time::Duration;
 
     use super::*;
 
@@ -200,4 +738,269 @@ mod tests {
             assert_eq!(stdout, expected);
         }
     }
+
+    #[test]
+    fn test_config_cells() {
+        let mut stdout: Vec<u8> = vec![];
+        let config = Config {
+            cell_width: CellWidth::U16,
+            ..Config::default()
+        };
+        // computes 256, which is zero only in an 8-bit cell
+        let code = "++++++++++++++++[>++++++++++++++++<-]>[>+<[-]]>.";
+        let res = run_with_config(code, &mut &b""[..], &mut stdout, &config);
+        assert!(res.is_ok());
+        assert_eq!(stdout, b"\x01");
+        stdout.clear();
+        let res = run(code, &mut &b""[..], &mut stdout);
+        assert!(res.is_ok());
+        assert_eq!(stdout, b"\x00");
+
+        let config = Config {
+            overflow: Overflow::Saturate,
+            ..Config::default()
+        };
+        stdout.clear();
+        let res = run_with_config("-+.", &mut &b""[..], &mut stdout, &config);
+        assert!(res.is_ok());
+        assert_eq!(stdout, b"\x01");
+
+        let config = Config {
+            overflow: Overflow::Error,
+            ..Config::default()
+        };
+        let res = run_with_config("+-+-", &mut &b""[..], &mut stdout, &config);
+        assert!(res.is_ok());
+        let res = run_with_config("-", &mut &b""[..], &mut stdout, &config);
+        assert!(matches!(res, Err(Error::CellOutOfRange(0))));
+    }
+
+    #[test]
+    fn test_config_tape() {
+        let mut stdout: Vec<u8> = vec![];
+        let config = Config {
+            tape_len: 4,
+            tape: TapeKind::Bounded,
+            ..Config::default()
+        };
+        let res = run_with_config(">>><<<", &mut &b""[..], &mut stdout, &config);
+        assert!(res.is_ok());
+        let res = run_with_config(">>>>", &mut &b""[..], &mut stdout, &config);
+        assert!(matches!(res, Err(Error::TapeOutOfRange(4))));
+        let res = run_with_config("<", &mut &b""[..], &mut stdout, &config);
+        assert!(matches!(res, Err(Error::TapeOutOfRange(-1))));
+
+        let config = Config {
+            tape_len: 4,
+            tape: TapeKind::Growable,
+            ..Config::default()
+        };
+        let res = run_with_config("<", &mut &b""[..], &mut stdout, &config);
+        assert!(matches!(res, Err(Error::TapeOutOfRange(-1))));
+        stdout.clear();
+        let res = run_with_config(">>>>>>>>+.", &mut &b""[..], &mut stdout, &config);
+        assert!(res.is_ok());
+        assert_eq!(stdout, b"\x01");
+
+        let config = Config {
+            tape_len: 4,
+            ..Config::default()
+        };
+        stdout.clear();
+        let res = run_with_config("<+>>>>.", &mut &b""[..], &mut stdout, &config);
+        assert!(res.is_ok());
+        assert_eq!(stdout, b"\x01");
+    }
+
+    #[test]
+    fn test_eof() {
+        let code = ",+.,+.";
+        let cases = [
+            (Eof::Unchanged, Some(&b"\x42\x43"[..])),
+            (Eof::Zero, Some(b"\x42\x01")),
+            (Eof::MinusOne, Some(b"\x42\x00")),
+            (Eof::Error, None),
+        ];
+        for (eof, expected) in cases {
+            let config = Config {
+                eof,
+                ..Config::default()
+            };
+            let mut stdout: Vec<u8> = vec![];
+            let res = run_with_config(code, &mut &b"A"[..], &mut stdout, &config);
+            match expected {
+                Some(expected) => {
+                    assert!(res.is_ok());
+                    assert_eq!(stdout, expected);
+                }
+                None => assert!(matches!(res, Err(Error::UnexpectedEof))),
+            }
+        }
+    }
+
+    #[test]
+    fn test_folding() {
+        let config = Config::default();
+        let program = into_basic_blocks(">+>+<<", &config).unwrap();
+        assert_eq!(program[0].instrs, [Cmd::Add(1, 1), Cmd::Add(2, 1)]);
+        let program = into_basic_blocks("+++--->>>.<", &config).unwrap();
+        assert_eq!(program[0].instrs, [Cmd::Putc(3), Cmd::Move(2)]);
+
+        let config = Config {
+            overflow: Overflow::Saturate,
+            tape: TapeKind::Bounded,
+            ..Config::default()
+        };
+        let program = into_basic_blocks("+++-->><", &config).unwrap();
+        assert_eq!(
+            program[0].instrs,
+            [Cmd::Add(0, 3), Cmd::Add(0, -2), Cmd::Move(2), Cmd::Move(-1)]
+        );
+    }
+
+    #[test]
+    fn test_loop_idioms() {
+        let config = Config::default();
+        let program = into_basic_blocks("+[-]>[->+>++<<]<[>]", &config).unwrap();
+        assert_eq!(program.len(), 1);
+        assert_eq!(
+            program[0].instrs,
+            [
+                Cmd::Add(0, 1),
+                Cmd::SetZero(0),
+                Cmd::Move(1),
+                Cmd::MulAdd {
+                    offset: 1,
+                    factor: 1
+                },
+                Cmd::MulAdd {
+                    offset: 2,
+                    factor: 2
+                },
+                Cmd::SetZero(0),
+                Cmd::Move(-1),
+                Cmd::ScanRight(1),
+            ]
+        );
+
+        // `[+]` may not terminate if cells do not wrap around
+        let config = Config {
+            overflow: Overflow::Saturate,
+            ..Config::default()
+        };
+        let program = into_basic_blocks("[+][-][->+<]", &config).unwrap();
+        assert_eq!(program.len(), 5);
+
+        // the second loop runs 256 - 48 times
+        let code = "++++++[>++++++++<-]>[<+>+]<.";
+        let mut stdout: Vec<u8> = vec![];
+        let res = run(code, &mut &b""[..], &mut stdout);
+        assert!(res.is_ok());
+        assert_eq!(stdout, [208]);
+    }
+
+    #[test]
+    fn test_syntax_error() {
+        let res = into_basic_blocks("+[\n>[-]\n]]<", &Config::default());
+        let Err(Error::SyntaxError(error)) = res else {
+            panic!("expected a syntax error");
+        };
+        assert_eq!((error.bracket, error.offset), (']', 9));
+        assert_eq!((error.line, error.column), (3, 2));
+        assert_eq!(
+            error.to_string(),
+            "unmatched bracket `]` at line 3, column 2\n  |\n3 | ]]<\n  |  ^"
+        );
+
+        let res = into_basic_blocks("[[]\n[", &Config::default());
+        let Err(Error::SyntaxError(error)) = res else {
+            panic!("expected a syntax error");
+        };
+        assert_eq!((error.bracket, error.line, error.column), ('[', 1, 1));
+
+        let position = SourcePosition::new("+\n\t\t>>]\r\n", 6);
+        assert_eq!((position.line, position.column), (2, 5));
+        assert_eq!(position.to_string(), "2 | \t\t>>]\n  | \t\t  ^");
+    }
+
+    #[test]
+    fn test_limits() {
+        let mut stdout: Vec<u8> = vec![];
+        let config = Config {
+            max_steps: Some(1000),
+            ..Config::default()
+        };
+        let res = run_with_config("+[]", &mut &b""[..], &mut stdout, &config);
+        assert!(matches!(res, Err(Error::LimitExceeded(steps)) if steps > 1000));
+        let res = run_with_config("+++[-]", &mut &b""[..], &mut stdout, &config);
+        assert!(res.is_ok());
+
+        let config = Config {
+            timeout: Some(Duration::from_millis(10)),
+            ..Config::default()
+        };
+        let res = run_with_config("+[>+]", &mut &b""[..], &mut stdout, &config);
+        assert!(matches!(res, Err(Error::LimitExceeded(_))));
+    }
+
+    #[test]
+    fn test_program_reuse() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Program>();
+
+        // reverses each line of input
+        let program = Program::compile(">,[>,]<[.<]").unwrap();
+        std::thread::scope(|scope| {
+            for i in 0..8 {
+                let program = &program;
+                scope.spawn(move || {
+                    let input = format!("{}abc", i);
+                    let mut stdout: Vec<u8> = vec![];
+                    let res = program.run(&mut input.as_bytes(), &mut stdout);
+                    assert!(res.is_ok());
+                    assert_eq!(stdout, format!("cba{}", i).as_bytes());
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn test_dialect() {
+        let code = "+>++>+++<#<[.>]!ab";
+        let mut stdout: Vec<u8> = vec![];
+        let mut diagnostics: Vec<u8> = vec![];
+        let program = Program::compile(code).unwrap();
+        let res = program.run_with_diagnostics(&mut &b"c"[..], &mut stdout, &mut diagnostics);
+        assert!(res.is_ok());
+        assert_eq!(stdout, b"\x01\x02\x03");
+        assert!(diagnostics.is_empty());
+
+        let config = Config {
+            dialect: Dialect {
+                tape_dump: true,
+                inline_input: true,
+            },
+            ..Config::default()
+        };
+        let code = "+>++>+++<#<[.>]>>>,.,.,.!ab";
+        let program = Program::compile_with_config(code, &config).unwrap();
+        for _ in 0..2 {
+            stdout.clear();
+            diagnostics.clear();
+            let res = program.run_with_diagnostics(&mut &b"c"[..], &mut stdout, &mut diagnostics);
+            assert!(res.is_ok());
+            assert_eq!(stdout, b"\x01\x02\x03abc");
+            assert_eq!(
+                diagnostics,
+                b"0: 1  [1: 2]  2: 3  3: 0  4: 0  5: 0  6: 0  7: 0  8: 0  9: 0\n"
+            );
+        }
+
+        // `#` at the left end of a cyclic tape shows only the cells to the right
+        let mut diagnostics: Vec<u8> = vec![];
+        let program = Program::compile_with_config("#", &config).unwrap();
+        let res = program.run_with_diagnostics(&mut &b""[..], &mut vec![], &mut diagnostics);
+        assert!(res.is_ok());
+        assert!(diagnostics.starts_with(b"[0: 0]  1: 0"));
+    }
 }
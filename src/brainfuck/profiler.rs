@@ -0,0 +1,199 @@
+//! Execution profiler for Brainfuck programs.
+
+use std::io::{self, BufRead, Read, Write};
+use std::ops::Range;
+
+use super::{
+    build_basic_blocks, execute_with_config, is_command, split_inline_input, ByteCodeProgram,
+    Config, Error, SourceMap,
+};
+
+/// Execution count of a basic block, a piece of code between two brackets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockProfile {
+    /// Byte range of the source covered by the block, including the bracket that ends it.
+    pub span: Range<usize>,
+    /// Number of commands in the block.
+    pub commands: usize,
+    /// Number of times the block was executed.
+    pub hits: u64,
+}
+
+/// Execution count of a loop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoopProfile {
+    /// Byte offset of the `[`.
+    pub open: usize,
+    /// Byte offset of the matching `]`.
+    pub close: usize,
+    /// Number of times the loop was reached.
+    pub entries: u64,
+    /// Total number of times the loop body was executed.
+    pub iterations: u64,
+}
+
+/// Counts how many times each part of a Brainfuck program is executed.
+///
+/// The program is compiled without turning loops into single instructions, so that every loop
+/// iteration is counted, and therefore runs slower than a [`Program`](super::Program).
+/// Counts accumulate over all runs of the same profiler.
+#[derive(Debug, Clone)]
+pub struct Profiler {
+    basic_blocks: ByteCodeProgram,
+    source_map: SourceMap,
+    code: String,
+    inline_input: Vec<u8>,
+    config: Config,
+    counts: Vec<u64>,
+}
+
+impl Profiler {
+    /// Compiles the source code for profiling.
+    pub fn new(source: &str, config: &Config) -> Result<Self, Error> {
+        let (code, inline_input) = split_inline_input(source, config);
+        let mut source_map = SourceMap::default();
+        let basic_blocks = build_basic_blocks(code, config, Some(&mut source_map))?;
+        Ok(Self {
+            counts: vec![0; basic_blocks.len()],
+            basic_blocks,
+            source_map,
+            code: code.to_string(),
+            inline_input: inline_input.to_vec(),
+            config: config.clone(),
+        })
+    }
+
+    /// Runs the program from the start, adding to the counts. Tape dumps from `#` are written
+    /// to stderr.
+    ///
+    /// If the program stops with an error, the counts up to that point are kept.
+    pub fn run<I: BufRead, O: Write>(
+        &mut self,
+        input: &mut I,
+        output: &mut O,
+    ) -> Result<(), Error> {
+        let mut input = (&self.inline_input[..]).chain(input);
+        execute_with_config(
+            &self.basic_blocks,
+            &mut input,
+            output,
+            &mut io::stderr(),
+            &mut self.counts[..],
+            &self.config,
+        )
+    }
+
+    /// Execution counts of all basic blocks, in source order.
+    pub fn blocks(&self) -> Vec<BlockProfile> {
+        self.source_map
+            .blocks
+            .iter()
+            .zip(&self.counts)
+            .map(|(span, &hits)| BlockProfile {
+                span: span.clone(),
+                commands: self.code[span.clone()]
+                    .chars()
+                    .filter(|&c| is_command(c, &self.config))
+                    .count(),
+                hits,
+            })
+            .collect()
+    }
+
+    /// Execution counts of all loops, in the order of their `]`.
+    pub fn loops(&self) -> Vec<LoopProfile> {
+        self.source_map
+            .loops
+            .iter()
+            .map(|&(open, close, bb_no)| LoopProfile {
+                open,
+                close,
+                entries: self.counts[bb_no],
+                iterations: self.counts[bb_no + 1],
+            })
+            .collect()
+    }
+
+    /// Byte offsets of all commands with the number of times each was executed, in source order.
+    pub fn command_hits(&self) -> Vec<(usize, u64)> {
+        self.source_map
+            .blocks
+            .iter()
+            .zip(&self.counts)
+            .flat_map(|(span, &hits)| {
+                self.code[span.clone()]
+                    .char_indices()
+                    .filter(|&(_, c)| is_command(c, &self.config))
+                    .map(move |(pos, _)| (span.start + pos, hits))
+            })
+            .collect()
+    }
+
+    /// Total number of commands executed.
+    pub fn steps(&self) -> u64 {
+        self.blocks()
+            .iter()
+            .map(|block| block.hits * block.commands as u64)
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profiler() {
+        let code = "++[>+++[>+<-]<-]>>.";
+        let mut profiler = Profiler::new(code, &Config::default()).unwrap();
+        let mut stdout: Vec<u8> = vec![];
+        assert!(profiler.run(&mut &b""[..], &mut stdout).is_ok());
+        assert_eq!(stdout, b"\x06");
+
+        assert_eq!(
+            profiler.loops(),
+            vec![
+                LoopProfile {
+                    open: 7,
+                    close: 12,
+                    entries: 2,
+                    iterations: 6
+                },
+                LoopProfile {
+                    open: 2,
+                    close: 15,
+                    entries: 1,
+                    iterations: 2
+                },
+            ]
+        );
+        let blocks = profiler.blocks();
+        assert_eq!(blocks.len(), 5);
+        assert_eq!(
+            blocks[2],
+            BlockProfile {
+                span: 8..13,
+                commands: 5,
+                hits: 6
+            }
+        );
+        let hits = profiler.command_hits();
+        assert_eq!(hits.len(), code.len());
+        assert_eq!(hits[0], (0, 1));
+        assert_eq!(hits[4], (4, 2));
+        assert_eq!(hits[8], (8, 6));
+        assert_eq!(profiler.steps(), hits.iter().map(|&(_, hits)| hits).sum());
+
+        // counts accumulate over runs, and are kept on errors
+        assert!(profiler.run(&mut &b""[..], &mut stdout).is_ok());
+        assert_eq!(profiler.loops()[0].iterations, 12);
+        let config = Config {
+            max_steps: Some(10),
+            ..Config::default()
+        };
+        let mut profiler = Profiler::new(code, &config).unwrap();
+        let res = profiler.run(&mut &b""[..], &mut stdout);
+        assert!(matches!(res, Err(Error::LimitExceeded(_))));
+        assert!(profiler.steps() > 0);
+    }
+}
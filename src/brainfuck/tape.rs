@@ -0,0 +1,179 @@
+//! Memory tape and cell arithmetic shared by the Brainfuck executors.
+
+use super::{Config, Error, Overflow, TapeKind};
+
+/// An unsigned integer type usable as a Brainfuck cell.
+pub(crate) trait Cell: Copy + Default + Eq + Send + Sync + 'static {
+    /// Converts from `u64`, truncating to the width of the cell.
+    fn from_u64(value: u64) -> Self;
+    /// Converts to `u64` without loss.
+    fn to_u64(self) -> u64;
+    /// Adds a signed amount, returning `None` if the cell goes out of range
+    /// under [`Overflow::Error`].
+    fn add(self, delta: i64, overflow: Overflow) -> Option<Self>;
+}
+
+macro_rules! impl_cell {
+    ($($t:ty),*) => {$(
+        impl Cell for $t {
+            #[inline]
+            fn from_u64(value: u64) -> Self {
+                value as $t
+            }
+
+            #[inline]
+            fn to_u64(self) -> u64 {
+                self as u64
+            }
+
+            #[inline]
+            fn add(self, delta: i64, overflow: Overflow) -> Option<Self> {
+                let magnitude = <$t>::try_from(delta.unsigned_abs());
+                match overflow {
+                    Overflow::Wrap => Some(self.wrapping_add(delta as $t)),
+                    Overflow::Saturate => {
+                        let magnitude = magnitude.unwrap_or(<$t>::MAX);
+                        Some(if delta >= 0 {
+                            self.saturating_add(magnitude)
+                        } else {
+                            self.saturating_sub(magnitude)
+                        })
+                    }
+                    Overflow::Error => {
+                        let magnitude = magnitude.ok()?;
+                        if delta >= 0 {
+                            self.checked_add(magnitude)
+                        } else {
+                            self.checked_sub(magnitude)
+                        }
+                    }
+                }
+            }
+        }
+    )*};
+}
+
+impl_cell!(u8, u16, u32, u64);
+
+/// A memory tape together with the data pointer.
+#[derive(Debug, Clone)]
+pub(crate) struct Tape<C> {
+    cells: Vec<C>,
+    ptr: usize,
+    kind: TapeKind,
+    overflow: Overflow,
+}
+
+impl<C: Cell> Tape<C> {
+    pub(crate) fn new(config: &Config) -> Self {
+        Self {
+            cells: vec![C::default(); config.tape_len.max(1)],
+            ptr: 0,
+            kind: config.tape,
+            overflow: config.overflow,
+        }
+    }
+
+    /// Resolves the cell at `offset` from the pointer, growing the tape if allowed.
+    #[inline]
+    pub(crate) fn index(&mut self, offset: isize) -> Result<usize, Error> {
+        let len = self.cells.len();
+        let target = self.ptr as isize + offset;
+        if (0..len as isize).contains(&target) {
+            return Ok(target as usize);
+        }
+        match self.kind {
+            TapeKind::Cyclic => Ok(target.rem_euclid(len as isize) as usize),
+            TapeKind::Growable if target >= 0 => {
+                let target = target as usize;
+                if target >= len {
+                    self.cells.resize((target + 1).max(len * 2), C::default());
+                }
+                Ok(target)
+            }
+            _ => Err(Error::TapeOutOfRange(target)),
+        }
+    }
+
+    /// Moves the pointer by `delta` cells.
+    #[inline]
+    pub(crate) fn shift(&mut self, delta: isize) -> Result<(), Error> {
+        self.ptr = self.index(delta)?;
+        Ok(())
+    }
+
+    #[inline]
+    pub(crate) fn get(&mut self, offset: isize) -> Result<C, Error> {
+        let index = self.index(offset)?;
+        Ok(self.cells[index])
+    }
+
+    #[inline]
+    pub(crate) fn set(&mut self, offset: isize, value: C) -> Result<(), Error> {
+        let index = self.index(offset)?;
+        self.cells[index] = value;
+        Ok(())
+    }
+
+    /// Adds `delta` to the cell at `offset`, following the overflow policy.
+    #[inline]
+    pub(crate) fn add(&mut self, offset: isize, delta: i64) -> Result<(), Error> {
+        let index = self.index(offset)?;
+        self.cells[index] = self.cells[index]
+            .add(delta, self.overflow)
+            .ok_or(Error::CellOutOfRange(index))?;
+        Ok(())
+    }
+
+    /// Position of the pointer.
+    #[inline]
+    pub(crate) fn ptr(&self) -> usize {
+        self.ptr
+    }
+
+    /// Current number of cells.
+    #[inline]
+    pub(crate) fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    /// The cell at an absolute position, if it exists.
+    #[inline]
+    pub(crate) fn cell(&self, index: usize) -> Option<C> {
+        self.cells.get(index).copied()
+    }
+
+    /// Formats the cells within `radius` of the cell at `center`; see [`format_cells`].
+    pub(crate) fn snapshot(&self, center: usize, radius: usize) -> String {
+        format_cells(center, radius, self.cells.len(), |index| {
+            self.cells[index].to_u64()
+        })
+    }
+
+    /// Whether the current cell is zero.
+    #[inline]
+    pub(crate) fn is_zero(&self) -> bool {
+        self.cells[self.ptr] == C::default()
+    }
+}
+
+/// Formats the cells within `radius` of the cell at `center` as `index: value` pairs, with the
+/// center cell in brackets, e.g. `0: 0  [1: 72]  2: 105`.
+pub(crate) fn format_cells(
+    center: usize,
+    radius: usize,
+    len: usize,
+    cell: impl Fn(usize) -> u64,
+) -> String {
+    let end = center.saturating_add(radius).min(len - 1);
+    let cells: Vec<String> = (center.saturating_sub(radius)..=end)
+        .map(|index| {
+            if index == center {
+                format!("[{}: {}]", index, cell(index))
+            } else {
+                format!("{}: {}", index, cell(index))
+            }
+        })
+        .collect();
+    cells.join("  ")
+}
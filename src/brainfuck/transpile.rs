@@ -0,0 +1,607 @@
+//! Code generators that turn Brainfuck programs into source code of other languages.
+//!
+//! The generated code is built from the same bytecode as
+//! [`run_with_config`](super::run_with_config) and follows the given [`Config`] for the tape,
+//! the cells, EOF and the dialect, so that it produces the same output for the same input.
+//! Tape dumps from `#` go to stderr. [`Config::max_steps`] and [`Config::timeout`] are not
+//! supported and are ignored.
+//!
+//! When the interpreter would stop with an error, the generated program prints the same
+//! error message to stderr and exits with status 1.
+
+use std::fmt::{self, Write};
+
+use super::{
+    into_basic_blocks, split_inline_input, CellWidth, Cmd, Config, Eof, Error, Overflow, TapeKind,
+    DUMP_RADIUS,
+};
+
+/// Translates a Brainfuck program into a standalone C program.
+///
+/// The output only depends on the C standard library.
+pub fn to_c(source: &str, config: &Config) -> Result<String, Error> {
+    let (code, inline_input) = split_inline_input(source, config);
+    let basic_blocks = into_basic_blocks(code, config)?;
+    let mut out = String::new();
+    write_c(&mut out, &basic_blocks, inline_input, config)
+        .expect("writing to a String cannot fail");
+    Ok(out)
+}
+
+fn c_cell_type(width: CellWidth) -> &'static str {
+    match width {
+        CellWidth::U8 => "uint8_t",
+        CellWidth::U16 => "uint16_t",
+        CellWidth::U32 => "uint32_t",
+        CellWidth::U64 => "uint64_t",
+    }
+}
+
+/// Formats bytes as the elements of an array literal.
+fn byte_list(bytes: &[u8]) -> String {
+    let bytes: Vec<String> = bytes.iter().map(|byte| byte.to_string()).collect();
+    bytes.join(", ")
+}
+
+fn write_c(
+    out: &mut String,
+    basic_blocks: &super::ByteCodeProgram,
+    inline_input: &[u8],
+    config: &Config,
+) -> fmt::Result {
+    writeln!(out, "#include <stdint.h>")?;
+    writeln!(out, "#include <stdio.h>")?;
+    writeln!(out, "#include <stdlib.h>")?;
+    writeln!(out, "#include <string.h>")?;
+    writeln!(out)?;
+    writeln!(out, "typedef {} cell;", c_cell_type(config.cell_width))?;
+    writeln!(out, "#define CELL_MAX ((cell)-1)")?;
+    writeln!(out)?;
+    writeln!(out, "static cell *tape;")?;
+    writeln!(out, "static long long len = {};", config.tape_len.max(1))?;
+    writeln!(out, "static long long ptr = 0;")?;
+    if !inline_input.is_empty() {
+        writeln!(
+            out,
+            "static const unsigned char inline_input[] = {{{}}};",
+            byte_list(inline_input)
+        )?;
+        writeln!(out, "static size_t inline_pos = 0;")?;
+    }
+    writeln!(out)?;
+    writeln!(
+        out,
+        r#"static inline void fail(const char *message) {{
+    fflush(stdout);
+    fprintf(stderr, "error: %s\n", message);
+    exit(1);
+}}
+
+static inline void fail_at(const char *format, long long position) {{
+    char message[128];
+    snprintf(message, sizeof message, format, position);
+    fail(message);
+}}
+"#
+    )?;
+
+    writeln!(out, "static inline long long at(long long offset) {{")?;
+    writeln!(out, "    long long target = ptr + offset;")?;
+    writeln!(out, "    if (target >= 0 && target < len) return target;")?;
+    match config.tape {
+        TapeKind::Cyclic => {
+            writeln!(out, "    target %= len;")?;
+            writeln!(out, "    return target < 0 ? target + len : target;")?;
+        }
+        TapeKind::Bounded => {
+            writeln!(
+                out,
+                r#"    fail_at("pointer moved out of the tape to position %lld", target);"#
+            )?;
+            writeln!(out, "    return 0;")?;
+        }
+        TapeKind::Growable => {
+            writeln!(
+                out,
+                r#"    if (target < 0) fail_at("pointer moved out of the tape to position %lld", target);
+    long long new_len = target + 1 > len * 2 ? target + 1 : len * 2;
+    tape = realloc(tape, new_len * sizeof(cell));
+    if (!tape) fail("out of memory");
+    memset(tape + len, 0, (new_len - len) * sizeof(cell));
+    len = new_len;
+    return target;"#
+            )?;
+        }
+    }
+    writeln!(out, "}}")?;
+    writeln!(out)?;
+
+    writeln!(
+        out,
+        "static inline void add(long long offset, int64_t delta) {{"
+    )?;
+    writeln!(out, "    long long i = at(offset);")?;
+    match config.overflow {
+        Overflow::Wrap => writeln!(out, "    tape[i] += (cell)delta;")?,
+        Overflow::Saturate | Overflow::Error => {
+            let on_overflow = |bound: &str| match config.overflow {
+                Overflow::Saturate => format!("tape[i] = {};", bound),
+                _ => r#"fail_at("cell at position %lld went out of range", i);"#.to_string(),
+            };
+            writeln!(
+                out,
+                r#"    uint64_t magnitude = delta >= 0 ? (uint64_t)delta : -(uint64_t)delta;
+    if (delta >= 0) {{
+        if (magnitude > (uint64_t)(CELL_MAX - tape[i])) {}
+        else tape[i] += (cell)magnitude;
+    }} else {{
+        if (magnitude > (uint64_t)tape[i]) {}
+        else tape[i] -= (cell)magnitude;
+    }}"#,
+                on_overflow("CELL_MAX"),
+                on_overflow("0"),
+            )?;
+        }
+    }
+    writeln!(out, "}}")?;
+    writeln!(out)?;
+
+    writeln!(out, "static inline void getc_at(long long offset) {{")?;
+    writeln!(out, "    long long i = at(offset);")?;
+    if inline_input.is_empty() {
+        writeln!(out, "    int c = getchar();")?;
+    } else {
+        writeln!(
+            out,
+            "    int c = inline_pos < sizeof inline_input ? inline_input[inline_pos++] : getchar();"
+        )?;
+    }
+    writeln!(out, "    if (c != EOF) {{")?;
+    writeln!(out, "        tape[i] = (cell)c;")?;
+    writeln!(out, "        return;")?;
+    writeln!(out, "    }}")?;
+    match config.eof {
+        Eof::Unchanged => (),
+        Eof::Zero => writeln!(out, "    tape[i] = 0;")?,
+        Eof::MinusOne => writeln!(out, "    tape[i] = CELL_MAX;")?,
+        Eof::Error => writeln!(out, r#"    fail("unexpected end of input");"#)?,
+    }
+    writeln!(out, "}}")?;
+    writeln!(out)?;
+
+    if config.dialect.tape_dump {
+        writeln!(
+            out,
+            r#"static void dump(long long offset) {{
+    long long center = at(offset);
+    long long start = center > {radius} ? center - {radius} : 0;
+    long long end = center + {radius} < len - 1 ? center + {radius} : len - 1;
+    for (long long i = start; i <= end; i++) {{
+        if (i > start) fputs("  ", stderr);
+        fprintf(stderr, i == center ? "[%lld: %llu]" : "%lld: %llu", i, (unsigned long long)tape[i]);
+    }}
+    fputc('\n', stderr);
+}}
+"#,
+            radius = DUMP_RADIUS
+        )?;
+    }
+
+    writeln!(out, "int main(void) {{")?;
+    writeln!(out, "    tape = calloc(len, sizeof(cell));")?;
+    writeln!(out, r#"    if (!tape) fail("out of memory");"#)?;
+    let mut is_target = vec![false; basic_blocks.len()];
+    for bb in basic_blocks {
+        for target in bb.jz.into_iter().chain(bb.jnz) {
+            is_target[target] = true;
+        }
+    }
+    for (bb_no, bb) in basic_blocks.iter().enumerate() {
+        if is_target[bb_no] {
+            writeln!(out, "bb{}:", bb_no)?;
+        }
+        for &instr in &bb.instrs {
+            write!(out, "    ")?;
+            match instr {
+                Cmd::Add(offset, delta) => writeln!(out, "add({}, {});", offset, delta)?,
+                Cmd::Move(delta) => writeln!(out, "ptr = at({});", delta)?,
+                Cmd::Getc(offset) => writeln!(out, "getc_at({});", offset)?,
+                Cmd::Putc(offset) => {
+                    writeln!(out, "putchar((unsigned char)tape[at({})]);", offset)?
+                }
+                Cmd::Dump(offset) => writeln!(out, "dump({});", offset)?,
+                Cmd::SetZero(offset) => writeln!(out, "tape[at({})] = 0;", offset)?,
+                Cmd::MulAdd { offset, factor } => writeln!(
+                    out,
+                    "add({}, (int64_t)((uint64_t)tape[ptr] * {}ULL));",
+                    offset, factor as u64
+                )?,
+                Cmd::ScanRight(stride) => writeln!(out, "while (tape[ptr]) ptr = at({});", stride)?,
+                Cmd::ScanLeft(stride) => writeln!(out, "while (tape[ptr]) ptr = at(-{});", stride)?,
+            }
+        }
+        let target = |next: Option<usize>| next.map_or("end".to_string(), |bb| format!("bb{}", bb));
+        match (bb.jz, bb.jnz) {
+            (None, None) => writeln!(out, "    goto end;")?,
+            (jz, jnz) => writeln!(
+                out,
+                "    if (tape[ptr]) goto {}; else goto {};",
+                target(jnz),
+                target(jz)
+            )?,
+        }
+    }
+    writeln!(out, "end:")?;
+    writeln!(out, "    return 0;")?;
+    writeln!(out, "}}")
+}
+
+/// Translates a Brainfuck program into a self-contained Rust source file.
+///
+/// The output only depends on the standard library. It defines
+/// `pub fn run<I: BufRead, O: Write>(input: &mut I, output: &mut O) -> Result<(), Error>`
+/// with an `Error` type whose messages match [`Error`], and a `main` function that runs the
+/// program on stdin and stdout.
+pub fn to_rust(source: &str, config: &Config) -> Result<String, Error> {
+    let (code, inline_input) = split_inline_input(source, config);
+    let basic_blocks = into_basic_blocks(code, config)?;
+    let mut out = String::new();
+    write_rust(&mut out, &basic_blocks, inline_input, config)
+        .expect("writing to a String cannot fail");
+    Ok(out)
+}
+
+/// How a basic block ends, in terms of the source structure.
+enum BlockEnd {
+    /// A `[`; the following blocks up to the matching `]` are the loop body.
+    Open,
+    /// A `]`.
+    Close,
+    /// The end of the program.
+    End,
+}
+
+fn block_end(bb_no: usize, bb: &super::BasicBlock) -> BlockEnd {
+    match (bb.jz, bb.jnz) {
+        (None, None) => BlockEnd::End,
+        (Some(jz), _) if jz == bb_no + 1 => BlockEnd::Close,
+        _ => BlockEnd::Open,
+    }
+}
+
+fn rust_cell_type(width: CellWidth) -> &'static str {
+    match width {
+        CellWidth::U8 => "u8",
+        CellWidth::U16 => "u16",
+        CellWidth::U32 => "u32",
+        CellWidth::U64 => "u64",
+    }
+}
+
+fn write_rust(
+    out: &mut String,
+    basic_blocks: &super::ByteCodeProgram,
+    inline_input: &[u8],
+    config: &Config,
+) -> fmt::Result {
+    writeln!(out, "// Generated by esobox from a Brainfuck program.")?;
+    writeln!(out)?;
+    writeln!(out, "use std::fmt;")?;
+    writeln!(out, "use std::io::{{self, BufRead, Write}};")?;
+    writeln!(out)?;
+    writeln!(out, "type Cell = {};", rust_cell_type(config.cell_width))?;
+    writeln!(out)?;
+    writeln!(out, "const TAPE_LEN: usize = {};", config.tape_len.max(1))?;
+    writeln!(
+        out,
+        r#"
+/// Error that stops the program.
+#[derive(Debug)]
+pub enum Error {{
+    /// The pointer moved out of the tape.
+    TapeOutOfRange(isize),
+    /// A cell went out of its range.
+    CellOutOfRange(usize),
+    /// Input was read at end of file.
+    UnexpectedEof,
+    /// I/O error.
+    IoError(io::Error),
+}}
+
+impl fmt::Display for Error {{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {{
+        match self {{
+            Error::TapeOutOfRange(pos) => write!(f, "pointer moved out of the tape to position {{}}", pos),
+            Error::CellOutOfRange(pos) => write!(f, "cell at position {{}} went out of range", pos),
+            Error::UnexpectedEof => write!(f, "unexpected end of input"),
+            Error::IoError(_) => write!(f, "unexpected I/O error"),
+        }}
+    }}
+}}
+
+impl std::error::Error for Error {{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {{
+        match self {{
+            Error::IoError(error) => Some(error),
+            _ => None,
+        }}
+    }}
+}}
+
+impl From<io::Error> for Error {{
+    fn from(error: io::Error) -> Self {{
+        Error::IoError(error)
+    }}
+}}
+
+struct Tape {{
+    cells: Vec<Cell>,
+    ptr: usize,
+}}
+
+#[allow(dead_code)]
+impl Tape {{
+    #[inline]
+    fn at(&mut self, offset: isize) -> Result<usize, Error> {{
+        let len = self.cells.len();
+        let target = self.ptr as isize + offset;
+        if (0..len as isize).contains(&target) {{
+            return Ok(target as usize);
+        }}"#
+    )?;
+    match config.tape {
+        TapeKind::Cyclic => writeln!(out, "        Ok(target.rem_euclid(len as isize) as usize)")?,
+        TapeKind::Bounded => writeln!(out, "        Err(Error::TapeOutOfRange(target))")?,
+        TapeKind::Growable => writeln!(
+            out,
+            r#"        if target < 0 {{
+            return Err(Error::TapeOutOfRange(target));
+        }}
+        self.cells.resize((target as usize + 1).max(len * 2), 0);
+        Ok(target as usize)"#
+        )?,
+    }
+    writeln!(
+        out,
+        r#"    }}
+
+    #[inline]
+    fn get(&mut self, offset: isize) -> Result<Cell, Error> {{
+        let i = self.at(offset)?;
+        Ok(self.cells[i])
+    }}
+
+    #[inline]
+    fn set(&mut self, offset: isize, value: Cell) -> Result<(), Error> {{
+        let i = self.at(offset)?;
+        self.cells[i] = value;
+        Ok(())
+    }}
+"#
+    )?;
+    writeln!(out, "    #[inline]")?;
+    writeln!(
+        out,
+        "    fn add(&mut self, offset: isize, delta: i64) -> Result<(), Error> {{"
+    )?;
+    writeln!(out, "        let i = self.at(offset)?;")?;
+    match config.overflow {
+        Overflow::Wrap => writeln!(
+            out,
+            "        self.cells[i] = self.cells[i].wrapping_add(delta as Cell);"
+        )?,
+        Overflow::Saturate => writeln!(
+            out,
+            r#"        let magnitude = Cell::try_from(delta.unsigned_abs()).unwrap_or(Cell::MAX);
+        self.cells[i] = if delta >= 0 {{
+            self.cells[i].saturating_add(magnitude)
+        }} else {{
+            self.cells[i].saturating_sub(magnitude)
+        }};"#
+        )?,
+        Overflow::Error => writeln!(
+            out,
+            r#"        let value = Cell::try_from(delta.unsigned_abs()).ok().and_then(|magnitude| {{
+            if delta >= 0 {{
+                self.cells[i].checked_add(magnitude)
+            }} else {{
+                self.cells[i].checked_sub(magnitude)
+            }}
+        }});
+        self.cells[i] = value.ok_or(Error::CellOutOfRange(i))?;"#
+        )?,
+    }
+    writeln!(out, "        Ok(())")?;
+    writeln!(out, "    }}")?;
+    writeln!(out)?;
+    writeln!(out, "    #[inline]")?;
+    writeln!(
+        out,
+        "    fn getc<I: BufRead>(&mut self, offset: isize, input: &mut I) -> Result<(), Error> {{"
+    )?;
+    writeln!(out, "        let i = self.at(offset)?;")?;
+    writeln!(out, "        let buf = input.fill_buf()?;")?;
+    writeln!(out, "        if let Some(&byte) = buf.first() {{")?;
+    writeln!(out, "            input.consume(1);")?;
+    writeln!(out, "            self.cells[i] = byte as Cell;")?;
+    writeln!(out, "            return Ok(());")?;
+    writeln!(out, "        }}")?;
+    match config.eof {
+        Eof::Unchanged => (),
+        Eof::Zero => writeln!(out, "        self.cells[i] = 0;")?,
+        Eof::MinusOne => writeln!(out, "        self.cells[i] = Cell::MAX;")?,
+        Eof::Error => writeln!(out, "        return Err(Error::UnexpectedEof);")?,
+    }
+    if config.eof != Eof::Error {
+        writeln!(out, "        Ok(())")?;
+    }
+    writeln!(out, "    }}")?;
+    if config.dialect.tape_dump {
+        writeln!(
+            out,
+            r#"
+    fn dump(&mut self, offset: isize) -> Result<(), Error> {{
+        let center = self.at(offset)?;
+        let end = (center + {radius}).min(self.cells.len() - 1);
+        let cells: Vec<String> = (center.saturating_sub({radius})..=end)
+            .map(|i| if i == center {{
+                format!("[{{}}: {{}}]", i, self.cells[i])
+            }} else {{
+                format!("{{}}: {{}}", i, self.cells[i])
+            }})
+            .collect();
+        eprintln!("{{}}", cells.join("  "));
+        Ok(())
+    }}"#,
+            radius = DUMP_RADIUS
+        )?;
+    }
+    writeln!(out, "}}")?;
+    writeln!(out)?;
+
+    writeln!(out, "/// Runs the program.")?;
+    writeln!(out, "#[allow(unused_variables)]")?;
+    writeln!(
+        out,
+        "pub fn run<I: BufRead, O: Write>(input: &mut I, output: &mut O) -> Result<(), Error> {{"
+    )?;
+    writeln!(out, "    let mut t = Tape {{")?;
+    writeln!(out, "        cells: vec![0; TAPE_LEN],")?;
+    writeln!(out, "        ptr: 0,")?;
+    writeln!(out, "    }};")?;
+    if !inline_input.is_empty() {
+        writeln!(
+            out,
+            "    let mut input = io::Read::chain(&[{}][..], input);",
+            byte_list(inline_input)
+        )?;
+        writeln!(out, "    let input = &mut input;")?;
+    }
+    let mut depth = 1;
+    for (bb_no, bb) in basic_blocks.iter().enumerate() {
+        let indent = "    ".repeat(depth);
+        for &instr in &bb.instrs {
+            write!(out, "{}", indent)?;
+            match instr {
+                Cmd::Add(offset, delta) => writeln!(out, "t.add({}, {})?;", offset, delta)?,
+                Cmd::Move(delta) => writeln!(out, "t.ptr = t.at({})?;", delta)?,
+                Cmd::Getc(offset) => writeln!(out, "t.getc({}, input)?;", offset)?,
+                Cmd::Putc(offset) => {
+                    writeln!(out, "output.write_all(&[t.get({})? as u8])?;", offset)?
+                }
+                Cmd::Dump(offset) => writeln!(out, "t.dump({})?;", offset)?,
+                Cmd::SetZero(offset) => writeln!(out, "t.set({}, 0)?;", offset)?,
+                Cmd::MulAdd { offset, factor } => writeln!(
+                    out,
+                    "t.add({}, (t.cells[t.ptr] as u64).wrapping_mul({}) as i64)?;",
+                    offset, factor as u64
+                )?,
+                Cmd::ScanRight(stride) => writeln!(
+                    out,
+                    "while t.cells[t.ptr] != 0 {{ t.ptr = t.at({})?; }}",
+                    stride
+                )?,
+                Cmd::ScanLeft(stride) => writeln!(
+                    out,
+                    "while t.cells[t.ptr] != 0 {{ t.ptr = t.at(-{})?; }}",
+                    stride
+                )?,
+            }
+        }
+        match block_end(bb_no, bb) {
+            BlockEnd::Open => {
+                writeln!(out, "{}while t.cells[t.ptr] != 0 {{", indent)?;
+                depth += 1;
+            }
+            BlockEnd::Close => {
+                depth -= 1;
+                writeln!(out, "{}}}", "    ".repeat(depth))?;
+            }
+            BlockEnd::End => (),
+        }
+    }
+    writeln!(out, "    Ok(())")?;
+    writeln!(out, "}}")?;
+    writeln!(
+        out,
+        r#"
+#[allow(dead_code)]
+fn main() {{
+    let mut stdout = io::stdout().lock();
+    if let Err(error) = run(&mut io::stdin().lock(), &mut stdout) {{
+        let _ = stdout.flush();
+        eprintln!("error: {{}}", error);
+        std::process::exit(1);
+    }}
+}}"#
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::brainfuck::Dialect;
+
+    #[test]
+    fn test_to_c() {
+        let code = to_c("+[->+<]>.", &Config::default()).unwrap();
+        assert!(code.contains("typedef uint8_t cell;"));
+        assert!(code.contains("static long long len = 65536;"));
+        assert!(code.contains("add(1, (int64_t)((uint64_t)tape[ptr] * 1ULL));"));
+        assert!(code.contains("putchar((unsigned char)tape[at(1)]);"));
+
+        let config = Config {
+            cell_width: CellWidth::U32,
+            overflow: Overflow::Error,
+            tape: TapeKind::Bounded,
+            eof: Eof::Error,
+            ..Config::default()
+        };
+        let code = to_c(",[.,]", &config).unwrap();
+        assert!(code.contains("typedef uint32_t cell;"));
+        assert!(code.contains("went out of range"));
+        assert!(code.contains("pointer moved out of the tape"));
+        assert!(code.contains(r#"fail("unexpected end of input");"#));
+
+        let config = Config {
+            dialect: Dialect {
+                tape_dump: true,
+                inline_input: true,
+            },
+            ..Config::default()
+        };
+        let code = to_c(",#!ab", &config).unwrap();
+        assert!(code.contains("static const unsigned char inline_input[] = {97, 98};"));
+        assert!(code.contains("dump(0);"));
+        let code = to_c(",#!ab", &Config::default()).unwrap();
+        assert!(!code.contains("inline_input"));
+        assert!(!code.contains("dump("));
+
+        assert!(matches!(
+            to_c("[", &Config::default()),
+            Err(Error::SyntaxError(_))
+        ));
+    }
+
+    #[test]
+    fn test_to_rust() {
+        let code = to_rust("+[->+<]>[>,.]", &Config::default()).unwrap();
+        assert!(code.contains("type Cell = u8;"));
+        assert!(code.contains("const TAPE_LEN: usize = 65536;"));
+        assert!(code.contains("t.add(1, (t.cells[t.ptr] as u64).wrapping_mul(1) as i64)?;"));
+        assert!(code.contains(
+            "    while t.cells[t.ptr] != 0 {\n        t.getc(1, input)?;\n        output.write_all(&[t.get(1)? as u8])?;"
+        ));
+
+        let config = Config {
+            cell_width: CellWidth::U16,
+            overflow: Overflow::Saturate,
+            eof: Eof::Zero,
+            ..Config::default()
+        };
+        let code = to_rust(",.", &config).unwrap();
+        assert!(code.contains("type Cell = u16;"));
+        assert!(code.contains("saturating_add"));
+        assert!(code.contains("self.cells[i] = 0;"));
+    }
+}
@@ -0,0 +1,48 @@
+//! Execution limits shared by all languages.
+
+use std::time::{Duration, Instant};
+
+/// The step or time limit of a run was exceeded. Contains the number of steps executed so far.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct LimitExceeded(pub u64);
+
+/// Tracks the execution limits of a single run.
+pub(crate) struct Budget {
+    steps: u64,
+    max_steps: u64,
+    deadline: Option<Instant>,
+    next_check: u64,
+}
+
+impl Budget {
+    /// Number of steps between two checks of the clock.
+    const CHECK_INTERVAL: u64 = 1 << 16;
+
+    pub(crate) fn new(max_steps: Option<u64>, timeout: Option<Duration>) -> Self {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        Self {
+            steps: 0,
+            max_steps: max_steps.unwrap_or(u64::MAX),
+            deadline,
+            next_check: if deadline.is_some() { 0 } else { u64::MAX },
+        }
+    }
+
+    #[inline]
+    pub(crate) fn spend(&mut self, steps: u64) -> Result<(), LimitExceeded> {
+        self.steps = self.steps.saturating_add(steps);
+        if self.steps > self.max_steps {
+            return Err(LimitExceeded(self.steps));
+        }
+        if self.steps >= self.next_check {
+            if self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+            {
+                return Err(LimitExceeded(self.steps));
+            }
+            self.next_check = self.steps + Self::CHECK_INTERVAL;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,103 @@
+//! Command line arguments shared by the `esobox` binary and the [`corpus`](crate::corpus)
+//! runner, so that `.args` files accept exactly the options of the command line.
+
+use std::time::Duration;
+
+use clap::parser::ValueSource;
+use clap::{arg, value_parser, Arg, ArgMatches};
+
+use crate::{brainfuck, Options};
+
+/// The arguments that set [`Options`] for running a program.
+pub fn run_args() -> Vec<Arg<'static>> {
+    let mut args = vec![eof_arg()];
+    args.extend(dialect_args());
+    args.extend([
+        arg!(--"max-steps" <STEPS> "Stop the program after executing this many steps")
+            .required(false)
+            .value_parser(value_parser!(u64)),
+        arg!(--timeout <SECONDS> "Stop the program after running for this many seconds")
+            .required(false)
+            .value_parser(parse_timeout),
+        arg!(--"no-sandbox" "Let Funge-98 programs access files and environment variables"),
+        arg!(--"wide-words" "Run Malbolge with 20-trit words, and so 3^20 words of memory"),
+        arg!(--"codel-size" <PIXELS> "Size of a Piet codel [default: detected from the image]")
+            .required(false)
+            .value_parser(value_parser!(u64).range(1..)),
+        arg!(-v --value <VALUES> "Push these numbers onto the ><> stack before the program starts")
+            .required(false)
+            .multiple_values(true)
+            .value_parser(value_parser!(f64)),
+    ]);
+    args
+}
+
+/// Updates `options` with the [`run_args`] given on the command line, keeping the others.
+pub fn update_options(matches: &ArgMatches, options: &mut Options) {
+    if matches.value_source("eof") == Some(ValueSource::CommandLine) {
+        options.eof = eof_policy(matches);
+    }
+    options.dialect.tape_dump |= matches.contains_id("tape-dump");
+    options.dialect.inline_input |= matches.contains_id("inline-input");
+    if let Some(&max_steps) = matches.get_one::<u64>("max-steps") {
+        options.max_steps = Some(max_steps);
+    }
+    if let Some(&timeout) = matches.get_one::<Duration>("timeout") {
+        options.timeout = Some(timeout);
+    }
+    if matches.contains_id("no-sandbox") {
+        options.sandbox = false;
+    }
+    options.wide_words |= matches.contains_id("wide-words");
+    if let Some(&size) = matches.get_one::<u64>("codel-size") {
+        options.codel_size = Some(size as usize);
+    }
+    if let Some(values) = matches.get_many::<f64>("value") {
+        options.initial_stack = values.copied().collect();
+    }
+}
+
+/// The `--eof` argument, for commands that run Brainfuck programs.
+pub fn eof_arg() -> Arg<'static> {
+    arg!(--eof <POLICY> "Behavior of Brainfuck `,` on end of input")
+        .required(false)
+        .value_parser(["unchanged", "zero", "minus-one", "error"])
+        .default_value("unchanged")
+}
+
+/// The EOF policy chosen with [`eof_arg`].
+pub fn eof_policy(matches: &ArgMatches) -> brainfuck::Eof {
+    match matches.get_one::<String>("eof").unwrap() as &str {
+        "unchanged" => brainfuck::Eof::Unchanged,
+        "zero" => brainfuck::Eof::Zero,
+        "minus-one" => brainfuck::Eof::MinusOne,
+        "error" => brainfuck::Eof::Error,
+        _ => unreachable!(),
+    }
+}
+
+/// The flags that enable Brainfuck extensions.
+pub fn dialect_args() -> [Arg<'static>; 2] {
+    [
+        arg!(--"tape-dump" "Make Brainfuck `#` print the cells around the pointer to stderr"),
+        arg!(--"inline-input" "Treat the source after the first Brainfuck `!` as input"),
+    ]
+}
+
+/// The Brainfuck extensions enabled with [`dialect_args`].
+pub fn dialect(matches: &ArgMatches) -> brainfuck::Dialect {
+    brainfuck::Dialect {
+        tape_dump: matches.contains_id("tape-dump"),
+        inline_input: matches.contains_id("inline-input"),
+    }
+}
+
+/// Parses a number of seconds, rejecting values that are negative, not a number or too large
+/// for a [`Duration`].
+pub fn parse_timeout(value: &str) -> Result<Duration, String> {
+    value
+        .parse()
+        .ok()
+        .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+        .ok_or_else(|| "expected a non-negative number of seconds".to_owned())
+}
@@ -0,0 +1,366 @@
+//! Data-driven conformance tests for any language.
+//!
+//! A test corpus is a directory of source files, each with sidecar files of the same name and
+//! a different extension:
+//!
+//! - `.in`: input of the program. No input if missing.
+//! - `.out`: exact expected output.
+//! - `.err`: the program is expected to stop with an error whose message, including all of its
+//!   causes, contains the contents of this file with surrounding whitespace trimmed.
+//! - `.args`: options in the same syntax as the command line, such as `--eof zero`.
+//!
+//! A source file is any file with an extension of one of the [`languages`](crate::languages),
+//! and it is a test case only if it has a `.out` or `.err` file. Subdirectories are searched
+//! as well. Output written to stderr by the program, such as Brainfuck tape dumps, is not
+//! checked.
+
+use clap::Command;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+use crate::{cli, find_language_by_extension, Language, Options};
+
+/// Errors while loading a test corpus.
+#[derive(Error, Debug)]
+pub enum Error {
+    /// A file or directory of the corpus could not be read.
+    #[error("failed to read `{}`", .path.display())]
+    Io {
+        /// The file or directory that could not be read.
+        path: PathBuf,
+        /// The underlying error.
+        #[source]
+        source: io::Error,
+    },
+    /// A `.args` file contains something other than a known option.
+    #[error("invalid options in `{}`: {message}", .path.display())]
+    InvalidArgs {
+        /// The `.args` file.
+        path: PathBuf,
+        /// What is wrong with the options.
+        message: String,
+    },
+}
+
+/// A single test case of a corpus.
+pub struct Case {
+    /// Path of the source file.
+    pub path: PathBuf,
+    /// Language of the source file, from its extension.
+    pub language: &'static dyn Language,
+    /// Options to compile the program with.
+    pub options: Options,
+    /// Input of the program.
+    pub input: Vec<u8>,
+    /// Expected output, if any.
+    pub output: Option<Vec<u8>>,
+    /// Text expected in the error message, if the program should fail.
+    pub error: Option<String>,
+}
+
+/// Reason a test case failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Failure {
+    /// The program printed something other than expected.
+    Output {
+        /// The expected output.
+        expected: Vec<u8>,
+        /// The actual output.
+        actual: Vec<u8>,
+    },
+    /// The program stopped with an error that was not expected.
+    UnexpectedError(String),
+    /// The program finished successfully, but an error was expected.
+    MissingError {
+        /// Text expected in the error message.
+        expected: String,
+    },
+    /// The program stopped with an error other than the expected one.
+    WrongError {
+        /// Text expected in the error message.
+        expected: String,
+        /// The actual error message.
+        actual: String,
+    },
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Failure::Output { expected, actual } => {
+                let pos = expected
+                    .iter()
+                    .zip(actual)
+                    .position(|(a, b)| a != b)
+                    .unwrap_or_else(|| expected.len().min(actual.len()));
+                writeln!(f, "output differs at byte {}", pos)?;
+                writeln!(f, "expected: {}", excerpt(expected, pos))?;
+                write!(f, "  actual: {}", excerpt(actual, pos))
+            }
+            Failure::UnexpectedError(message) => write!(f, "unexpected error: {}", message),
+            Failure::MissingError { expected } => {
+                write!(f, "expected an error containing {:?}", expected)
+            }
+            Failure::WrongError { expected, actual } => write!(
+                f,
+                "expected an error containing {:?}, got: {}",
+                expected, actual
+            ),
+        }
+    }
+}
+
+/// Number of bytes shown on each side of the first difference in the output.
+const EXCERPT_RADIUS: usize = 20;
+
+/// Shows the bytes around `pos`, escaped.
+fn excerpt(bytes: &[u8], pos: usize) -> String {
+    let start = pos.saturating_sub(EXCERPT_RADIUS).min(bytes.len());
+    let end = (pos + EXCERPT_RADIUS).min(bytes.len());
+    let mut out = String::new();
+    if start > 0 {
+        out.push_str("...");
+    }
+    out.push('"');
+    out.extend(
+        bytes[start..end]
+            .iter()
+            .flat_map(|&b| b.escape_ascii())
+            .map(char::from),
+    );
+    out.push('"');
+    if end < bytes.len() {
+        out.push_str("...");
+    }
+    out
+}
+
+impl Case {
+    /// Loads the test case of a source file, or returns `None` if it is not one.
+    ///
+    /// `defaults` are used for the options not given in the `.args` file.
+    pub fn load(path: &Path, defaults: &Options) -> Result<Option<Self>, Error> {
+        let Some(language) = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(find_language_by_extension)
+        else {
+            return Ok(None);
+        };
+        let output = read_sidecar(path, "out")?;
+        let error = read_sidecar(path, "err")?
+            .map(|error| String::from_utf8_lossy(&error).trim().to_string());
+        if output.is_none() && error.is_none() {
+            return Ok(None);
+        }
+        let mut options = defaults.clone();
+        if let Some(args) = read_sidecar(path, "args")? {
+            parse_args(&String::from_utf8_lossy(&args), &mut options).map_err(|message| {
+                Error::InvalidArgs {
+                    path: path.with_extension("args"),
+                    message,
+                }
+            })?;
+        }
+        Ok(Some(Self {
+            path: path.to_path_buf(),
+            language,
+            options,
+            input: read_sidecar(path, "in")?.unwrap_or_default(),
+            output,
+            error,
+        }))
+    }
+
+    /// Runs the test case and checks the result.
+    ///
+    /// Returns an error only if the source file cannot be read; everything that goes wrong in
+    /// the program itself is a [`Failure`].
+    pub fn run(&self) -> Result<Result<(), Failure>, Error> {
+        let source = fs::read(&self.path).map_err(|source| Error::Io {
+            path: self.path.clone(),
+            source,
+        })?;
+        let mut actual = vec![];
+        let result = self
+            .language
+            .compile_bytes(&source, &self.options)
+            .and_then(|program| program.run(&mut &self.input[..], &mut actual));
+        let result = match (result, &self.error) {
+            (Ok(()), None) => Ok(()),
+            (Ok(()), Some(expected)) => Err(Failure::MissingError {
+                expected: expected.clone(),
+            }),
+            (Err(error), None) => Err(Failure::UnexpectedError(error_chain(&error))),
+            (Err(error), Some(expected)) => {
+                let actual = error_chain(&error);
+                if actual.contains(expected.as_str()) {
+                    Ok(())
+                } else {
+                    Err(Failure::WrongError {
+                        expected: expected.clone(),
+                        actual,
+                    })
+                }
+            }
+        };
+        Ok(result.and_then(|()| match &self.output {
+            Some(expected) if *expected != actual => Err(Failure::Output {
+                expected: expected.clone(),
+                actual,
+            }),
+            _ => Ok(()),
+        }))
+    }
+}
+
+/// Finds all test cases in the directory and its subdirectories, sorted by path.
+///
+/// `defaults` are used for the options not given in `.args` files.
+pub fn discover(dir: &Path, defaults: &Options) -> Result<Vec<Case>, Error> {
+    let mut cases = vec![];
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let entries = fs::read_dir(&dir).map_err(|source| Error::Io {
+            path: dir.clone(),
+            source,
+        })?;
+        for entry in entries {
+            let path = entry
+                .map_err(|source| Error::Io {
+                    path: dir.clone(),
+                    source,
+                })?
+                .path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else if let Some(case) = Case::load(&path, defaults)? {
+                cases.push(case);
+            }
+        }
+    }
+    cases.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(cases)
+}
+
+/// Reads the sidecar file with the extension, if it exists.
+fn read_sidecar(path: &Path, extension: &str) -> Result<Option<Vec<u8>>, Error> {
+    let path = path.with_extension(extension);
+    match fs::read(&path) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(source) => Err(Error::Io { path, source }),
+    }
+}
+
+/// Applies options in command line syntax, accepting the same options as `esobox` itself.
+fn parse_args(args: &str, options: &mut Options) -> Result<(), String> {
+    let matches = Command::new("args")
+        .no_binary_name(true)
+        .allow_negative_numbers(true)
+        .args(cli::run_args())
+        .try_get_matches_from(args.split_whitespace())
+        .map_err(|error| {
+            // keep only the first line, without the usage and help hints
+            let message = error.to_string();
+            let line = message.lines().next().unwrap_or_default();
+            line.trim_start_matches("error: ").to_string()
+        })?;
+    cli::update_options(&matches, options);
+    Ok(())
+}
+
+/// Formats an error with all of its causes.
+fn error_chain(error: &dyn std::error::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::brainfuck;
+    use std::time::Duration;
+
+    #[test]
+    fn test_corpus() {
+        let dir = std::env::temp_dir().join(format!("esobox-corpus-{}", std::process::id()));
+        let sub = dir.join("sub");
+        fs::create_dir_all(&sub).unwrap();
+        let files: &[(&str, &str)] = &[
+            ("cat.bf", ",[.,]"),
+            ("cat.in", "abc"),
+            ("cat.out", "abc"),
+            ("cat.args", "--eof zero\n"),
+            ("eof.b", ",,"),
+            ("eof.args", "--eof error"),
+            ("eof.err", "unexpected end of input\n"),
+            ("wrong.bf", "+++."),
+            ("wrong.out", "\x02"),
+            ("no_expectation.bf", "+"),
+            ("notes.txt", "not a source file"),
+            ("sub/fails.bf", "+"),
+            ("sub/fails.err", "end of input"),
+        ];
+        for (name, contents) in files {
+            fs::write(dir.join(name), contents).unwrap();
+        }
+
+        let cases = discover(&dir, &Options::default()).unwrap();
+        let names: Vec<_> = cases
+            .iter()
+            .map(|case| case.path.strip_prefix(&dir).unwrap().to_path_buf())
+            .collect();
+        assert_eq!(
+            names,
+            ["cat.bf", "eof.b", "sub/fails.bf", "wrong.bf"].map(PathBuf::from)
+        );
+        assert_eq!(cases[0].options.eof, brainfuck::Eof::Zero);
+        let results: Vec<_> = cases.iter().map(|case| case.run().unwrap()).collect();
+        assert_eq!(results[0], Ok(()));
+        assert_eq!(results[1], Ok(()));
+        assert_eq!(
+            results[2],
+            Err(Failure::MissingError {
+                expected: "end of input".to_string()
+            })
+        );
+        let failure = results[3].clone().unwrap_err();
+        assert_eq!(
+            failure.to_string(),
+            "output differs at byte 0\nexpected: \"\\x02\"\n  actual: \"\\x03\""
+        );
+
+        // options not in the `.args` file keep their defaults
+        let defaults = Options {
+            timeout: Some(Duration::from_secs(1)),
+            ..Options::default()
+        };
+        fs::write(dir.join("cat.args"), "--tape-dump -v -1 2.5").unwrap();
+        let case = Case::load(&dir.join("cat.bf"), &defaults).unwrap().unwrap();
+        assert_eq!(case.options.timeout, defaults.timeout);
+        assert!(case.options.dialect.tape_dump);
+        assert_eq!(case.options.initial_stack, [-1.0, 2.5]);
+
+        fs::write(dir.join("cat.args"), "--eof sometimes").unwrap();
+        let Err(error) = discover(&dir, &Options::default()) else {
+            panic!("expected invalid options");
+        };
+        assert!(matches!(error, Error::InvalidArgs { .. }));
+        assert!(error.to_string().contains("\"sometimes\""), "{}", error);
+        fs::remove_dir_all(&dir).ok();
+        assert!(matches!(
+            discover(&dir, &Options::default()),
+            Err(Error::Io { .. })
+        ));
+    }
+}
@@ -0,0 +1,78 @@
+//! The crate-level error type.
+
+use thiserror::Error;
+
+use crate::{befunge93, brainfuck, fish, funge98, malbolge, piet, unlambda, whitespace};
+
+/// Error type of the [`Language`](crate::Language) and [`Program`](crate::Program) traits.
+///
+/// The language-specific error is available through [`std::error::Error::source`].
+#[derive(Error, Debug)]
+pub enum Error {
+    /// The source code could not be compiled.
+    #[error("failed to compile {language} program")]
+    Compile {
+        /// Name of the language.
+        language: &'static str,
+        /// The language-specific error.
+        #[source]
+        source: LanguageError,
+    },
+    /// The program was terminated by an error while running.
+    #[error("{language} program terminated with an error")]
+    Runtime {
+        /// Name of the language.
+        language: &'static str,
+        /// The language-specific error.
+        #[source]
+        source: LanguageError,
+    },
+}
+
+impl Error {
+    pub(crate) fn compile(language: &'static str, source: impl Into<LanguageError>) -> Self {
+        Self::Compile {
+            language,
+            source: source.into(),
+        }
+    }
+
+    pub(crate) fn runtime(language: &'static str, source: impl Into<LanguageError>) -> Self {
+        Self::Runtime {
+            language,
+            source: source.into(),
+        }
+    }
+}
+
+/// Error of one of the languages.
+#[derive(Error, Debug)]
+pub enum LanguageError {
+    /// Error from [`brainfuck`].
+    #[error(transparent)]
+    Brainfuck(#[from] brainfuck::Error),
+    /// Error from [`fish`].
+    #[error(transparent)]
+    Fish(#[from] fish::Error),
+    /// Error from [`befunge93`].
+    #[error(transparent)]
+    Befunge93(#[from] befunge93::Error),
+    /// Error from [`funge98`].
+    #[error(transparent)]
+    Funge98(#[from] funge98::Error),
+    /// Error from [`malbolge`].
+    #[error(transparent)]
+    Malbolge(#[from] malbolge::Error),
+    /// Error from [`piet`].
+    #[error(transparent)]
+    Piet(#[from] piet::Error),
+    /// Error from [`unlambda`].
+    #[error(transparent)]
+    Unlambda(#[from] unlambda::Error),
+    /// Error from [`whitespace`].
+    #[error(transparent)]
+    Whitespace(#[from] whitespace::Error),
+    /// The source of a language whose programs are text is not valid UTF-8.
+    #[error("source is not valid UTF-8")]
+    InvalidUtf8(#[from] std::str::Utf8Error),
+}
@@ -0,0 +1,630 @@
+//! An implementation of [><>] (Fish).
+//!
+//! The codebox starts as the lines of the source and grows when `p` writes outside of it. The
+//! instruction pointer wraps around its edges. Every value on the stacks and in the codebox
+//! is a 64-bit float, so `,` can give fractions as in the reference interpreter, and
+//! integers are exact up to 2<sup>53</sup>.
+//!
+//! Everything the reference interpreter rejects with "something smells fishy..." stops the
+//! program with [`Error::Fishy`], whose [`Fault`] tells what went wrong.
+//!
+//! The parts left undefined by the specification behave as follows:
+//!
+//! - Cells outside the lines of the source are empty. `g` reads them as 0, string mode
+//!   pushes them as spaces, and executing them does nothing, like executing a space.
+//! - `.` moves the IP to the given cell, and the IP then moves on as after any other
+//!   instruction, so the instruction in that cell itself is skipped.
+//! - `.`, `g` and `p` need integer coordinates, and `.` and `p` need non-negative ones.
+//! - `]` on the only stack empties it and its register.
+//! - Characters are read and written as UTF-8. `i` pushes -1 at EOF. `n` writes integers
+//!   without a fractional part.
+//!
+//! [><>]: https://esolangs.org/wiki/Fish
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::time::Duration;
+use thiserror::Error;
+
+use crate::budget::{Budget, LimitExceeded};
+use crate::input::read_char;
+use crate::rng::Rng;
+use crate::{Language, Options};
+
+/// Error enum for ><>.
+#[derive(Error, Debug)]
+pub enum Error {
+    /// The program did something invalid, which the reference interpreter reports as
+    /// "something smells fishy...".
+    #[error("something smells fishy... ({fault} at ({x}, {y}))")]
+    Fishy {
+        /// What went wrong.
+        fault: Fault,
+        /// Column of the instruction.
+        x: i64,
+        /// Row of the instruction.
+        y: i64,
+    },
+    /// The program ran past [`Config::max_steps`] or [`Config::timeout`].
+    /// Contains the number of steps executed so far.
+    #[error("execution limit exceeded after {0} steps")]
+    LimitExceeded(u64),
+    /// I/O error, which may occur during I/O operations.
+    #[error("unexpected I/O error")]
+    IoError(#[from] io::Error),
+}
+
+impl From<LimitExceeded> for Error {
+    fn from(LimitExceeded(steps): LimitExceeded) -> Self {
+        Error::LimitExceeded(steps)
+    }
+}
+
+/// The reason of an [`Error::Fishy`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Fault {
+    /// The cell does not hold an instruction. Contains the value of the cell.
+    InvalidInstruction(f64),
+    /// An instruction needed more values than the stack has.
+    StackUnderflow,
+    /// `,` or `%` with a divisor of zero.
+    DivisionByZero,
+    /// `o` of a value that is not a Unicode scalar value.
+    InvalidCharacter(f64),
+    /// `.`, `g` or `p` with coordinates they cannot use.
+    InvalidCoordinates(f64, f64),
+    /// `[` with a count that is not an integer between 0 and the size of the stack.
+    InvalidCount(f64),
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Fault::InvalidInstruction(value) => match to_char(value) {
+                Some(c) => write!(f, "invalid instruction `{}`", c.escape_debug()),
+                None => write!(f, "invalid instruction {}", value),
+            },
+            Fault::StackUnderflow => write!(f, "stack underflow"),
+            Fault::DivisionByZero => write!(f, "division by zero"),
+            Fault::InvalidCharacter(value) => write!(f, "{} is not a character", value),
+            Fault::InvalidCoordinates(x, y) => write!(f, "invalid coordinates ({}, {})", x, y),
+            Fault::InvalidCount(count) => {
+                write!(f, "cannot move {} values to a new stack", count)
+            }
+        }
+    }
+}
+
+/// Settings of the ><> interpreter.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Config {
+    /// Maximum number of steps to execute, where each step executes one cell of the
+    /// codebox. `None` means unlimited.
+    pub max_steps: Option<u64>,
+    /// Wall-clock time limit of a single run. `None` means unlimited.
+    pub timeout: Option<Duration>,
+    /// Seed of the random directions of `x`. `None` picks a different seed on every run.
+    pub seed: Option<u64>,
+    /// Values on the stack when the program starts, from the bottom up, like the `-v` option
+    /// of the reference interpreter.
+    pub initial_stack: Vec<f64>,
+}
+
+/// ><> interpreter.
+pub fn run<I: BufRead, O: Write>(source: &str, input: &mut I, output: &mut O) -> Result<(), Error> {
+    run_with_config(source, input, output, &Config::default())
+}
+
+/// ><> interpreter with a custom configuration.
+pub fn run_with_config<I: BufRead, O: Write>(
+    source: &str,
+    input: &mut I,
+    output: &mut O,
+    config: &Config,
+) -> Result<(), Error> {
+    Program::compile_with_config(source, config).run(input, output)
+}
+
+/// The [`Language`] implementation for ><>.
+///
+/// Compiles with the default [`Config`], except for the limits and the initial stack taken
+/// from [`Options`].
+#[derive(Debug, Clone, Copy)]
+pub struct Fish;
+
+impl Language for Fish {
+    fn name(&self) -> &'static str {
+        "fish"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["><>"]
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["fish"]
+    }
+
+    fn compile(
+        &self,
+        source: &str,
+        options: &Options,
+    ) -> Result<Box<dyn crate::Program>, crate::Error> {
+        let config = Config {
+            max_steps: options.max_steps,
+            timeout: options.timeout,
+            initial_stack: options.initial_stack.clone(),
+            ..Config::default()
+        };
+        Ok(Box::new(Program::compile_with_config(source, &config)))
+    }
+}
+
+/// A ><> program loaded into its codebox.
+///
+/// Each run starts from a fresh copy of the codebox, so changes made by `p` do not carry over
+/// to the next run.
+#[derive(Debug, Clone)]
+pub struct Program {
+    codebox: Codebox,
+    config: Config,
+}
+
+impl Program {
+    /// Loads the source code with the default [`Config`].
+    pub fn compile(source: &str) -> Self {
+        Self::compile_with_config(source, &Config::default())
+    }
+
+    /// Loads the source code with a custom configuration. Any source is a valid program,
+    /// since invalid instructions are only reported when they are executed.
+    ///
+    /// Lines may end with `\n` or `\r\n`.
+    pub fn compile_with_config(source: &str, config: &Config) -> Self {
+        let lines: Vec<Vec<char>> = source.lines().map(|line| line.chars().collect()).collect();
+        let width = lines.iter().map(Vec::len).max().unwrap_or(0).max(1);
+        let height = lines.len().max(1);
+        let mut cells = vec![0.0; width * height];
+        for (y, line) in lines.iter().enumerate() {
+            for (x, &c) in line.iter().enumerate() {
+                cells[y * width + x] = c as u32 as f64;
+            }
+        }
+        Self {
+            codebox: Codebox {
+                width: width as i64,
+                height: height as i64,
+                dense_width: width as i64,
+                cells,
+                sparse: HashMap::new(),
+            },
+            config: config.clone(),
+        }
+    }
+
+    /// The configuration the program was compiled with.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Runs the program from the start.
+    pub fn run<I: BufRead, O: Write>(&self, input: &mut I, output: &mut O) -> Result<(), Error> {
+        let mut state = State {
+            codebox: self.codebox.clone(),
+            stacks: vec![Stack {
+                values: self.config.initial_stack.clone(),
+                register: None,
+            }],
+            x: 0,
+            y: 0,
+            dx: 1,
+            dy: 0,
+        };
+        let result = state.execute(input, output, &self.config);
+        output.flush()?;
+        result
+    }
+}
+
+impl crate::Program for Program {
+    fn run(
+        &self,
+        mut input: &mut dyn BufRead,
+        mut output: &mut dyn Write,
+    ) -> Result<(), crate::Error> {
+        Program::run(self, &mut input, &mut output)
+            .map_err(|error| crate::Error::runtime(Fish.name(), error))
+    }
+}
+
+/// The codebox: the cells of the source, and the cells written by `p` outside of them.
+#[derive(Debug, Clone)]
+struct Codebox {
+    /// Width the IP wraps around at, which grows when `p` writes further to the right.
+    width: i64,
+    /// Height the IP wraps around at, which grows when `p` writes further down.
+    height: i64,
+    /// Width of the lines of the source, which are stored in `cells`.
+    dense_width: i64,
+    cells: Vec<f64>,
+    sparse: HashMap<(i64, i64), f64>,
+}
+
+impl Codebox {
+    fn dense_index(&self, x: i64, y: i64) -> Option<usize> {
+        let in_bounds = (0..self.dense_width).contains(&x)
+            && (0..self.cells.len() as i64 / self.dense_width).contains(&y);
+        in_bounds.then(|| (y * self.dense_width + x) as usize)
+    }
+
+    fn get(&self, x: i64, y: i64) -> f64 {
+        match self.dense_index(x, y) {
+            Some(index) => self.cells[index],
+            None => self.sparse.get(&(x, y)).copied().unwrap_or(0.0),
+        }
+    }
+
+    fn set(&mut self, x: i64, y: i64, value: f64) {
+        match self.dense_index(x, y) {
+            Some(index) => self.cells[index] = value,
+            None => {
+                self.sparse.insert((x, y), value);
+                self.width = self.width.max(x + 1);
+                self.height = self.height.max(y + 1);
+            }
+        }
+    }
+}
+
+/// One stack of the stack of stacks, with its register.
+#[derive(Debug, Clone, Default)]
+struct Stack {
+    values: Vec<f64>,
+    register: Option<f64>,
+}
+
+/// The state of a running program.
+struct State {
+    codebox: Codebox,
+    /// The stack of stacks. The last one is the current stack.
+    stacks: Vec<Stack>,
+    x: i64,
+    y: i64,
+    dx: i64,
+    dy: i64,
+}
+
+impl State {
+    fn execute<I: BufRead, O: Write>(
+        &mut self,
+        input: &mut I,
+        output: &mut O,
+        config: &Config,
+    ) -> Result<(), Error> {
+        let mut budget = Budget::new(config.max_steps, config.timeout);
+        let mut rng = Rng::new(config.seed);
+        // the quote that started string mode
+        let mut quote = None;
+        loop {
+            budget.spend(1)?;
+            let value = self.codebox.get(self.x, self.y);
+            let instruction = to_char(value).unwrap_or(char::REPLACEMENT_CHARACTER);
+            if let Some(c) = quote {
+                if instruction == c {
+                    quote = None;
+                } else {
+                    self.push(if value == 0.0 {
+                        ' ' as u32 as f64
+                    } else {
+                        value
+                    });
+                }
+                self.advance();
+                continue;
+            }
+            match instruction {
+                '\0' | ' ' => (),
+                '>' => (self.dx, self.dy) = (1, 0),
+                '<' => (self.dx, self.dy) = (-1, 0),
+                '^' => (self.dx, self.dy) = (0, -1),
+                'v' => (self.dx, self.dy) = (0, 1),
+                '/' => (self.dx, self.dy) = (-self.dy, -self.dx),
+                '\\' => (self.dx, self.dy) = (self.dy, self.dx),
+                '|' => self.dx = -self.dx,
+                '_' => self.dy = -self.dy,
+                '#' => (self.dx, self.dy) = (-self.dx, -self.dy),
+                'x' => (self.dx, self.dy) = [(1, 0), (-1, 0), (0, -1), (0, 1)][rng.below(4)],
+                '!' => self.advance(),
+                '?' => {
+                    if self.pop()? == 0.0 {
+                        self.advance();
+                    }
+                }
+                '.' => {
+                    let (x, y) = self.pop_coordinates()?;
+                    if x < 0 || y < 0 {
+                        return Err(self.fishy(Fault::InvalidCoordinates(x as f64, y as f64)));
+                    }
+                    (self.x, self.y) = (x, y);
+                }
+                '0'..='9' | 'a'..='f' => self.push(instruction.to_digit(16).unwrap() as f64),
+                '+' | '-' | '*' | ',' | '%' | '=' | ')' | '(' => {
+                    let x = self.pop()?;
+                    let y = self.pop()?;
+                    if x == 0.0 && matches!(instruction, ',' | '%') {
+                        return Err(self.fishy(Fault::DivisionByZero));
+                    }
+                    self.push(match instruction {
+                        '+' => y + x,
+                        '-' => y - x,
+                        '*' => y * x,
+                        ',' => y / x,
+                        // the result has the sign of the divisor, as in Python
+                        '%' => y - x * (y / x).floor(),
+                        '=' => (y == x) as u8 as f64,
+                        ')' => (y > x) as u8 as f64,
+                        _ => (y < x) as u8 as f64,
+                    });
+                }
+                '"' | '\'' => quote = Some(instruction),
+                ':' => {
+                    let top = self.pop()?;
+                    self.push(top);
+                    self.push(top);
+                }
+                '~' => {
+                    self.pop()?;
+                }
+                '$' => {
+                    let values = self.top_values(2)?;
+                    values.swap(0, 1);
+                }
+                '@' => self.top_values(3)?.rotate_right(1),
+                '}' => {
+                    let values = &mut self.stack().values;
+                    let shift = values.len().min(1);
+                    values.rotate_right(shift);
+                }
+                '{' => {
+                    let values = &mut self.stack().values;
+                    let shift = values.len().min(1);
+                    values.rotate_left(shift);
+                }
+                'r' => self.stack().values.reverse(),
+                'l' => {
+                    let len = self.stack().values.len();
+                    self.push(len as f64);
+                }
+                '[' => {
+                    let count = self.pop()?;
+                    let len = self.stack().values.len();
+                    let start = match to_integer(count) {
+                        Some(count) if (0..=len as i64).contains(&count) => len - count as usize,
+                        _ => return Err(self.fishy(Fault::InvalidCount(count))),
+                    };
+                    let values = self.stack().values.split_off(start);
+                    self.stacks.push(Stack {
+                        values,
+                        register: None,
+                    });
+                }
+                ']' => {
+                    let Stack { values, .. } = self.stacks.pop().unwrap();
+                    match self.stacks.last_mut() {
+                        Some(stack) => stack.values.extend(values),
+                        None => self.stacks.push(Stack::default()),
+                    }
+                }
+                '&' => match self.stack().register.take() {
+                    Some(value) => self.push(value),
+                    None => {
+                        let value = self.pop()?;
+                        self.stack().register = Some(value);
+                    }
+                },
+                'o' => {
+                    let value = self.pop()?;
+                    match to_char(value) {
+                        Some(c) => write!(output, "{}", c)?,
+                        None => return Err(self.fishy(Fault::InvalidCharacter(value))),
+                    }
+                }
+                'n' => {
+                    let value = self.pop()?;
+                    write!(output, "{}", value)?;
+                }
+                'i' => {
+                    let value = read_char(input)?.map_or(-1.0, |c| c as u32 as f64);
+                    self.push(value);
+                }
+                'g' => {
+                    let (x, y) = self.pop_coordinates()?;
+                    let value = self.codebox.get(x, y);
+                    self.push(value);
+                }
+                'p' => {
+                    let (x, y) = self.pop_coordinates()?;
+                    let value = self.pop()?;
+                    if x < 0 || y < 0 {
+                        return Err(self.fishy(Fault::InvalidCoordinates(x as f64, y as f64)));
+                    }
+                    self.codebox.set(x, y, value);
+                }
+                ';' => return Ok(()),
+                _ => return Err(self.fishy(Fault::InvalidInstruction(value))),
+            }
+            self.advance();
+        }
+    }
+
+    /// Moves the IP one cell in its direction, wrapping around the edges of the codebox.
+    fn advance(&mut self) {
+        self.x += self.dx;
+        self.y += self.dy;
+        if self.x >= self.codebox.width {
+            self.x = 0;
+        } else if self.x < 0 {
+            self.x = self.codebox.width - 1;
+        }
+        if self.y >= self.codebox.height {
+            self.y = 0;
+        } else if self.y < 0 {
+            self.y = self.codebox.height - 1;
+        }
+    }
+
+    fn fishy(&self, fault: Fault) -> Error {
+        Error::Fishy {
+            fault,
+            x: self.x,
+            y: self.y,
+        }
+    }
+
+    fn stack(&mut self) -> &mut Stack {
+        self.stacks.last_mut().unwrap()
+    }
+
+    fn push(&mut self, value: f64) {
+        self.stack().values.push(value);
+    }
+
+    fn pop(&mut self) -> Result<f64, Error> {
+        match self.stack().values.pop() {
+            Some(value) => Ok(value),
+            None => Err(self.fishy(Fault::StackUnderflow)),
+        }
+    }
+
+    /// The top `n` values of the current stack, from the bottom up.
+    fn top_values(&mut self, n: usize) -> Result<&mut [f64], Error> {
+        let len = self.stack().values.len();
+        if len < n {
+            return Err(self.fishy(Fault::StackUnderflow));
+        }
+        Ok(&mut self.stack().values[len - n..])
+    }
+
+    /// Pops `y` and then `x`, which must be integers.
+    fn pop_coordinates(&mut self) -> Result<(i64, i64), Error> {
+        let y = self.pop()?;
+        let x = self.pop()?;
+        match (to_integer(x), to_integer(y)) {
+            (Some(x), Some(y)) => Ok((x, y)),
+            _ => Err(self.fishy(Fault::InvalidCoordinates(x, y))),
+        }
+    }
+}
+
+/// Converts a value to an integer, if it is one.
+fn to_integer(value: f64) -> Option<i64> {
+    (value.fract() == 0.0 && value.abs() < 2f64.powi(63)).then_some(value as i64)
+}
+
+/// Converts a value to the character with that code point, if there is one.
+fn to_char(value: f64) -> Option<char> {
+    to_integer(value)
+        .and_then(|value| u32::try_from(value).ok())
+        .and_then(char::from_u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_str(source: &str, input: &str, config: &Config) -> (Result<(), Error>, String) {
+        let config = Config {
+            max_steps: Some(100_000),
+            ..config.clone()
+        };
+        let mut stdout: Vec<u8> = vec![];
+        let res = run_with_config(source, &mut input.as_bytes(), &mut stdout, &config);
+        (res, String::from_utf8(stdout).unwrap())
+    }
+
+    #[test]
+    fn test_fish() {
+        let default = Config::default();
+        let cases = [
+            // string mode and a loop that wraps around through empty cells
+            (">\"olleh\"v\n        >l?!;o", "", "hello"),
+            // arithmetic on floats, with `%` taking the sign of the divisor
+            ("12,n 03-2%n 3b*n a3-n 23(n 23)n 22=n;", "", "0.51337101"),
+            // moving part of the stack to a new stack and back
+            ("1234 2[r]lnnnnn;", "", "43421"),
+            // `$`, `@`, `}` and `{` on [1, 2, 3]
+            ("123$nnn; 123@nnn; 123}nnn; 123{nnn;", "", "231"),
+            // the register of the current stack
+            ("5&3&nn;", "", "53"),
+            // `i` at EOF, and `o` of what it read
+            ("ii+n;", "a", "96"),
+            ("io;", "é", "é"),
+            // mirrors: down through `\`, left through `/`, and back with `|`
+            ("\\  ;\n1\n/n", "", "1"),
+            ("12!;n|", "", "21"),
+            // `.` skips the instruction it lands on, and `!` and `?` skip the next one
+            ("40.;;1n!;0?;2n;", "", "12"),
+        ];
+        for (source, input, expected) in cases {
+            let (res, stdout) = run_str(source, input, &default);
+            assert!(res.is_ok(), "{:?} failed: {:?}", source, res);
+            assert_eq!(stdout, expected, "{:?}", source);
+        }
+
+        // self-modification: overwrite the `;` with `X`, read it back, then fail on it
+        let (res, stdout) = run_str("\"X\"a0pa0gn;", "", &default);
+        assert_eq!(stdout, "88");
+        assert!(matches!(
+            res,
+            Err(Error::Fishy { fault: Fault::InvalidInstruction(value), x: 10, y: 0 }) if value == 88.0
+        ));
+
+        // the initial stack, like `-v` of the reference interpreter
+        let config = Config {
+            initial_stack: vec![1.5, -2.0],
+            ..Config::default()
+        };
+        assert_eq!(run_str("+n;", "", &config).1, "-0.5");
+
+        // `x` either prints or wraps around to `;`, and up and down come back to `x`
+        let mut outputs = vec![];
+        for seed in 0..16 {
+            let config = Config {
+                seed: Some(seed),
+                ..Config::default()
+            };
+            let (res, stdout) = run_str("x1n;", "", &config);
+            assert!(res.is_ok());
+            outputs.push(stdout);
+        }
+        assert!(outputs.contains(&"1".to_string()));
+        assert!(outputs.contains(&String::new()));
+    }
+
+    #[test]
+    fn test_fish_errors() {
+        let cases = [
+            ("1+", Fault::StackUnderflow, 1),
+            ("10,", Fault::DivisionByZero, 2),
+            ("01-o", Fault::InvalidCharacter(-1.0), 3),
+            ("15[", Fault::InvalidCount(5.0), 2),
+            ("12,0g", Fault::InvalidCoordinates(0.5, 0.0), 4),
+            ("01-0.", Fault::InvalidCoordinates(-1.0, 0.0), 4),
+            ("1$", Fault::StackUnderflow, 1),
+            ("?", Fault::StackUnderflow, 0),
+            ("z", Fault::InvalidInstruction('z' as u32 as f64), 0),
+        ];
+        for (source, expected, expected_x) in cases {
+            let (res, _) = run_str(source, "", &Config::default());
+            match res {
+                Err(Error::Fishy { fault, x, y: 0 }) => {
+                    assert_eq!((fault, x), (expected, expected_x), "{:?}", source)
+                }
+                res => panic!("{:?} gave {:?}", source, res),
+            }
+        }
+        let (res, _) = run_str(" ", "", &Config::default());
+        assert!(matches!(res, Err(Error::LimitExceeded(100_001))));
+    }
+}
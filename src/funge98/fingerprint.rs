@@ -0,0 +1,302 @@
+//! Fingerprints, the extension mechanism of Funge-98, and the ones built in.
+//!
+//! A fingerprint is loaded with `(` and gives meaning to some of the instructions `A` to `Z`
+//! until it is unloaded with `)`. New fingerprints implement [`Fingerprint`] and are made
+//! available through [`Config::fingerprints`](super::Config::fingerprints).
+
+use std::any::TypeId;
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use super::space::{Space, Vector};
+use super::{Error, Ip};
+
+/// A Funge-98 fingerprint.
+pub trait Fingerprint: fmt::Debug + Send + Sync {
+    /// The fingerprint ID, usually the name of the fingerprint as computed by [`fingerprint_id`].
+    fn id(&self) -> i64;
+
+    /// The instructions defined by the fingerprint, as a string of uppercase letters. Any other
+    /// character makes [`Program::compile_with_config`](super::Program::compile_with_config)
+    /// fail with [`Error::InvalidFingerprint`].
+    fn letters(&self) -> &'static str;
+
+    /// Executes one of the instructions in [`letters`](Fingerprint::letters).
+    ///
+    /// Errors stop the whole program; to make the instruction fail in the usual Funge-98 way,
+    /// call [`Context::reflect`] instead.
+    fn execute(&self, letter: u8, context: &mut Context<'_>) -> Result<(), Error>;
+}
+
+/// Computes the ID of a fingerprint from its name, treating each character as a base-256 digit.
+pub fn fingerprint_id(name: &str) -> i64 {
+    name.bytes().fold(0i64, |id, byte| {
+        id.wrapping_mul(256).wrapping_add(byte as i64)
+    })
+}
+
+/// All the built-in fingerprints.
+pub fn builtin_fingerprints() -> Vec<Arc<dyn Fingerprint>> {
+    vec![
+        Arc::new(Null),
+        Arc::new(Roma),
+        Arc::new(Modu),
+        Arc::new(Hrti),
+    ]
+}
+
+/// The state of the IP executing a fingerprint instruction, and Funge-Space.
+pub struct Context<'a> {
+    pub(super) ip: &'a mut Ip,
+    pub(super) space: &'a mut Space,
+}
+
+impl Context<'_> {
+    /// Pops a value from the top stack, or 0 if it is empty.
+    pub fn pop(&mut self) -> i64 {
+        self.ip.pop()
+    }
+
+    /// Pushes a value onto the top stack.
+    pub fn push(&mut self, value: i64) {
+        self.ip.push(value);
+    }
+
+    /// Pops a vector, the y coordinate first.
+    pub fn pop_vector(&mut self) -> Vector {
+        self.ip.pop_vector()
+    }
+
+    /// Pushes a vector, the x coordinate first.
+    pub fn push_vector(&mut self, vector: Vector) {
+        self.ip.push_vector(vector);
+    }
+
+    /// Reverses the direction of the IP.
+    pub fn reflect(&mut self) {
+        self.ip.reflect();
+    }
+
+    /// Position of the IP, which is the position of the instruction being executed.
+    pub fn position(&self) -> Vector {
+        self.ip.pos
+    }
+
+    /// Direction of the IP.
+    pub fn delta(&self) -> Vector {
+        self.ip.delta
+    }
+
+    /// Changes the direction of the IP.
+    pub fn set_delta(&mut self, delta: Vector) {
+        self.ip.delta = delta;
+    }
+
+    /// The storage offset, which `p` and `g` add to their coordinates.
+    pub fn storage_offset(&self) -> Vector {
+        self.ip.offset
+    }
+
+    /// Reads a cell of Funge-Space at an absolute position.
+    pub fn get(&self, pos: Vector) -> i64 {
+        self.space.get(pos)
+    }
+
+    /// Writes a cell of Funge-Space at an absolute position.
+    pub fn put(&mut self, pos: Vector, value: i64) {
+        self.space.put(pos, value);
+    }
+
+    /// State of the IP kept by fingerprints, one value per type, created on first use.
+    pub fn data<T: Default + Send + 'static>(&mut self) -> &mut T {
+        self.ip
+            .data
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::<T>::default())
+            .downcast_mut()
+            .expect("data is stored under its own type")
+    }
+}
+
+/// `NULL`: every letter reflects, which hides the meaning of previously loaded fingerprints.
+#[derive(Debug, Clone, Copy)]
+pub struct Null;
+
+impl Fingerprint for Null {
+    fn id(&self) -> i64 {
+        fingerprint_id("NULL")
+    }
+
+    fn letters(&self) -> &'static str {
+        "ABCDEFGHIJKLMNOPQRSTUVWXYZ"
+    }
+
+    fn execute(&self, _letter: u8, context: &mut Context<'_>) -> Result<(), Error> {
+        context.reflect();
+        Ok(())
+    }
+}
+
+/// `ROMA`: the Roman numerals `C`, `D`, `I`, `L`, `M`, `V` and `X` push their values.
+#[derive(Debug, Clone, Copy)]
+pub struct Roma;
+
+impl Fingerprint for Roma {
+    fn id(&self) -> i64 {
+        fingerprint_id("ROMA")
+    }
+
+    fn letters(&self) -> &'static str {
+        "CDILMVX"
+    }
+
+    fn execute(&self, letter: u8, context: &mut Context<'_>) -> Result<(), Error> {
+        context.push(match letter {
+            b'I' => 1,
+            b'V' => 5,
+            b'X' => 10,
+            b'L' => 50,
+            b'C' => 100,
+            b'D' => 500,
+            _ => 1000,
+        });
+        Ok(())
+    }
+}
+
+/// `MODU`: more kinds of modulo, each popping `b` and then `a` and pushing 0 if `b` is 0.
+///
+/// - `M`: the result has the sign of `b`, as if division rounded down.
+/// - `U`: the absolute value of the remainder.
+/// - `R`: the remainder as in C, with the sign of `a`.
+#[derive(Debug, Clone, Copy)]
+pub struct Modu;
+
+impl Fingerprint for Modu {
+    fn id(&self) -> i64 {
+        fingerprint_id("MODU")
+    }
+
+    fn letters(&self) -> &'static str {
+        "MRU"
+    }
+
+    fn execute(&self, letter: u8, context: &mut Context<'_>) -> Result<(), Error> {
+        let b = context.pop();
+        let a = context.pop();
+        let rem = if b == 0 { 0 } else { a.wrapping_rem(b) };
+        context.push(match letter {
+            b'M' if rem != 0 && (rem < 0) != (b < 0) => rem + b,
+            b'U' => rem.wrapping_abs(),
+            _ => rem,
+        });
+        Ok(())
+    }
+}
+
+/// `HRTI`: a timer with microsecond granularity, kept separately by each IP.
+///
+/// - `G`: pushes the granularity in microseconds, which is 1.
+/// - `M`: marks the current time.
+/// - `T`: pushes the microseconds since the mark, or reflects if there is none.
+/// - `E`: erases the mark.
+/// - `S`: pushes the microseconds since the start of the current second.
+#[derive(Debug, Clone, Copy)]
+pub struct Hrti;
+
+/// The mark of [`Hrti`].
+#[derive(Default)]
+struct Mark(Option<Instant>);
+
+impl Fingerprint for Hrti {
+    fn id(&self) -> i64 {
+        fingerprint_id("HRTI")
+    }
+
+    fn letters(&self) -> &'static str {
+        "EGMST"
+    }
+
+    fn execute(&self, letter: u8, context: &mut Context<'_>) -> Result<(), Error> {
+        match letter {
+            b'G' => context.push(1),
+            b'M' => context.data::<Mark>().0 = Some(Instant::now()),
+            b'T' => match context.data::<Mark>().0 {
+                Some(mark) => context.push(mark.elapsed().as_micros() as i64),
+                None => context.reflect(),
+            },
+            b'E' => context.data::<Mark>().0 = None,
+            _ => {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default();
+                context.push(now.subsec_micros() as i64);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::funge98::{run_with_config, Config, Program};
+
+    fn run_str(source: &str) -> String {
+        let mut stdout: Vec<u8> = vec![];
+        let config = Config {
+            max_steps: Some(10_000),
+            ..Config::default()
+        };
+        let res = run_with_config(source, &mut &b""[..], &mut stdout, &config);
+        assert!(res.is_ok(), "{:?}", res);
+        String::from_utf8(stdout).unwrap()
+    }
+
+    #[test]
+    fn test_fingerprints() {
+        assert_eq!(fingerprint_id("NULL"), 0x4e554c4c);
+        // `(` pushes the ID and 1, and unknown fingerprints reflect
+        assert_eq!(run_str("\"AMOR\"4(..@"), format!("1 {} ", 0x524f4d41));
+        assert_eq!(run_str("\"?OOF\"4#v(1.@\n        >2.@"), "2 ");
+        assert_eq!(
+            run_str("\"AMOR\"4($$MDCLXVI.......@"),
+            "1 5 10 50 100 500 1000 "
+        );
+        assert_eq!(
+            run_str("\"UDOM\"4($$ 3-5M. 3-5U. 3-5R. 5 03-M. 50M.@"),
+            "2 3 -3 -1 0 "
+        );
+        // NULL hides ROMA until it is unloaded, and letters without a meaning reflect
+        assert_eq!(run_str("\"LLUN\"4($$ #vI\n            >2.@"), "2 ");
+        assert_eq!(run_str("\"AMOR\"4($$\"LLUN\"4($$\"LLUN\"4)I.@"), "1 ");
+        assert_eq!(run_str("#vQ\n >1.@"), "1 ");
+        assert_eq!(
+            run_str("\"ITRH\"4($$ #vT2.@\n            >G.MT01-`.@"),
+            "1 1 "
+        );
+
+        #[derive(Debug)]
+        struct Lowercase;
+        impl Fingerprint for Lowercase {
+            fn id(&self) -> i64 {
+                fingerprint_id("LOWR")
+            }
+            fn letters(&self) -> &'static str {
+                "Ab"
+            }
+            fn execute(&self, _letter: u8, _context: &mut Context<'_>) -> Result<(), Error> {
+                Ok(())
+            }
+        }
+        let config = Config {
+            fingerprints: vec![Arc::new(Lowercase)],
+            ..Config::default()
+        };
+        assert!(matches!(
+            Program::compile_with_config("@", &config),
+            Err(Error::InvalidFingerprint { letter: 'b', .. })
+        ));
+    }
+}
@@ -0,0 +1,897 @@
+//! An implementation of [Funge-98] in two dimensions, also known as Befunge-98.
+//!
+//! Funge-Space is unbounded in every direction and wraps around as Lahey-space. Cells and
+//! stack values are 64-bit signed integers with wrapping arithmetic. Each IP has a stack of
+//! stacks, manipulated by `{`, `}` and `u`, and loads [fingerprints](Fingerprint) with `(`
+//! and `)`. `NULL`, `ROMA`, `MODU` and `HRTI` are built in, and more can be added through
+//! [`Config::fingerprints`].
+//!
+//! The file I/O instructions `i` and `o` are only available when [`Config::sandbox`] is
+//! disabled; in the sandbox, they reflect and `y` does not report environment variables.
+//! Concurrency (`t`) and the system call `=` are not supported, and reflect like every other
+//! unknown instruction.
+//!
+//! The parts left open by the specification behave as follows:
+//!
+//! - `/` and `%` by zero push 0. Both round towards zero.
+//! - `,` writes the lowest byte of the value, and `.` writes the number followed by a space.
+//!   `&` reads a decimal number like in [Befunge-93](crate::befunge93), and `~` reads a single
+//!   byte. Both reflect at EOF.
+//! - `k` executes the next instruction from its own position. If that leaves the IP where it
+//!   was, the IP then moves past the executed instruction; otherwise it moves on from wherever
+//!   the instruction took it.
+//! - `q` with a nonzero exit code stops the program with [`Error::Quit`].
+//! - `{`, `}` and `u` reflect when there is not enough memory for the values they move or
+//!   add, after popping their count.
+//!
+//! [Funge-98]: https://github.com/catseye/Funge-98/blob/master/doc/funge98.markdown
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+use crate::budget::{Budget, LimitExceeded};
+use crate::input::{read_byte, read_number};
+use crate::rng::Rng;
+use crate::{Language, Options};
+
+mod fingerprint;
+mod space;
+
+pub use fingerprint::{
+    builtin_fingerprints, fingerprint_id, Context, Fingerprint, Hrti, Modu, Null, Roma,
+};
+pub use space::Vector;
+use space::{add, Space};
+
+const SPACE: i64 = b' ' as i64;
+
+/// Error enum for Funge-98.
+#[derive(Error, Debug)]
+pub enum Error {
+    /// The program executed `q` with a nonzero exit code. Contains the exit code.
+    #[error("program quit with exit code {0}")]
+    Quit(i32),
+    /// The program ran past [`Config::max_steps`] or [`Config::timeout`].
+    /// Contains the number of steps executed so far.
+    #[error("execution limit exceeded after {0} steps")]
+    LimitExceeded(u64),
+    /// A fingerprint in [`Config::fingerprints`] defines an instruction that is not one of `A`
+    /// to `Z`.
+    #[error("fingerprint {id:#x} defines `{letter}`, which is not an uppercase letter")]
+    InvalidFingerprint {
+        /// ID of the fingerprint.
+        id: i64,
+        /// The invalid letter.
+        letter: char,
+    },
+    /// I/O error, which may occur during I/O operations.
+    #[error("unexpected I/O error")]
+    IoError(#[from] io::Error),
+}
+
+impl From<LimitExceeded> for Error {
+    fn from(LimitExceeded(steps): LimitExceeded) -> Self {
+        Error::LimitExceeded(steps)
+    }
+}
+
+/// Settings of the Funge-98 interpreter.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Maximum number of steps to execute, where each cell the IP passes is a step, including
+    /// the spaces and the comments it skips. `{`, `}` and `u` also count a step for each value
+    /// they move or add. `None` means unlimited.
+    pub max_steps: Option<u64>,
+    /// Wall-clock time limit of a single run. `None` means unlimited.
+    pub timeout: Option<Duration>,
+    /// Seed of the random directions of `?`. `None` picks a different seed on every run.
+    pub seed: Option<u64>,
+    /// Whether to keep the program from accessing the file system and the environment.
+    /// Enabled by default.
+    pub sandbox: bool,
+    /// Fingerprints that can be loaded with `(`. By default, [`builtin_fingerprints`].
+    pub fingerprints: Vec<Arc<dyn Fingerprint>>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            max_steps: None,
+            timeout: None,
+            seed: None,
+            sandbox: true,
+            fingerprints: builtin_fingerprints(),
+        }
+    }
+}
+
+/// Funge-98 interpreter.
+pub fn run<I: BufRead, O: Write>(source: &str, input: &mut I, output: &mut O) -> Result<(), Error> {
+    run_with_config(source, input, output, &Config::default())
+}
+
+/// Funge-98 interpreter with a custom configuration.
+pub fn run_with_config<I: BufRead, O: Write>(
+    source: &str,
+    input: &mut I,
+    output: &mut O,
+    config: &Config,
+) -> Result<(), Error> {
+    Program::compile_with_config(source, config)?.run(input, output)
+}
+
+/// The [`Language`] implementation for Funge-98.
+///
+/// Compiles with the default [`Config`], except for the limits and the sandbox taken from
+/// [`Options`].
+#[derive(Debug, Clone, Copy)]
+pub struct Funge98;
+
+impl Language for Funge98 {
+    fn name(&self) -> &'static str {
+        "funge98"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["befunge98", "bf98"]
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["b98", "bf98"]
+    }
+
+    fn compile(
+        &self,
+        source: &str,
+        options: &Options,
+    ) -> Result<Box<dyn crate::Program>, crate::Error> {
+        let config = Config {
+            max_steps: options.max_steps,
+            timeout: options.timeout,
+            sandbox: options.sandbox,
+            ..Config::default()
+        };
+        let program = Program::compile_with_config(source, &config)
+            .map_err(|error| crate::Error::compile(self.name(), error))?;
+        Ok(Box::new(program))
+    }
+}
+
+/// A Funge-98 program loaded into Funge-Space.
+///
+/// Each run starts from a fresh copy of Funge-Space.
+#[derive(Debug, Clone)]
+pub struct Program {
+    space: Space,
+    config: Config,
+}
+
+impl Program {
+    /// Loads the source code with the default [`Config`].
+    pub fn compile(source: &str) -> Result<Self, Error> {
+        Self::compile_with_config(source, &Config::default())
+    }
+
+    /// Loads the source code with a custom configuration.
+    ///
+    /// Lines may end with `\n`, `\r\n` or `\r`, and form feeds are ignored. Any text is a valid
+    /// program, so loading only fails with [`Error::InvalidFingerprint`] for a fingerprint
+    /// whose [`letters`](Fingerprint::letters) are not all uppercase.
+    pub fn compile_with_config(source: &str, config: &Config) -> Result<Self, Error> {
+        for fingerprint in &config.fingerprints {
+            if let Some(letter) = fingerprint
+                .letters()
+                .chars()
+                .find(|letter| !letter.is_ascii_uppercase())
+            {
+                return Err(Error::InvalidFingerprint {
+                    id: fingerprint.id(),
+                    letter,
+                });
+            }
+        }
+        let mut space = Space::default();
+        space.load((0, 0), source.chars().map(|c| c as i64), false);
+        Ok(Self {
+            space,
+            config: config.clone(),
+        })
+    }
+
+    /// The configuration the program was compiled with.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Runs the program from the start.
+    pub fn run<I: BufRead, O: Write>(&self, input: &mut I, output: &mut O) -> Result<(), Error> {
+        let mut machine = Machine {
+            space: self.space.clone(),
+            ip: Ip::default(),
+            config: &self.config,
+            input,
+            output,
+            budget: Budget::new(self.config.max_steps, self.config.timeout),
+            rng: Rng::new(self.config.seed),
+        };
+        let result = machine.run();
+        machine.output.flush()?;
+        result
+    }
+}
+
+impl crate::Program for Program {
+    fn run(
+        &self,
+        mut input: &mut dyn BufRead,
+        mut output: &mut dyn Write,
+    ) -> Result<(), crate::Error> {
+        Program::run(self, &mut input, &mut output)
+            .map_err(|error| crate::Error::runtime(Funge98.name(), error))
+    }
+}
+
+/// An instruction pointer.
+pub(crate) struct Ip {
+    pos: Vector,
+    delta: Vector,
+    offset: Vector,
+    /// The stack stack, with the top of stack stack (TOSS) last.
+    stacks: Vec<Vec<i64>>,
+    string_mode: bool,
+    /// Indices into [`Config::fingerprints`] giving the meaning of each of `A` to `Z`.
+    semantics: [Vec<usize>; 26],
+    /// State kept by fingerprints.
+    data: HashMap<TypeId, Box<dyn Any + Send>>,
+}
+
+impl Default for Ip {
+    fn default() -> Self {
+        Self {
+            pos: (0, 0),
+            delta: (1, 0),
+            offset: (0, 0),
+            stacks: vec![vec![]],
+            string_mode: false,
+            semantics: Default::default(),
+            data: HashMap::new(),
+        }
+    }
+}
+
+impl Ip {
+    fn toss(&mut self) -> &mut Vec<i64> {
+        self.stacks.last_mut().expect("there is always a stack")
+    }
+
+    fn pop(&mut self) -> i64 {
+        self.toss().pop().unwrap_or(0)
+    }
+
+    fn push(&mut self, value: i64) {
+        self.toss().push(value);
+    }
+
+    fn pop_vector(&mut self) -> Vector {
+        let y = self.pop();
+        (self.pop(), y)
+    }
+
+    fn push_vector(&mut self, (x, y): Vector) {
+        self.toss().extend([x, y]);
+    }
+
+    /// Pops a null-terminated string, with the first character on top.
+    fn pop_string(&mut self) -> String {
+        let mut string = String::new();
+        loop {
+            match self.pop() {
+                0 => return string,
+                value => string.push(
+                    u32::try_from(value)
+                        .ok()
+                        .and_then(char::from_u32)
+                        .unwrap_or(char::REPLACEMENT_CHARACTER),
+                ),
+            }
+        }
+    }
+
+    fn reflect(&mut self) {
+        self.delta = (self.delta.0.wrapping_neg(), self.delta.1.wrapping_neg());
+    }
+}
+
+/// Makes room for `n` more values on the stack, returning whether there is enough memory.
+fn reserve(stack: &mut Vec<i64>, n: u64) -> bool {
+    usize::try_from(n).is_ok_and(|n| stack.try_reserve(n).is_ok())
+}
+
+/// Moves the top `n` values of `from` onto `to` in the same order, as if `from` had zeros
+/// below its bottom.
+fn transfer(from: &mut Vec<i64>, to: &mut Vec<i64>, n: usize) {
+    if n > from.len() {
+        to.resize(to.len() + n - from.len(), 0);
+        to.append(from);
+    } else {
+        to.extend(from.drain(from.len() - n..));
+    }
+}
+
+/// Whether to keep running after an instruction.
+enum Flow {
+    Continue,
+    Stop,
+}
+
+/// The state of a single run.
+struct Machine<'a, I, O> {
+    space: Space,
+    ip: Ip,
+    config: &'a Config,
+    input: &'a mut I,
+    output: &'a mut O,
+    budget: Budget,
+    rng: Rng,
+}
+
+impl<I: BufRead, O: Write> Machine<'_, I, O> {
+    fn run(&mut self) -> Result<(), Error> {
+        loop {
+            self.budget.spend(1)?;
+            let value = self.space.get(self.ip.pos);
+            if self.ip.string_mode {
+                if value == b'"' as i64 {
+                    self.ip.string_mode = false;
+                } else {
+                    self.ip.push(value);
+                    // consecutive spaces in a string push a single space
+                    while value == SPACE && self.space.get(self.next()) == SPACE {
+                        self.budget.spend(1)?;
+                        self.ip.pos = self.next();
+                    }
+                }
+            } else if value == SPACE || value == b';' as i64 {
+                self.ip.pos = self.find_instruction(self.ip.pos)?;
+                continue;
+            } else if let Flow::Stop = self.execute(value)? {
+                return Ok(());
+            }
+            self.ip.pos = self.next();
+        }
+    }
+
+    /// The position after moving the IP once.
+    fn next(&self) -> Vector {
+        self.space.advance(self.ip.pos, self.ip.delta)
+    }
+
+    /// Finds the first instruction from `pos` along the path of the IP, skipping spaces and
+    /// everything between `;`s.
+    fn find_instruction(&mut self, mut pos: Vector) -> Result<Vector, Error> {
+        let delta = self.ip.delta;
+        loop {
+            let value = self.space.get(pos);
+            if value == b';' as i64 {
+                pos = self.space.advance(pos, delta);
+                while self.space.get(pos) != b';' as i64 {
+                    self.budget.spend(1)?;
+                    pos = self.space.advance(pos, delta);
+                }
+            } else if value != SPACE {
+                return Ok(pos);
+            }
+            self.budget.spend(1)?;
+            pos = self.space.advance(pos, delta);
+        }
+    }
+
+    fn execute(&mut self, value: i64) -> Result<Flow, Error> {
+        let Ok(command) = u8::try_from(value) else {
+            self.ip.reflect();
+            return Ok(Flow::Continue);
+        };
+        let ip = &mut self.ip;
+        match command {
+            b'0'..=b'9' => ip.push((command - b'0') as i64),
+            b'a'..=b'f' => ip.push((command - b'a' + 10) as i64),
+            b'+' | b'-' | b'*' | b'/' | b'%' | b'`' => {
+                let b = ip.pop();
+                let a = ip.pop();
+                ip.push(match command {
+                    b'+' => a.wrapping_add(b),
+                    b'-' => a.wrapping_sub(b),
+                    b'*' => a.wrapping_mul(b),
+                    b'/' | b'%' if b == 0 => 0,
+                    b'/' => a.wrapping_div(b),
+                    b'%' => a.wrapping_rem(b),
+                    _ => (a > b) as i64,
+                });
+            }
+            b'!' => {
+                let a = ip.pop();
+                ip.push((a == 0) as i64);
+            }
+            b'>' => ip.delta = (1, 0),
+            b'<' => ip.delta = (-1, 0),
+            b'^' => ip.delta = (0, -1),
+            b'v' => ip.delta = (0, 1),
+            b'?' => ip.delta = [(1, 0), (-1, 0), (0, -1), (0, 1)][self.rng.below(4)],
+            b'_' => ip.delta = if ip.pop() == 0 { (1, 0) } else { (-1, 0) },
+            b'|' => ip.delta = if ip.pop() == 0 { (0, 1) } else { (0, -1) },
+            b'[' => ip.delta = (ip.delta.1, ip.delta.0.wrapping_neg()),
+            b']' => ip.delta = (ip.delta.1.wrapping_neg(), ip.delta.0),
+            b'r' => ip.reflect(),
+            b'x' => ip.delta = ip.pop_vector(),
+            b'w' => {
+                let b = ip.pop();
+                let a = ip.pop();
+                if a < b {
+                    ip.delta = (ip.delta.1, ip.delta.0.wrapping_neg());
+                } else if a > b {
+                    ip.delta = (ip.delta.1.wrapping_neg(), ip.delta.0);
+                }
+            }
+            b'"' => ip.string_mode = true,
+            b'\'' => {
+                let next = self.next();
+                self.ip.push(self.space.get(next));
+                self.ip.pos = next;
+            }
+            b's' => {
+                let next = self.next();
+                let value = self.ip.pop();
+                self.space.put(next, value);
+                self.ip.pos = next;
+            }
+            b':' => {
+                let a = ip.pop();
+                ip.toss().extend([a, a]);
+            }
+            b'\\' => {
+                let b = ip.pop();
+                let a = ip.pop();
+                ip.toss().extend([b, a]);
+            }
+            b'$' => {
+                ip.pop();
+            }
+            b'n' => ip.toss().clear(),
+            b'.' => {
+                let value = ip.pop();
+                write!(self.output, "{} ", value)?;
+            }
+            b',' => {
+                let value = ip.pop();
+                self.output.write_all(&[value as u8])?;
+            }
+            b'#' => self.ip.pos = self.next(),
+            b'j' => {
+                let n = ip.pop();
+                self.ip.pos = self.space.jump(self.ip.pos, self.ip.delta, n);
+            }
+            b'k' => return self.iterate(),
+            b'p' => {
+                let pos = add(ip.pop_vector(), ip.offset);
+                let value = ip.pop();
+                self.space.put(pos, value);
+            }
+            b'g' => {
+                let pos = add(ip.pop_vector(), ip.offset);
+                ip.push(self.space.get(pos));
+            }
+            b'&' => match read_number(self.input)? {
+                Some(value) => self.ip.push(value),
+                None => self.ip.reflect(),
+            },
+            b'~' => match read_byte(self.input)? {
+                Some(byte) => self.ip.push(byte as i64),
+                None => self.ip.reflect(),
+            },
+            b'{' => {
+                let n = ip.pop();
+                self.budget.spend(n.unsigned_abs())?;
+                let mut toss = vec![];
+                let soss = ip.toss();
+                let room = if n > 0 { &mut toss } else { &mut *soss };
+                if !reserve(room, n.unsigned_abs()) {
+                    ip.reflect();
+                    return Ok(Flow::Continue);
+                }
+                if n > 0 {
+                    transfer(soss, &mut toss, n as usize);
+                } else {
+                    soss.resize(soss.len() + n.unsigned_abs() as usize, 0);
+                }
+                let offset = ip.offset;
+                ip.push_vector(offset);
+                ip.offset = add(ip.pos, ip.delta);
+                ip.stacks.push(toss);
+            }
+            b'}' if ip.stacks.len() > 1 => {
+                let n = ip.pop();
+                self.budget.spend(n.unsigned_abs())?;
+                let soss = ip.stacks.len() - 2;
+                if n > 0 && !reserve(&mut ip.stacks[soss], n as u64) {
+                    ip.reflect();
+                    return Ok(Flow::Continue);
+                }
+                let mut toss = ip.stacks.pop().expect("there are at least two stacks");
+                ip.offset = ip.pop_vector();
+                let soss = ip.toss();
+                if n > 0 {
+                    transfer(&mut toss, soss, n as usize);
+                } else {
+                    soss.truncate(soss.len().saturating_sub(n.unsigned_abs() as usize));
+                }
+            }
+            b'u' if ip.stacks.len() > 1 => {
+                let count = ip.pop();
+                self.budget.spend(count.unsigned_abs())?;
+                let [.., soss, toss] = &mut ip.stacks[..] else {
+                    unreachable!("there are at least two stacks");
+                };
+                let (from, to) = if count > 0 {
+                    (soss, toss)
+                } else {
+                    (toss, soss)
+                };
+                if !reserve(to, count.unsigned_abs()) {
+                    ip.reflect();
+                    return Ok(Flow::Continue);
+                }
+                for _ in 0..count.unsigned_abs() {
+                    to.push(from.pop().unwrap_or(0));
+                }
+            }
+            b'(' | b')' => self.fingerprint(command == b'(')?,
+            b'A'..=b'Z' => {
+                let letter = (command - b'A') as usize;
+                match ip.semantics[letter].last() {
+                    Some(&index) => {
+                        let fingerprint = Arc::clone(&self.config.fingerprints[index]);
+                        let mut context = Context {
+                            ip: &mut self.ip,
+                            space: &mut self.space,
+                        };
+                        fingerprint.execute(command, &mut context)?;
+                    }
+                    None => ip.reflect(),
+                }
+            }
+            b'i' => self.input_file(),
+            b'o' => self.output_file(),
+            b'y' => self.sysinfo(),
+            b'z' => (),
+            b'@' => return Ok(Flow::Stop),
+            b'q' => {
+                let code = ip.pop() as i32;
+                return if code == 0 {
+                    Ok(Flow::Stop)
+                } else {
+                    Err(Error::Quit(code))
+                };
+            }
+            _ => ip.reflect(),
+        }
+        Ok(Flow::Continue)
+    }
+
+    /// `k`: executes the next instruction as many times as popped.
+    fn iterate(&mut self) -> Result<Flow, Error> {
+        let n = self.ip.pop();
+        let start = self.ip.pos;
+        let target = self.find_instruction(self.next())?;
+        if n <= 0 {
+            self.ip.pos = target;
+            return Ok(Flow::Continue);
+        }
+        let value = self.space.get(target);
+        for _ in 0..n {
+            self.budget.spend(1)?;
+            if let Flow::Stop = self.execute(value)? {
+                return Ok(Flow::Stop);
+            }
+        }
+        if self.ip.pos == start {
+            self.ip.pos = target;
+        }
+        Ok(Flow::Continue)
+    }
+
+    /// `(` and `)`: loads or unloads a fingerprint.
+    fn fingerprint(&mut self, load: bool) -> Result<(), Error> {
+        let count = self.ip.pop();
+        self.budget.spend(count.max(0) as u64)?;
+        let mut id: i64 = 0;
+        for _ in 0..count {
+            id = id.wrapping_mul(256).wrapping_add(self.ip.pop());
+        }
+        let Some(index) = self
+            .config
+            .fingerprints
+            .iter()
+            .position(|fingerprint| fingerprint.id() == id)
+        else {
+            self.ip.reflect();
+            return Ok(());
+        };
+        for letter in self.config.fingerprints[index].letters().bytes() {
+            let semantics = &mut self.ip.semantics[(letter - b'A') as usize];
+            if load {
+                semantics.push(index);
+            } else {
+                semantics.pop();
+            }
+        }
+        if load {
+            self.ip.push(id);
+            self.ip.push(1);
+        }
+        Ok(())
+    }
+
+    /// `i`: loads a file into Funge-Space.
+    fn input_file(&mut self) {
+        let name = self.ip.pop_string();
+        let flags = self.ip.pop();
+        let origin = self.ip.pop_vector();
+        let contents = match self.config.sandbox {
+            true => None,
+            false => fs::read(name).ok(),
+        };
+        let Some(contents) = contents else {
+            self.ip.reflect();
+            return;
+        };
+        let cells = contents.into_iter().map(|byte| byte as i64);
+        let size = self
+            .space
+            .load(add(origin, self.ip.offset), cells, flags & 1 != 0);
+        self.ip.push_vector(size);
+        self.ip.push_vector(origin);
+    }
+
+    /// `o`: writes a rectangle of Funge-Space to a file.
+    fn output_file(&mut self) {
+        let name = self.ip.pop_string();
+        let flags = self.ip.pop();
+        let origin = add(self.ip.pop_vector(), self.ip.offset);
+        let (width, height) = self.ip.pop_vector();
+        if self.config.sandbox || width < 0 || height < 0 {
+            self.ip.reflect();
+            return;
+        }
+        let text_mode = flags & 1 != 0;
+        let mut lines = vec![];
+        for y in 0..height {
+            let mut line: Vec<u8> = (0..width)
+                .map(|x| self.space.get(add(origin, (x, y))) as u8)
+                .collect();
+            if text_mode {
+                line.truncate(line.iter().rposition(|&b| b != b' ').map_or(0, |i| i + 1));
+            }
+            lines.push(line);
+        }
+        if text_mode {
+            while lines.last().is_some_and(|line| line.is_empty()) {
+                lines.pop();
+            }
+        }
+        let mut contents = vec![];
+        for line in lines {
+            contents.extend(line);
+            contents.push(b'\n');
+        }
+        if fs::write(name, contents).is_err() {
+            self.ip.reflect();
+        }
+    }
+
+    /// `y`: pushes information about the interpreter and the IP, or picks one of its cells.
+    fn sysinfo(&mut self) {
+        let n = self.ip.pop();
+        let mut info: Vec<i64> = vec![];
+        let push_strings = |info: &mut Vec<i64>, strings: &[String]| {
+            for string in strings.iter().rev() {
+                info.push(0);
+                info.extend(string.chars().rev().map(|c| c as i64));
+            }
+        };
+        // the list is built from the bottom: environment variables, then arguments
+        info.push(0);
+        if !self.config.sandbox {
+            let vars: Vec<String> = std::env::vars()
+                .map(|(name, value)| format!("{}={}", name, value))
+                .collect();
+            push_strings(&mut info, &vars);
+        }
+        info.extend([0, 0]);
+        for stack in &self.ip.stacks {
+            info.push(stack.len() as i64);
+        }
+        info.push(self.ip.stacks.len() as i64);
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs() as i64;
+        let (days, secs) = (now.div_euclid(86400), now.rem_euclid(86400));
+        let (year, month, day) = civil_from_days(days);
+        info.push((secs / 3600) * 65536 + (secs / 60 % 60) * 256 + secs % 60);
+        info.push((year - 1900) * 65536 + month * 256 + day);
+        let (min, max) = self.space.bounds().unwrap_or_default();
+        for (x, y) in [
+            (max.0 - min.0, max.1 - min.1),
+            min,
+            self.ip.offset,
+            self.ip.delta,
+            self.ip.pos,
+        ] {
+            info.extend([x, y]);
+        }
+        let version = [
+            env!("CARGO_PKG_VERSION_MAJOR"),
+            env!("CARGO_PKG_VERSION_MINOR"),
+            env!("CARGO_PKG_VERSION_PATCH"),
+        ]
+        .iter()
+        .fold(0, |version, part| {
+            version * 100 + part.parse::<i64>().unwrap_or(0)
+        });
+        let flags = if self.config.sandbox { 0 } else { 0b110 };
+        info.extend([
+            0,                                 // team number
+            0,                                 // IP ID
+            2,                                 // dimensions
+            std::path::MAIN_SEPARATOR as i64,  // path separator
+            0,                                 // `=` is not supported
+            version,                           // version number
+            fingerprint_id("ESBX"),            // handprint
+            std::mem::size_of::<i64>() as i64, // bytes per cell
+            flags,                             // `i` and `o` outside the sandbox
+        ]);
+
+        let toss = self.ip.toss();
+        if n <= 0 {
+            toss.extend(info);
+            return;
+        }
+        let n = n as usize;
+        let value = if n <= info.len() {
+            info[info.len() - n]
+        } else {
+            // picks from the stack below, as if everything was pushed
+            let depth = n - info.len();
+            toss.len().checked_sub(depth).map_or(0, |index| toss[index])
+        };
+        toss.push(value);
+    }
+}
+
+/// Converts days since 1970-01-01 into a date in the proleptic Gregorian calendar.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_str(source: &str, input: &str, config: &Config) -> (Result<(), Error>, String) {
+        let config = Config {
+            max_steps: Some(100_000),
+            ..config.clone()
+        };
+        let mut stdout: Vec<u8> = vec![];
+        let res = run_with_config(source, &mut input.as_bytes(), &mut stdout, &config);
+        (res, String::from_utf8(stdout).unwrap())
+    }
+
+    fn output(source: &str, input: &str) -> String {
+        let (res, stdout) = run_str(source, input, &Config::default());
+        assert!(res.is_ok(), "{:?}", res);
+        stdout
+    }
+
+    #[test]
+    fn test_funge98() {
+        // hello world with SGML spaces, hex digits and wrapping around through `;` comments
+        assert_eq!(
+            output("\"dlrow  olleh\">:#,_ ;comment; a,@", ""),
+            "hello world\n"
+        );
+        assert_eq!(output("a1+.ff*.@", ""), "11 225 ");
+        // division by zero, `'`, `s`, `w`, `[` and `]`
+        assert_eq!(output("10/. 10%. 'A. 'Bs 89+0g,@", ""), "0 0 65 B");
+        for (ab, expected) in [("12", "3 "), ("11", "4 "), ("21", "5 ")] {
+            let code = format!("v  >3.@\n>{}w4.@\n   >5.@", ab);
+            assert_eq!(output(&code, ""), expected);
+        }
+        assert_eq!(output("]\n5\n.\n@", ""), "5 ");
+        assert_eq!(output("[\n@\n.\n5", ""), "5 ");
+        // `j`, `k` and `x`
+        assert_eq!(output("2j..1.@", ""), "1 ");
+        assert_eq!(output("13k. 5.@", ""), "1 0 0 5 ");
+        assert_eq!(output("10k. 5.@", ""), "5 ");
+        assert_eq!(output("01x\n  3\n  .\n  @", ""), "3 ");
+        // input, with reflection at EOF
+        assert_eq!(output("#v&.@\n >2.@", "-12"), "-12 ");
+        assert_eq!(output("#v~.@\n >2.@", "a"), "97 ");
+        assert_eq!(output("#v~.@\n >2.@", ""), "2 ");
+
+        // `{` moves values and the storage offset, `}` moves them back, `u` moves between
+        assert_eq!(output("123 2{ .. 9 1} ..@", ""), "3 2 9 1 ");
+        assert_eq!(output("1{00g.@", ""), "48 ");
+        assert_eq!(output("12 0{ 4u.... }@", ""), "1 2 0 0 ");
+        assert_eq!(output("#v}5.@\n >6.@", ""), "6 ");
+
+        // self-modification outside the source extends Funge-Space
+        assert_eq!(output("'@80p", ""), "");
+        assert_eq!(output("55*:* 0 0p 0 0g.@", ""), "625 ");
+    }
+
+    #[test]
+    fn test_funge98_system() {
+        // flags, cell size, dimensions, position and the sizes of the stacks
+        assert_eq!(output("1y. 2y. 7y.@", ""), "0 8 2 ");
+        assert_eq!(output("  ay.by.cy.dy.@", ""), "0 6 0 1 ");
+        assert_eq!(output("5 6 7 bb+y. bc+y.@", ""), "1 3 ");
+        let unsandboxed = Config {
+            sandbox: false,
+            ..Config::default()
+        };
+        let (res, stdout) = run_str("1y.@", "", &unsandboxed);
+        assert!(res.is_ok(), "{:?}", res);
+        assert_eq!(stdout, "6 ");
+
+        // `o` writes the source as text and `i` loads it back below, in the sandbox or not
+        let path = std::env::temp_dir().join(format!("esobox-funge98-{}", std::process::id()));
+        let name: String = path.to_str().unwrap().chars().rev().collect();
+        let code = format!("21 01 1 0\"{name}\" o 04 0 0\"{name}\" i $$$$ 04g, @\nAB");
+        let _ = std::fs::remove_file(&path);
+        let (res, stdout) = run_str(&code, "", &unsandboxed);
+        assert!(res.is_ok(), "{:?}", res);
+        assert_eq!(stdout, "A");
+        assert_eq!(std::fs::read(&path).unwrap(), b"AB\n");
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(output("#vo@\n >1.@", ""), "1 ");
+        assert_eq!(output("#vi@\n >1.@", ""), "1 ");
+
+        assert!(matches!(
+            run_str("3q", "", &Config::default()).0,
+            Err(Error::Quit(3))
+        ));
+        assert!(run_str("0q", "", &Config::default()).0.is_ok());
+        assert!(matches!(
+            run_str("", "", &Config::default()).0,
+            Err(Error::LimitExceeded(_))
+        ));
+        // huge counts for `{` and `u` run out of steps with a limit, and reflect without one
+        for code in ["ff*:*:*:*{@", "1{ff*:*:*u@"] {
+            assert!(matches!(
+                run_str(code, "", &Config::default()).0,
+                Err(Error::LimitExceeded(_))
+            ));
+        }
+        let mut stdout: Vec<u8> = vec![];
+        for code in ["ff*:*:*:*{1.@", "1{ff*:*:*:*u1.@"] {
+            run(code, &mut &b""[..], &mut stdout).unwrap();
+        }
+        assert_eq!(stdout, b"");
+    }
+}
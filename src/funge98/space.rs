@@ -0,0 +1,204 @@
+//! Unbounded two-dimensional Funge-Space with Lahey-space wrapping.
+
+use std::collections::HashMap;
+
+/// A position or a delta in Funge-Space.
+pub type Vector = (i64, i64);
+
+const SPACE: i64 = b' ' as i64;
+
+/// Funge-Space, with every cell initially a space.
+///
+/// Only the cells that are not spaces are stored. The bounds are the smallest rectangle that
+/// contains every cell that has ever been set to something other than a space; they never
+/// shrink, which the specification allows.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Space {
+    cells: HashMap<Vector, i64>,
+    bounds: Option<(Vector, Vector)>,
+}
+
+impl Space {
+    pub(crate) fn get(&self, pos: Vector) -> i64 {
+        self.cells.get(&pos).copied().unwrap_or(SPACE)
+    }
+
+    pub(crate) fn put(&mut self, pos: Vector, value: i64) {
+        if value == SPACE {
+            self.cells.remove(&pos);
+            return;
+        }
+        self.cells.insert(pos, value);
+        self.bounds = Some(match self.bounds {
+            None => (pos, pos),
+            Some((min, max)) => (
+                (min.0.min(pos.0), min.1.min(pos.1)),
+                (max.0.max(pos.0), max.1.max(pos.1)),
+            ),
+        });
+    }
+
+    /// The least and greatest points of the bounds, or `None` if nothing was ever written.
+    pub(crate) fn bounds(&self) -> Option<(Vector, Vector)> {
+        self.bounds
+    }
+
+    /// Writes the contents of a file at `origin`, and returns the size of the rectangle it
+    /// covers. Spaces do not overwrite the existing cells.
+    ///
+    /// In text mode, `\n`, `\r\n` and `\r` start a new line and form feeds are ignored. In
+    /// binary mode, everything goes on a single line.
+    pub(crate) fn load(
+        &mut self,
+        origin: Vector,
+        cells: impl IntoIterator<Item = i64>,
+        binary: bool,
+    ) -> Vector {
+        let (mut x, mut y) = (0i64, 0i64);
+        let mut size = (0i64, 0i64);
+        let mut after_cr = false;
+        for value in cells {
+            if !binary {
+                let cr = after_cr;
+                after_cr = value == b'\r' as i64;
+                if value == b'\n' as i64 && cr {
+                    continue;
+                }
+                if value == b'\n' as i64 || value == b'\r' as i64 {
+                    (x, y) = (0, y + 1);
+                    continue;
+                }
+                if value == 0x0c {
+                    continue;
+                }
+            }
+            if value != SPACE {
+                self.put((origin.0.wrapping_add(x), origin.1.wrapping_add(y)), value);
+            }
+            x += 1;
+            size = (size.0.max(x), size.1.max(y + 1));
+        }
+        size
+    }
+
+    /// The position after moving from `pos` by `delta`, wrapping around the bounds as in
+    /// Lahey-space: an IP leaving the bounds comes back from the opposite side on the same line.
+    pub(crate) fn advance(&self, pos: Vector, delta: Vector) -> Vector {
+        let next = add(pos, delta);
+        if self.contains(next) {
+            return next;
+        }
+        match self.line_range(pos, delta) {
+            Some((first, _)) => add(pos, mul(delta, first)),
+            None => next,
+        }
+    }
+
+    /// The position after moving `n` times from `pos` by `delta`, wrapping around the bounds.
+    pub(crate) fn jump(&self, pos: Vector, delta: Vector, n: i64) -> Vector {
+        match self.line_range(pos, delta) {
+            Some((first, last)) if (first..=last).contains(&0) => {
+                let len = (last - first + 1) as i128;
+                let k = (n as i128 - first as i128).rem_euclid(len) + first as i128;
+                add(pos, mul(delta, k as i64))
+            }
+            _ => add(pos, mul(delta, n)),
+        }
+    }
+
+    fn contains(&self, pos: Vector) -> bool {
+        self.bounds.is_some_and(|(min, max)| {
+            (min.0..=max.0).contains(&pos.0) && (min.1..=max.1).contains(&pos.1)
+        })
+    }
+
+    /// The range of `k` for which `pos + k * delta` is within the bounds, if any.
+    fn line_range(&self, pos: Vector, delta: Vector) -> Option<(i64, i64)> {
+        let (min, max) = self.bounds?;
+        if delta == (0, 0) {
+            return None;
+        }
+        let mut range = (i64::MIN as i128, i64::MAX as i128);
+        for (p, d, lo, hi) in [
+            (pos.0, delta.0, min.0, max.0),
+            (pos.1, delta.1, min.1, max.1),
+        ] {
+            let (p, d, lo, hi) = (p as i128, d as i128, lo as i128, hi as i128);
+            if d == 0 {
+                if !(lo..=hi).contains(&p) {
+                    return None;
+                }
+                continue;
+            }
+            // solve lo <= p + k * d <= hi for k
+            let (a, b) = if d > 0 {
+                (lo - p, hi - p)
+            } else {
+                (hi - p, lo - p)
+            };
+            range.0 = range.0.max(div_ceil(a, d));
+            range.1 = range.1.min(div_floor(b, d));
+        }
+        (range.0 <= range.1).then_some((range.0 as i64, range.1 as i64))
+    }
+}
+
+fn div_floor(a: i128, b: i128) -> i128 {
+    let q = a / b;
+    if (a % b != 0) && ((a < 0) != (b < 0)) {
+        q - 1
+    } else {
+        q
+    }
+}
+
+fn div_ceil(a: i128, b: i128) -> i128 {
+    -div_floor(-a, b)
+}
+
+pub(crate) fn add(a: Vector, b: Vector) -> Vector {
+    (a.0.wrapping_add(b.0), a.1.wrapping_add(b.1))
+}
+
+fn mul(a: Vector, k: i64) -> Vector {
+    (a.0.wrapping_mul(k), a.1.wrapping_mul(k))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_space() {
+        let mut space = Space::default();
+        assert_eq!(space.advance((0, 0), (1, 0)), (1, 0));
+        let size = space.load((0, 0), "ab c\r\nd\x0c\re".chars().map(|c| c as i64), false);
+        assert_eq!(size, (4, 3));
+        assert_eq!(space.get((3, 0)), 'c' as i64);
+        assert_eq!(space.get((0, 2)), 'e' as i64);
+        assert_eq!(space.bounds(), Some(((0, 0), (3, 2))));
+
+        // wrapping around in every direction, including diagonally
+        assert_eq!(space.advance((3, 0), (1, 0)), (0, 0));
+        assert_eq!(space.advance((0, 1), (-1, 0)), (3, 1));
+        assert_eq!(space.advance((2, 2), (0, 1)), (2, 0));
+        assert_eq!(space.advance((2, 2), (1, 1)), (0, 0));
+        assert_eq!(space.advance((0, 0), (2, 0)), (2, 0));
+        assert_eq!(space.advance((2, 0), (2, 0)), (0, 0));
+        // coming back into the bounds from outside
+        assert_eq!(space.advance((-5, 1), (1, 0)), (0, 1));
+        assert_eq!(space.advance((5, 1), (1, 0)), (0, 1));
+
+        assert_eq!(space.jump((1, 0), (1, 0), 6), (3, 0));
+        assert_eq!(space.jump((1, 0), (1, 0), -3), (2, 0));
+        assert_eq!(space.jump((1, 9), (1, 0), 2), (3, 9));
+
+        // spaces do not overwrite, and binary mode keeps line breaks as cells
+        let size = space.load((-2, 0), " xy\n".bytes().map(|b| b as i64), true);
+        assert_eq!(size, (4, 1));
+        assert_eq!(space.get((-2, 0)), ' ' as i64);
+        assert_eq!(space.get((-1, 0)), 'x' as i64);
+        assert_eq!(space.get((1, 0)), '\n' as i64);
+        assert_eq!(space.bounds(), Some(((-1, 0), (3, 2))));
+    }
+}
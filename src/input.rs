@@ -0,0 +1,69 @@
+//! Reading bytes, characters and numbers from the input, shared by the languages.
+
+use std::io::{self, BufRead};
+
+/// Reads a single byte. Returns `None` at EOF.
+pub(crate) fn read_byte<I: BufRead + ?Sized>(input: &mut I) -> io::Result<Option<u8>> {
+    let value = peek_byte(input)?;
+    if value.is_some() {
+        input.consume(1);
+    }
+    Ok(value)
+}
+
+/// Reads a single UTF-8 encoded character, replacing invalid sequences with U+FFFD.
+/// Returns `None` at EOF.
+pub(crate) fn read_char<I: BufRead + ?Sized>(input: &mut I) -> io::Result<Option<char>> {
+    let Some(first) = read_byte(input)? else {
+        return Ok(None);
+    };
+    let len = match first.leading_ones() {
+        0 => return Ok(Some(first as char)),
+        n @ 2..=4 => n as usize,
+        _ => return Ok(Some(char::REPLACEMENT_CHARACTER)),
+    };
+    let mut bytes = vec![first];
+    while bytes.len() < len {
+        match peek_byte(input)? {
+            Some(byte) if byte & 0xc0 == 0x80 => {
+                bytes.push(byte);
+                input.consume(1);
+            }
+            _ => break,
+        }
+    }
+    Ok(Some(match std::str::from_utf8(&bytes) {
+        Ok(text) => text.chars().next().unwrap_or(char::REPLACEMENT_CHARACTER),
+        Err(_) => char::REPLACEMENT_CHARACTER,
+    }))
+}
+
+fn peek_byte<I: BufRead + ?Sized>(input: &mut I) -> io::Result<Option<u8>> {
+    Ok(input.fill_buf()?.first().copied())
+}
+
+/// Reads the next decimal number, skipping anything before it except a `-` right before the
+/// digits, and leaving everything after it. Returns `None` at EOF.
+pub(crate) fn read_number<I: BufRead + ?Sized>(input: &mut I) -> io::Result<Option<i64>> {
+    let mut negative = false;
+    loop {
+        match peek_byte(input)? {
+            None => return Ok(None),
+            Some(byte) if byte.is_ascii_digit() => break,
+            Some(byte) => {
+                negative = byte == b'-';
+                input.consume(1);
+            }
+        }
+    }
+    let mut value: i64 = 0;
+    while let Some(byte) = peek_byte(input)?.filter(u8::is_ascii_digit) {
+        value = value.wrapping_mul(10).wrapping_add((byte - b'0') as i64);
+        input.consume(1);
+    }
+    Ok(Some(if negative {
+        value.wrapping_neg()
+    } else {
+        value
+    }))
+}
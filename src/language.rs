@@ -0,0 +1,136 @@
+//! The common interface implemented by every language, and the registry of languages.
+
+use std::io::{BufRead, Write};
+use std::time::Duration;
+
+use crate::{befunge93, brainfuck, fish, funge98, malbolge, piet, unlambda, whitespace, Error};
+
+/// Options that can be given to any language, typically from the command line.
+///
+/// Languages ignore the options that do not apply to them.
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Behavior of byte input on end of file, for languages where it is configurable.
+    pub eof: brainfuck::Eof,
+    /// Extensions enabled in Brainfuck.
+    pub dialect: brainfuck::Dialect,
+    /// Maximum number of steps to execute. What counts as a step depends on the language.
+    pub max_steps: Option<u64>,
+    /// Wall-clock time limit of a single run.
+    pub timeout: Option<Duration>,
+    /// Whether to keep programs from accessing the file system and the environment, for
+    /// languages that can. Enabled by default.
+    pub sandbox: bool,
+    /// Whether to run Malbolge with 20-trit words instead of the original 10-trit ones.
+    pub wide_words: bool,
+    /// Size of a Piet codel in pixels. `None` detects it from the image.
+    pub codel_size: Option<usize>,
+    /// Values on the ><> stack when the program starts, from the bottom up.
+    pub initial_stack: Vec<f64>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            eof: Default::default(),
+            dialect: Default::default(),
+            max_steps: None,
+            timeout: None,
+            sandbox: true,
+            wide_words: false,
+            codel_size: None,
+            initial_stack: vec![],
+        }
+    }
+}
+
+/// An esolang implementation.
+pub trait Language: Sync {
+    /// Canonical name of the language, used on the command line.
+    fn name(&self) -> &'static str;
+
+    /// Alternative names of the language.
+    fn aliases(&self) -> &'static [&'static str];
+
+    /// File extensions commonly used for source files, without the leading dot.
+    fn extensions(&self) -> &'static [&'static str];
+
+    /// Compiles the source code into a program that can be run any number of times.
+    fn compile(&self, source: &str, options: &Options) -> Result<Box<dyn Program>, Error>;
+
+    /// Compiles source code given as raw bytes, which is how languages whose programs are not
+    /// text, such as images, must be compiled.
+    ///
+    /// By default, the source must be valid UTF-8 and is given to [`compile`](Self::compile).
+    fn compile_bytes(&self, source: &[u8], options: &Options) -> Result<Box<dyn Program>, Error> {
+        let source =
+            std::str::from_utf8(source).map_err(|error| Error::compile(self.name(), error))?;
+        self.compile(source, options)
+    }
+}
+
+/// A compiled program.
+pub trait Program: Send + Sync {
+    /// Runs the program from the start, reading from `input` and writing to `output`.
+    fn run(&self, input: &mut dyn BufRead, output: &mut dyn Write) -> Result<(), Error>;
+}
+
+static LANGUAGES: &[&dyn Language] = &[
+    &brainfuck::Brainfuck,
+    &befunge93::Befunge93,
+    &funge98::Funge98,
+    &whitespace::Whitespace,
+    &malbolge::Malbolge,
+    &unlambda::Unlambda,
+    &piet::Piet,
+    &fish::Fish,
+];
+
+/// Returns all available languages.
+pub fn languages() -> &'static [&'static dyn Language] {
+    LANGUAGES
+}
+
+/// Finds a language by its name or one of its aliases.
+pub fn find_language(name: &str) -> Option<&'static dyn Language> {
+    LANGUAGES
+        .iter()
+        .copied()
+        .find(|lang| lang.name() == name || lang.aliases().contains(&name))
+}
+
+/// Finds a language by a file extension, without the leading dot.
+pub fn find_language_by_extension(extension: &str) -> Option<&'static dyn Language> {
+    LANGUAGES
+        .iter()
+        .copied()
+        .find(|lang| lang.extensions().contains(&extension))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry() {
+        for name in ["brainfuck", "bf"] {
+            let lang = find_language(name).expect("brainfuck is registered");
+            assert_eq!(lang.name(), "brainfuck");
+        }
+        assert_eq!(find_language("bf93").unwrap().name(), "befunge93");
+        assert_eq!(find_language_by_extension("b98").unwrap().name(), "funge98");
+        assert!(find_language("nonexistent").is_none());
+        assert_eq!(find_language_by_extension("b").unwrap().name(), "brainfuck");
+        assert!(find_language_by_extension("txt").is_none());
+
+        let program = find_language("bf")
+            .unwrap()
+            .compile(",[.[-],]", &Options::default())
+            .unwrap();
+        for input in [&b"abc"[..], b"esobox"] {
+            let mut stdout: Vec<u8> = vec![];
+            assert!(program.run(&mut &input[..], &mut stdout).is_ok());
+            assert_eq!(stdout, input);
+        }
+    }
+}
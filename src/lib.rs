@@ -11,11 +11,38 @@
 //! This `run` function returns `Ok(())` if run successfully, and `Err(...)` if
 //! the program was terminated by some kind of error. The `Error` enum is unique
 //! to each language, containing all possible error situations. Refer to the
-//! respective docs for details.
+//! respective docs for details. Piet programs are images, so [`piet::run`] takes the
+//! source as bytes instead.
 //!
 //! Each language implementation is intended to be "faster than naive",
 //! which will often be achieved by compiling "halfway" to bytecode.
+//!
+//! Every language is also available through the [`Language`] trait, which separates compiling
+//! the source from running the resulting [`Program`]. [`languages`] and [`find_language`] give
+//! access to all implemented languages without naming them in code. Errors from every
+//! language are wrapped in the crate-level [`Error`], which tells compile errors apart from
+//! runtime errors. The [`corpus`] module runs directories of test programs with their
+//! expected output through any of the languages.
 
 #![warn(missing_docs)]
 
+pub mod befunge93;
 pub mod brainfuck;
+mod budget;
+pub mod cli;
+pub mod corpus;
+mod error;
+pub mod fish;
+pub mod funge98;
+mod input;
+mod language;
+pub mod malbolge;
+pub mod piet;
+mod rng;
+pub mod unlambda;
+pub mod whitespace;
+
+pub use error::{Error, LanguageError};
+pub use language::{
+    find_language, find_language_by_extension, languages, Language, Options, Program,
+};
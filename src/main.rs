@@ -1,18 +1,41 @@
 use std::{
     fs,
-    io::{stdin, stdout, Read},
+    io::{stdin, stdout, BufRead, Read, Stdout, Write},
+    path::Path,
+    process::exit,
+    time::Duration,
 };
 
-use clap::{arg, command, value_parser};
+use clap::{arg, builder::PossibleValuesParser, command, value_parser, Arg, ArgMatches, Command};
 use esobox::*;
 
+/// Exit code when the program was terminated by an error.
+const EXIT_RUNTIME_ERROR: i32 = 1;
+/// Exit code when `esobox lint` found problems.
+const EXIT_LINTS_FOUND: i32 = 1;
+/// Exit code when some cases of `esobox test` failed.
+const EXIT_TESTS_FAILED: i32 = 1;
+/// Exit code when the source code could not be read.
+const EXIT_SOURCE_ERROR: i32 = 3;
+/// Exit code when the source code could not be compiled.
+const EXIT_COMPILE_ERROR: i32 = 4;
+
 fn main() {
+    let lang_names: Vec<&'static str> = languages()
+        .iter()
+        .flat_map(|lang| std::iter::once(lang.name()).chain(lang.aliases().iter().copied()))
+        .collect();
     let matches = command!()
-        .override_usage("esobox <LANGUAGE> <FILE>\n    esobox <LANGUAGE> - <ARGS>...")
+        .override_usage(
+            "esobox <LANGUAGE> <FILE>\n    esobox <LANGUAGE> - <ARGS>...\n    esobox <SUBCOMMAND>",
+        )
+        .args_conflicts_with_subcommands(true)
+        .allow_negative_numbers(true)
+        .subcommand_negates_reqs(true)
         .arg(
             arg!(lang: <LANGUAGE> "Name of the language to run")
                 .required(true)
-                .value_parser(["brainfuck", "bf"]),
+                .value_parser(PossibleValuesParser::new(lang_names)),
         )
         .arg(
             arg!(file: <FILE> "Name of the source file to run")
@@ -25,44 +48,592 @@ fn main() {
                 .required(false)
                 .value_parser(value_parser!(String)),
         )
+        .args(cli::run_args())
+        .subcommand(
+            Command::new("transpile")
+                .about("Translate a Brainfuck program into another language")
+                .arg(
+                    arg!(lang: <LANGUAGE> "Name of the source language")
+                        .value_parser(["brainfuck", "bf"]),
+                )
+                .arg(
+                    arg!(file: <FILE> "Name of the source file to translate")
+                        .value_parser(value_parser!(String)),
+                )
+                .arg(
+                    arg!(--target <TARGET> "Language to translate into")
+                        .required(false)
+                        .value_parser(["c", "rust"])
+                        .default_value("c"),
+                )
+                .arg(cli::eof_arg())
+                .args(cli::dialect_args()),
+        )
+        .subcommand(
+            Command::new("debug")
+                .about("Step through a Brainfuck program interactively")
+                .arg(
+                    arg!(lang: <LANGUAGE> "Name of the source language")
+                        .value_parser(["brainfuck", "bf"]),
+                )
+                .arg(
+                    arg!(file: <FILE> "Name of the source file to debug")
+                        .value_parser(value_parser!(String)),
+                )
+                .arg(
+                    arg!(--input <FILE> "File to use as the input of the program (empty by default)")
+                        .required(false)
+                        .value_parser(value_parser!(String)),
+                )
+                .arg(
+                    Arg::new("break")
+                        .short('b')
+                        .long("break")
+                        .value_name("OFFSET")
+                        .help("Set a breakpoint at the source offset")
+                        .multiple_occurrences(true)
+                        .value_parser(value_parser!(usize)),
+                )
+                .arg(cli::eof_arg())
+                .args(cli::dialect_args()),
+        )
+        .subcommand(
+            Command::new("profile")
+                .about("Run a Brainfuck program and report where it spends its time")
+                .long_about(
+                    "Run a Brainfuck program and report where it spends its time.\n\n\
+                     The program reads from stdin and writes to stdout as usual, and the report \
+                     is printed to stderr after the program finishes.",
+                )
+                .arg(
+                    arg!(lang: <LANGUAGE> "Name of the source language")
+                        .value_parser(["brainfuck", "bf"]),
+                )
+                .arg(
+                    arg!(file: <FILE> "Name of the source file to profile")
+                        .value_parser(value_parser!(String)),
+                )
+                .arg(
+                    arg!(--top <N> "Number of entries to show in each section of the report")
+                        .required(false)
+                        .value_parser(value_parser!(usize))
+                        .default_value("10"),
+                )
+                .arg(arg!(--annotate "Also print the source with execution counts on each line"))
+                .arg(cli::eof_arg())
+                .args(cli::dialect_args()),
+        )
+        .subcommand(
+            Command::new("fmt")
+                .about("Pretty-print a Brainfuck program with indented loops")
+                .arg(
+                    arg!(lang: <LANGUAGE> "Name of the source language")
+                        .value_parser(["brainfuck", "bf"]),
+                )
+                .arg(
+                    arg!(file: <FILE> "Name of the source file to format")
+                        .value_parser(value_parser!(String)),
+                )
+                .args(cli::dialect_args()),
+        )
+        .subcommand(
+            Command::new("minify")
+                .about("Remove comments and redundant commands from a Brainfuck program")
+                .arg(
+                    arg!(lang: <LANGUAGE> "Name of the source language")
+                        .value_parser(["brainfuck", "bf"]),
+                )
+                .arg(
+                    arg!(file: <FILE> "Name of the source file to minify")
+                        .value_parser(value_parser!(String)),
+                )
+                .args(cli::dialect_args()),
+        )
+        .subcommand(
+            Command::new("lint")
+                .about("Check a Brainfuck program for common mistakes")
+                .long_about(
+                    "Check a Brainfuck program for common mistakes.\n\n\
+                     Exits with code 1 if anything was found, so it can be used in CI.",
+                )
+                .arg(
+                    arg!(lang: <LANGUAGE> "Name of the source language")
+                        .value_parser(["brainfuck", "bf"]),
+                )
+                .arg(
+                    arg!(file: <FILE> "Name of the source file to check")
+                        .value_parser(value_parser!(String)),
+                )
+                .arg(
+                    arg!(--format <FORMAT> "Output format")
+                        .required(false)
+                        .value_parser(["text", "json"])
+                        .default_value("text"),
+                )
+                .args(cli::dialect_args()),
+        )
+        .subcommand(
+            Command::new("test")
+                .about("Run a directory of test programs and check their output")
+                .long_about(
+                    "Run a directory of test programs and check their output.\n\n\
+                     Every source file with a known extension and a `.out` or `.err` file next \
+                     to it is a test case. `.out` holds the expected output, `.err` text that \
+                     the error message should contain, `.in` the input, and `.args` options \
+                     such as `--eof zero`.",
+                )
+                .arg(
+                    arg!(dir: <DIR> "Directory containing the test cases")
+                        .value_parser(value_parser!(String)),
+                )
+                .arg(
+                    arg!(--timeout <SECONDS> "Fail each case that runs for longer than this")
+                        .required(false)
+                        .value_parser(cli::parse_timeout)
+                        .default_value("10"),
+                ),
+        )
         .get_matches();
+    match matches.subcommand() {
+        Some(("transpile", matches)) => transpile(matches),
+        Some(("debug", matches)) => debug(matches),
+        Some(("profile", matches)) => profile(matches),
+        Some(("fmt", matches)) => format(matches, false),
+        Some(("minify", matches)) => format(matches, true),
+        Some(("lint", matches)) => lint(matches),
+        Some(("test", matches)) => test(matches),
+        _ => run(&matches),
+    }
+}
+
+/// Reads the source file, or stdin if `file` is `-`. Exits on failure.
+fn read_source(file: &str) -> String {
+    let source = if file == "-" {
+        let mut source = String::new();
+        stdin().read_to_string(&mut source).map(|_| source)
+    } else {
+        fs::read_to_string(file)
+    };
+    source.unwrap_or_else(|error| {
+        eprintln!("error: failed to read source file `{}`: {}", file, error);
+        exit(EXIT_SOURCE_ERROR);
+    })
+}
+
+/// Reads the source as bytes, for languages whose programs need not be text.
+fn read_source_bytes(file: &str) -> Vec<u8> {
+    let source = if file == "-" {
+        let mut source = vec![];
+        stdin().read_to_end(&mut source).map(|_| source)
+    } else {
+        fs::read(file)
+    };
+    source.unwrap_or_else(|error| {
+        eprintln!("error: failed to read source file `{}`: {}", file, error);
+        exit(EXIT_SOURCE_ERROR);
+    })
+}
+
+/// Prints an error and all of its causes to stderr.
+fn print_error(error: &dyn std::error::Error) {
+    eprintln!("error: {}", error);
+    let mut source = error.source();
+    while let Some(cause) = source {
+        eprintln!("caused by: {}", cause);
+        source = cause.source();
+    }
+}
+
+fn run(matches: &ArgMatches) {
     let lang_name = matches.get_one::<String>("lang").unwrap();
     let file = matches.get_one::<String>("file").unwrap();
     let args = matches.get_many::<String>("args");
-    if file == "-" {
-        let mut source = String::new();
-        let mut stdin = stdin();
-        stdin
-            .read_to_string(&mut source)
-            .expect("Unexpected error while reading source from stdin");
-        let mut input = String::new();
-        if let Some(args) = args {
-            for arg in args {
-                input.push_str(arg);
-                input.push('\0');
+    let mut options = Options::default();
+    cli::update_options(matches, &mut options);
+    let language = find_language(lang_name).unwrap();
+    let source = read_source_bytes(file);
+    let result = language
+        .compile_bytes(&source, &options)
+        .and_then(|program| {
+            let mut output = stdout();
+            if file == "-" {
+                let mut input = String::new();
+                if let Some(args) = args {
+                    for arg in args {
+                        input.push_str(arg);
+                        input.push('\0');
+                    }
+                }
+                program.run(&mut input.as_bytes(), &mut output)
+            } else {
+                program.run(&mut stdin().lock(), &mut output)
             }
+        });
+    if let Err(error) = result {
+        // `q` in Funge-98 sets the exit code on purpose, so it is not reported as an error
+        if let Error::Runtime {
+            source: LanguageError::Funge98(funge98::Error::Quit(code)),
+            ..
+        } = error
+        {
+            exit(code);
         }
-        let mut output = stdout();
-        match lang_name as &str {
-            "brainfuck" | "bf" => {
-                if let Err(error) = brainfuck::run(&source, &mut input.as_bytes(), &mut output) {
-                    eprintln!("Error: {:?}", error);
+        print_error(&error);
+        exit(match error {
+            Error::Compile { .. } => EXIT_COMPILE_ERROR,
+            Error::Runtime { .. } => EXIT_RUNTIME_ERROR,
+        });
+    }
+}
+
+fn transpile(matches: &ArgMatches) {
+    let file = matches.get_one::<String>("file").unwrap();
+    let source = read_source(file);
+    let config = brainfuck::Config {
+        eof: cli::eof_policy(matches),
+        dialect: cli::dialect(matches),
+        ..brainfuck::Config::default()
+    };
+    let result = match matches.get_one::<String>("target").unwrap() as &str {
+        "c" => brainfuck::transpile::to_c(&source, &config),
+        "rust" => brainfuck::transpile::to_rust(&source, &config),
+        _ => unreachable!(),
+    };
+    match result {
+        Ok(code) => print!("{}", code),
+        Err(error) => {
+            print_error(&error);
+            exit(EXIT_COMPILE_ERROR);
+        }
+    }
+}
+
+const DEBUG_HELP: &str = "\
+commands:
+  s, step [N]       execute N commands (default 1)
+  c, continue       run until a breakpoint, a `#`, a watched cell change, or the end
+  b, break OFFSET   set a breakpoint before the command at the source offset
+  d, delete OFFSET  delete the breakpoint at the source offset
+  w, watch CELL     stop when the cell at the position changes
+  unwatch CELL      stop watching the cell
+  t, tape [RADIUS]  show the cells around the pointer (default 8)
+  i, info           show the state of the debugger
+  h, help           show this message
+  q, quit           exit the debugger";
+
+fn debug(matches: &ArgMatches) {
+    let file = matches.get_one::<String>("file").unwrap();
+    let source = read_source(file);
+    let input = match matches.get_one::<String>("input") {
+        Some(path) => fs::read(path).unwrap_or_else(|error| {
+            eprintln!("error: failed to read input file `{}`: {}", path, error);
+            exit(EXIT_SOURCE_ERROR);
+        }),
+        None => vec![],
+    };
+    let config = brainfuck::Config {
+        eof: cli::eof_policy(matches),
+        dialect: cli::dialect(matches),
+        ..brainfuck::Config::default()
+    };
+    let mut debugger = brainfuck::Debugger::new(&source, &config, &input[..], stdout())
+        .unwrap_or_else(|error| {
+            print_error(&error);
+            exit(EXIT_COMPILE_ERROR);
+        });
+    if let Some(offsets) = matches.get_many::<usize>("break") {
+        for &offset in offsets {
+            debugger.add_breakpoint(offset);
+        }
+    }
+    show_position(&debugger, &source);
+    let mut commands = stdin().lock();
+    let mut line = String::new();
+    loop {
+        print!("(debug) ");
+        stdout().flush().ok();
+        line.clear();
+        if commands.read_line(&mut line).unwrap_or(0) == 0 {
+            println!();
+            break;
+        }
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            continue;
+        };
+        let arg = words.next().map(|word| word.parse::<usize>().ok());
+        match (command, arg) {
+            ("s" | "step", None | Some(Some(_))) => {
+                let count = arg.flatten().unwrap_or(1);
+                for _ in 0..count {
+                    match debugger.step() {
+                        Ok(brainfuck::StopReason::Step) => (),
+                        result => {
+                            show_stop(&debugger, result);
+                            break;
+                        }
+                    }
+                }
+                show_position(&debugger, &source);
+            }
+            ("c" | "continue", None) => {
+                let result = debugger.resume();
+                show_stop(&debugger, result);
+                show_position(&debugger, &source);
+            }
+            ("b" | "break", Some(Some(offset))) => {
+                debugger.add_breakpoint(offset);
+                println!("breakpoint set at offset {}", offset);
+            }
+            ("d" | "delete", Some(Some(offset))) => {
+                if debugger.remove_breakpoint(offset) {
+                    println!("breakpoint deleted at offset {}", offset);
+                } else {
+                    println!("no breakpoint at offset {}", offset);
                 }
             }
-            _ => unreachable!(),
+            ("w" | "watch", Some(Some(cell))) => {
+                debugger.add_watchpoint(cell);
+                println!("watching cell {}", cell);
+            }
+            ("unwatch", Some(Some(cell))) => {
+                if debugger.remove_watchpoint(cell) {
+                    println!("stopped watching cell {}", cell);
+                } else {
+                    println!("cell {} is not watched", cell);
+                }
+            }
+            ("t" | "tape", None | Some(Some(_))) => {
+                println!("{}", debugger.snapshot(arg.flatten().unwrap_or(8)));
+            }
+            ("i" | "info", None) => {
+                show_position(&debugger, &source);
+                println!("steps: {}", debugger.steps());
+                println!("pointer: {}", debugger.pointer());
+                let breakpoints: Vec<String> =
+                    debugger.breakpoints().map(|b| b.to_string()).collect();
+                println!("breakpoints: {}", breakpoints.join(", "));
+                let watchpoints: Vec<String> =
+                    debugger.watchpoints().map(|w| w.to_string()).collect();
+                println!("watchpoints: {}", watchpoints.join(", "));
+            }
+            ("h" | "help", None) => println!("{}", DEBUG_HELP),
+            ("q" | "quit", None) => break,
+            _ => println!("invalid command `{}`; type `help` for a list", line.trim()),
+        }
+    }
+}
+
+type Debugger<'a> = brainfuck::Debugger<&'a [u8], Stdout>;
+
+fn show_stop(debugger: &Debugger, result: Result<brainfuck::StopReason, brainfuck::Error>) {
+    // the program output may not end with a newline
+    println!();
+    match result {
+        Ok(brainfuck::StopReason::Step) => (),
+        Ok(brainfuck::StopReason::Breakpoint(offset)) => {
+            println!("breakpoint at offset {}", offset)
+        }
+        Ok(brainfuck::StopReason::DebugChar(offset)) => println!("`#` at offset {}", offset),
+        Ok(brainfuck::StopReason::Watchpoint { cell, old, new }) => {
+            println!("cell {} changed from {} to {}", cell, old, new)
+        }
+        Ok(brainfuck::StopReason::Finished) => {
+            println!("program finished after {} steps", debugger.steps())
+        }
+        Err(error) => print_error(&error),
+    }
+}
+
+/// Prints the source line of the next command with a caret under it.
+fn show_position(debugger: &Debugger, source: &str) {
+    let Some(offset) = debugger.position() else {
+        println!("not running");
+        return;
+    };
+    let position = brainfuck::SourcePosition::new(source, offset);
+    println!(
+        "at offset {}, line {}, column {}",
+        offset, position.line, position.column
+    );
+    println!("{}", position);
+}
+
+/// Pretty-prints or minifies a Brainfuck program.
+fn format(matches: &ArgMatches, minify: bool) {
+    let file = matches.get_one::<String>("file").unwrap();
+    let source = read_source(file);
+    let config = brainfuck::Config {
+        dialect: cli::dialect(matches),
+        ..brainfuck::Config::default()
+    };
+    let result = if minify {
+        brainfuck::format::minify(&source, &config)
+    } else {
+        brainfuck::format::pretty(&source, &config)
+    };
+    match result {
+        // a newline after `!` would become part of the input
+        Ok(code) if minify && !config.dialect.inline_input => println!("{}", code),
+        Ok(code) => print!("{}", code),
+        Err(error) => {
+            print_error(&error);
+            exit(EXIT_COMPILE_ERROR);
+        }
+    }
+}
+
+/// Prints the lints found in a Brainfuck program.
+fn lint(matches: &ArgMatches) {
+    let file = matches.get_one::<String>("file").unwrap();
+    let source = read_source(file);
+    let config = brainfuck::Config {
+        dialect: cli::dialect(matches),
+        ..brainfuck::Config::default()
+    };
+    let lints = match brainfuck::lint::lint(&source, &config) {
+        Ok(lints) => lints,
+        Err(error) => {
+            print_error(&error);
+            exit(EXIT_COMPILE_ERROR);
         }
+    };
+    if matches.get_one::<String>("format").unwrap() == "json" {
+        println!("{}", brainfuck::lint::to_json(&lints));
     } else {
-        let source =
-            fs::read_to_string(file).expect("Unexpected error while reading source from file");
-        let mut input = stdin().lock();
-        let mut output = stdout();
-        match lang_name as &str {
-            "brainfuck" | "bf" => {
-                if let Err(error) = brainfuck::run(&source, &mut input, &mut output) {
-                    eprintln!("Error: {:?}", error);
+        for lint in &lints {
+            println!(
+                "{}:{}:{}: warning: {} [{}]",
+                file,
+                lint.line,
+                lint.column,
+                lint.message,
+                lint.kind.name()
+            );
+        }
+    }
+    if !lints.is_empty() {
+        exit(EXIT_LINTS_FOUND);
+    }
+}
+
+/// Runs a test corpus and reports the results.
+fn test(matches: &ArgMatches) {
+    let dir = matches.get_one::<String>("dir").unwrap();
+    let defaults = Options {
+        timeout: matches.get_one::<Duration>("timeout").copied(),
+        ..Options::default()
+    };
+    let cases = corpus::discover(Path::new(dir), &defaults).unwrap_or_else(|error| {
+        print_error(&error);
+        exit(EXIT_SOURCE_ERROR);
+    });
+    let mut failed = 0;
+    for case in &cases {
+        match case.run() {
+            Ok(Ok(())) => println!("PASS {}", case.path.display()),
+            Ok(Err(failure)) => {
+                failed += 1;
+                println!("FAIL {}", case.path.display());
+                for line in failure.to_string().lines() {
+                    println!("    {}", line);
                 }
             }
-            _ => unreachable!(),
+            Err(error) => {
+                print_error(&error);
+                exit(EXIT_SOURCE_ERROR);
+            }
         }
     }
+    println!("{} passed, {} failed", cases.len() - failed, failed);
+    if failed > 0 {
+        exit(EXIT_TESTS_FAILED);
+    }
+}
+
+fn profile(matches: &ArgMatches) {
+    let file = matches.get_one::<String>("file").unwrap();
+    let source = read_source(file);
+    let config = brainfuck::Config {
+        eof: cli::eof_policy(matches),
+        dialect: cli::dialect(matches),
+        ..brainfuck::Config::default()
+    };
+    let mut profiler = brainfuck::Profiler::new(&source, &config).unwrap_or_else(|error| {
+        print_error(&error);
+        exit(EXIT_COMPILE_ERROR);
+    });
+    let result = profiler.run(&mut stdin().lock(), &mut stdout());
+    stdout().flush().ok();
+    let top = *matches.get_one::<usize>("top").unwrap();
+    let location = |start: usize, end: usize| {
+        let brainfuck::SourcePosition { line, column, .. } =
+            brainfuck::SourcePosition::new(&source, start);
+        let commands: String = source[start..end]
+            .chars()
+            .filter(|c| "+-<>,.[]#".contains(*c))
+            .collect();
+        let snippet = if commands.chars().count() > 40 {
+            format!("{}...", commands.chars().take(37).collect::<String>())
+        } else {
+            commands
+        };
+        format!("line {}, column {}: {}", line, column, snippet)
+    };
+
+    // the program output may not end with a newline
+    eprintln!();
+    eprintln!("executed {} commands", profiler.steps());
+    let mut blocks = profiler.blocks();
+    blocks.retain(|block| block.hits > 0 && block.commands > 0);
+    blocks.sort_by_key(|block| std::cmp::Reverse(block.hits * block.commands as u64));
+    eprintln!();
+    eprintln!("hottest blocks:");
+    eprintln!("{:>14}  {:>14}  location", "commands", "hits");
+    for block in blocks.iter().take(top) {
+        eprintln!(
+            "{:>14}  {:>14}  {}",
+            block.hits * block.commands as u64,
+            block.hits,
+            location(block.span.start, block.span.end)
+        );
+    }
+    let mut loops = profiler.loops();
+    loops.retain(|l| l.entries > 0);
+    loops.sort_by_key(|l| std::cmp::Reverse(l.iterations));
+    eprintln!();
+    eprintln!("hottest loops:");
+    eprintln!("{:>14}  {:>14}  location", "iterations", "entries");
+    for l in loops.iter().take(top) {
+        eprintln!(
+            "{:>14}  {:>14}  {}",
+            l.iterations,
+            l.entries,
+            location(l.open, l.close + 1)
+        );
+    }
+
+    if matches.contains_id("annotate") {
+        // the highest count among the commands on each line
+        let mut hits = profiler.command_hits().into_iter().peekable();
+        let mut line_start = 0;
+        eprintln!();
+        for line in source.split_inclusive('\n') {
+            let line_end = line_start + line.len();
+            let mut max_hits = None;
+            while let Some(&(_, count)) = hits.peek().filter(|&&(pos, _)| pos < line_end) {
+                max_hits = max_hits.max(Some(count));
+                hits.next();
+            }
+            let count = max_hits.map_or(String::new(), |count| count.to_string());
+            eprintln!("{:>14} | {}", count, line.trim_end_matches(['\r', '\n']));
+            line_start = line_end;
+        }
+    }
+
+    if let Err(error) = result {
+        eprintln!();
+        print_error(&error);
+        exit(EXIT_RUNTIME_ERROR);
+    }
 }
@@ -0,0 +1,519 @@
+//! An implementation of [Malbolge].
+//!
+//! Memory has 3<sup>10</sup> words of 10 trits each. Loading the source checks that every
+//! character is a valid instruction at its position, then fills the rest of memory with the
+//! crazy operation of the two previous words. Whitespace in the source is skipped.
+//!
+//! [`Config::wide_words`] switches to words of [`WIDE_TRITS`] trits, and so to 3<sup>20</sup>
+//! words of memory. Memory is still fixed in size and addresses wrap around at its end, unlike
+//! the unbounded memory of [Malbolge Unshackled]. The words past the source are computed when
+//! the program first uses them, since the fill repeats itself after a few words.
+//!
+//! The parts left undefined by the specification behave as follows:
+//!
+//! - Executing a word outside the printable range 33 to 126 stops the program with an error.
+//!   A word outside this range is left unchanged instead of being encrypted.
+//! - Output writes the lowest byte of `a`. Input at EOF sets `a` to the largest word, which
+//!   is 59048 in the standard memory model.
+//!
+//! [Malbolge]: https://esolangs.org/wiki/Malbolge
+//! [Malbolge Unshackled]: https://esolangs.org/wiki/Malbolge_Unshackled
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::time::Duration;
+use thiserror::Error;
+
+use crate::budget::{Budget, LimitExceeded};
+use crate::input::read_byte;
+use crate::{Language, Options};
+
+/// Number of trits in a word of the standard memory model.
+pub const TRITS: u32 = 10;
+/// Number of trits in a word under [`Config::wide_words`].
+pub const WIDE_TRITS: u32 = 20;
+
+/// The instructions in normalized form, indexed by `(character + position) % 94`.
+const INSTRUCTIONS: [(u64, char); 8] = [
+    (4, 'i'),
+    (5, '<'),
+    (23, '/'),
+    (39, '*'),
+    (40, 'j'),
+    (62, 'p'),
+    (68, 'o'),
+    (81, 'v'),
+];
+
+/// Replacement of each executed word, indexed by the word minus 33.
+const XLAT2: &[u8; 94] =
+    b"5z]&gqtyfr$(we4{WP)H-Zn,[%\\3dL+Q;>U!pJS72FhOA1CB6v^=I_0/8|jsb9m<.TVac`uY*MK'X~xDl}REokN:#?G\"i@";
+
+/// The crazy operation on single trits, indexed by the trit of `[d]` and then of `a`.
+const CRAZY: [[u64; 3]; 3] = [[1, 0, 0], [1, 0, 2], [2, 2, 1]];
+
+/// Error enum for Malbolge.
+#[derive(Error, Debug)]
+pub enum Error {
+    /// A character of the source is not a valid instruction at its position.
+    #[error(
+        "`{character}` at line {line}, column {column} is not a valid instruction at address {address}"
+    )]
+    InvalidInstruction {
+        /// The offending character.
+        character: char,
+        /// The memory address the character would be loaded at.
+        address: usize,
+        /// 1-based line number.
+        line: usize,
+        /// 1-based column, counted in characters.
+        column: usize,
+    },
+    /// The source has fewer than two instructions, which are needed to fill the memory.
+    #[error("source must contain at least two instructions")]
+    SourceTooShort,
+    /// The source has more instructions than the memory has words. Contains the number of
+    /// instructions.
+    #[error("source has {0} instructions, but the memory has only 59049 words")]
+    SourceTooLong(usize),
+    /// The code pointer reached a word that is not a printable character.
+    #[error("cannot execute {value} at address {address}")]
+    InvalidCode {
+        /// The value of the word.
+        value: u64,
+        /// The address of the word.
+        address: u64,
+    },
+    /// The program ran past [`Config::max_steps`] or [`Config::timeout`].
+    /// Contains the number of steps executed so far.
+    #[error("execution limit exceeded after {0} steps")]
+    LimitExceeded(u64),
+    /// I/O error, which may occur during I/O operations.
+    #[error("unexpected I/O error")]
+    IoError(#[from] io::Error),
+}
+
+impl From<LimitExceeded> for Error {
+    fn from(LimitExceeded(steps): LimitExceeded) -> Self {
+        Error::LimitExceeded(steps)
+    }
+}
+
+/// Settings of the Malbolge interpreter.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Config {
+    /// Maximum number of instructions to execute. `None` means unlimited.
+    pub max_steps: Option<u64>,
+    /// Wall-clock time limit of a single run. `None` means unlimited.
+    pub timeout: Option<Duration>,
+    /// Whether to use words of [`WIDE_TRITS`] trits instead of [`TRITS`], which also makes
+    /// memory 3<sup>20</sup> words long.
+    pub wide_words: bool,
+}
+
+/// Malbolge interpreter.
+pub fn run<I: BufRead, O: Write>(source: &str, input: &mut I, output: &mut O) -> Result<(), Error> {
+    run_with_config(source, input, output, &Config::default())
+}
+
+/// Malbolge interpreter with a custom configuration.
+pub fn run_with_config<I: BufRead, O: Write>(
+    source: &str,
+    input: &mut I,
+    output: &mut O,
+    config: &Config,
+) -> Result<(), Error> {
+    Program::compile_with_config(source, config)?.run(input, output)
+}
+
+/// Converts Malbolge source into its normalized form, where each instruction is written as
+/// one of `ji*p</vo` regardless of its position. Whitespace is dropped.
+pub fn normalize(source: &str) -> Result<String, Error> {
+    Ok(load(source)?
+        .into_iter()
+        .enumerate()
+        .map(|(address, value)| decode(value, address as u64).unwrap_or('o'))
+        .collect())
+}
+
+/// The [`Language`] implementation for Malbolge.
+///
+/// Compiles with the default [`Config`], except for the limits and the memory model taken
+/// from [`Options`].
+#[derive(Debug, Clone, Copy)]
+pub struct Malbolge;
+
+impl Language for Malbolge {
+    fn name(&self) -> &'static str {
+        "malbolge"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["mb"]
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["mb", "mal"]
+    }
+
+    fn compile(
+        &self,
+        source: &str,
+        options: &Options,
+    ) -> Result<Box<dyn crate::Program>, crate::Error> {
+        let config = Config {
+            max_steps: options.max_steps,
+            timeout: options.timeout,
+            wide_words: options.wide_words,
+        };
+        let program = Program::compile_with_config(source, &config)
+            .map_err(|error| crate::Error::compile(self.name(), error))?;
+        Ok(Box::new(program))
+    }
+}
+
+/// A Malbolge program loaded into memory.
+///
+/// Each run starts from a fresh copy of the memory, since Malbolge programs encrypt
+/// themselves as they run.
+#[derive(Debug, Clone)]
+pub struct Program {
+    memory: Memory,
+    config: Config,
+}
+
+impl Program {
+    /// Loads the source code with the default [`Config`].
+    pub fn compile(source: &str) -> Result<Self, Error> {
+        Self::compile_with_config(source, &Config::default())
+    }
+
+    /// Loads the source code with a custom configuration.
+    pub fn compile_with_config(source: &str, config: &Config) -> Result<Self, Error> {
+        let words = load(source)?;
+        let memory = if config.wide_words {
+            Memory::new(words, WIDE_TRITS)
+        } else {
+            if words.len() > 3usize.pow(TRITS) {
+                return Err(Error::SourceTooLong(words.len()));
+            }
+            let mut memory = Memory::new(words, TRITS);
+            memory.fill(memory.size - 1);
+            memory
+        };
+        Ok(Self {
+            memory,
+            config: config.clone(),
+        })
+    }
+
+    /// The configuration the program was compiled with.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Runs the program from the start.
+    pub fn run<I: BufRead, O: Write>(&self, input: &mut I, output: &mut O) -> Result<(), Error> {
+        let result = self.execute(input, output);
+        output.flush()?;
+        result
+    }
+
+    fn execute<I: BufRead, O: Write>(&self, input: &mut I, output: &mut O) -> Result<(), Error> {
+        let mut memory = self.memory.clone();
+        let mut budget = Budget::new(self.config.max_steps, self.config.timeout);
+        let (mut a, mut c, mut d) = (0u64, 0u64, 0u64);
+        loop {
+            budget.spend(1)?;
+            let value = memory.get(c);
+            let Some(instruction) = decode(value, c) else {
+                return Err(Error::InvalidCode { value, address: c });
+            };
+            match instruction {
+                'i' => c = memory.get(d),
+                '<' => output.write_all(&[a as u8])?,
+                '/' => {
+                    a = match read_byte(input)? {
+                        Some(byte) => byte as u64,
+                        None => memory.size - 1,
+                    }
+                }
+                '*' => {
+                    let value = memory.get(d);
+                    a = memory.rotate(value);
+                    memory.set(d, a);
+                }
+                'j' => d = memory.get(d),
+                'p' => {
+                    let value = memory.get(d);
+                    a = memory.crazy(a, value);
+                    memory.set(d, a);
+                }
+                'v' => return Ok(()),
+                _ => (),
+            }
+            let value = memory.get(c);
+            if (33..=126).contains(&value) {
+                memory.set(c, XLAT2[value as usize - 33] as u64);
+            }
+            c = memory.next(c);
+            d = memory.next(d);
+        }
+    }
+}
+
+impl crate::Program for Program {
+    fn run(
+        &self,
+        mut input: &mut dyn BufRead,
+        mut output: &mut dyn Write,
+    ) -> Result<(), crate::Error> {
+        Program::run(self, &mut input, &mut output)
+            .map_err(|error| crate::Error::runtime(Malbolge.name(), error))
+    }
+}
+
+/// The instruction a printable word at an address stands for, in normalized form. Words that
+/// are not instructions are no-ops, like `o`.
+fn decode(value: u64, address: u64) -> Option<char> {
+    if !(33..=126).contains(&value) {
+        return None;
+    }
+    let code = (value + address) % 94;
+    Some(
+        INSTRUCTIONS
+            .iter()
+            .find(|&&(c, _)| c == code)
+            .map_or('o', |&(_, instruction)| instruction),
+    )
+}
+
+/// Reads the instructions from the source, checking that each of them is valid.
+fn load(source: &str) -> Result<Vec<u64>, Error> {
+    let mut words = vec![];
+    for (line_no, line) in source.lines().enumerate() {
+        for (column, character) in line.chars().enumerate() {
+            if character.is_whitespace() {
+                continue;
+            }
+            let address = words.len();
+            let value = character as u64;
+            let valid = (33..=126).contains(&value)
+                && INSTRUCTIONS
+                    .iter()
+                    .any(|&(code, _)| code == (value + address as u64) % 94);
+            if !valid {
+                return Err(Error::InvalidInstruction {
+                    character,
+                    address,
+                    line: line_no + 1,
+                    column: column + 1,
+                });
+            }
+            words.push(value);
+        }
+    }
+    if words.len() < 2 {
+        return Err(Error::SourceTooShort);
+    }
+    Ok(words)
+}
+
+/// Memory of a running program.
+///
+/// `words` holds the source and the start of the fill. Since the crazy operation works trit by
+/// trit, and each trit of the fill repeats with a period of [`FILL_PERIOD`] after its first
+/// word, the words past `words` are computed from `period` when they are read, and kept in
+/// `written` once the program writes them. The memory thus stays small however far the
+/// program jumps.
+#[derive(Debug, Clone)]
+struct Memory {
+    words: Vec<u64>,
+    /// A period of the fill right after `words`, as it was before the program ran.
+    period: [u64; FILL_PERIOD],
+    written: HashMap<u64, u64>,
+    trits: u32,
+    /// Number of words in the address space, which is also the number of values of a word.
+    size: u64,
+}
+
+/// The period of the fill of the memory, after its first word.
+const FILL_PERIOD: usize = 6;
+
+impl Memory {
+    fn new(words: Vec<u64>, trits: u32) -> Self {
+        let mut memory = Self {
+            words,
+            period: [0; FILL_PERIOD],
+            written: HashMap::new(),
+            trits,
+            size: 3u64.pow(trits),
+        };
+        let len = memory.words.len() as u64 + FILL_PERIOD as u64;
+        memory.fill(len.min(memory.size - 1));
+        let start = memory.words.len() - FILL_PERIOD;
+        for (i, word) in memory.period.iter_mut().enumerate() {
+            *word = memory.words[start + i];
+        }
+        memory
+    }
+
+    /// Fills `words` up to and including `address`.
+    fn fill(&mut self, address: u64) {
+        let len = address as usize + 1;
+        self.words.reserve(len.saturating_sub(self.words.len()));
+        while self.words.len() < len {
+            let [.., x, y] = self.words[..] else {
+                unreachable!("memory starts with at least two words");
+            };
+            let word = self.crazy(y, x);
+            self.words.push(word);
+        }
+    }
+
+    fn get(&self, address: u64) -> u64 {
+        match self.words.get(address as usize) {
+            Some(&word) => word,
+            None => self.written.get(&address).copied().unwrap_or_else(|| {
+                let offset = address - self.words.len() as u64;
+                self.period[(offset % FILL_PERIOD as u64) as usize]
+            }),
+        }
+    }
+
+    fn set(&mut self, address: u64, value: u64) {
+        match self.words.get_mut(address as usize) {
+            Some(word) => *word = value,
+            None => {
+                self.written.insert(address, value);
+            }
+        }
+    }
+
+    fn next(&self, address: u64) -> u64 {
+        (address + 1) % self.size
+    }
+
+    /// Rotates a word right by one trit.
+    fn rotate(&self, value: u64) -> u64 {
+        value / 3 + value % 3 * (self.size / 3)
+    }
+
+    /// The crazy operation, trit by trit.
+    fn crazy(&self, mut a: u64, mut d: u64) -> u64 {
+        let (mut result, mut power) = (0, 1);
+        for _ in 0..self.trits {
+            result += CRAZY[(d % 3) as usize][(a % 3) as usize] * power;
+            (a, d, power) = (a / 3, d / 3, power * 3);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HELLO: &str =
+        "(=<`#9]~6ZY327Uv4-QsqpMn&+Ij\"'E%e{Ab~w=_:]Kw%o44Uqp0/Q?xNvL:`H%c#DD2^WV>gY;dts76qKJImZkj";
+
+    fn run_str(source: &str, input: &str, config: &Config) -> Result<Vec<u8>, Error> {
+        let config = Config {
+            max_steps: Some(100_000),
+            ..config.clone()
+        };
+        let mut stdout: Vec<u8> = vec![];
+        run_with_config(source, &mut input.as_bytes(), &mut stdout, &config)?;
+        Ok(stdout)
+    }
+
+    #[test]
+    fn test_malbolge() {
+        assert_eq!(
+            run_str(HELLO, "", &Config::default()).unwrap(),
+            b"Hello, world."
+        );
+        // whitespace is skipped without taking an address
+        let spaced = format!("{}\n  {}\n", &HELLO[..10], &HELLO[10..]);
+        assert_eq!(
+            run_str(&spaced, "", &Config::default()).unwrap(),
+            b"Hello, world."
+        );
+
+        // reads a byte, writes it and halts; EOF gives the largest word
+        let echo = "ubO";
+        let wide = Config {
+            wide_words: true,
+            ..Config::default()
+        };
+        for config in [&Config::default(), &wide] {
+            assert_eq!(run_str(echo, "A", config).unwrap(), b"A");
+        }
+        assert_eq!(
+            run_str(echo, "", &Config::default()).unwrap(),
+            [(59048 % 256) as u8]
+        );
+        assert_eq!(
+            run_str(echo, "", &wide).unwrap(),
+            [((3u64.pow(20) - 1) % 256) as u8]
+        );
+        let memory = Memory::new(vec![b'u' as u64, b'b' as u64], WIDE_TRITS);
+        assert!(memory.get(100_000) < 3u64.pow(20));
+        assert_eq!(memory.next(59048), 59049);
+        // words past the filled ones repeat the fill, and writes do not change it
+        let mut filled = Memory::new(vec![b'u' as u64, b'b' as u64], WIDE_TRITS);
+        filled.fill(1000);
+        let mut memory = memory.clone();
+        memory.set(500, 0);
+        for address in (0..=1000).filter(|&address| address != 500) {
+            assert_eq!(
+                memory.get(address),
+                filled.get(address),
+                "address {}",
+                address
+            );
+        }
+        assert_eq!(memory.get(500), 0);
+        // `j` jumps `d` near the end of memory without filling everything before it
+        assert!(matches!(
+            run_str("('&", "", &wide),
+            Err(Error::LimitExceeded(_)) | Err(Error::InvalidCode { .. })
+        ));
+
+        let normalized = normalize(HELLO).unwrap();
+        assert_eq!(normalized.len(), HELLO.len());
+        assert!(normalized.starts_with("jpp<"));
+
+        let memory = Memory::new(vec![0, 0], TRITS);
+        assert_eq!(memory.crazy(0, 0), 29524);
+        assert_eq!(memory.crazy(1, 0), 29523);
+        assert_eq!(memory.crazy(2, 1), 29525);
+        assert_eq!(memory.rotate(1), 19683);
+        assert_eq!(memory.rotate(19683 * 2 + 3), 6561 * 2 + 1);
+    }
+
+    #[test]
+    fn test_malbolge_errors() {
+        assert!(matches!(
+            run_str("(=\n b", "", &Config::default()),
+            Err(Error::InvalidInstruction {
+                character: 'b',
+                address: 2,
+                line: 2,
+                column: 2,
+            })
+        ));
+        assert!(matches!(
+            run_str("(", "", &Config::default()),
+            Err(Error::SourceTooShort)
+        ));
+        // a no-op at every address, one more than fits in memory
+        let nops: String = (0..59050)
+            .map(|address| char::from(33 + ((68 + 94 * 1000 - 33 - address) % 94) as u8))
+            .collect();
+        assert!(matches!(
+            run_str(&nops, "", &Config::default()),
+            Err(Error::SourceTooLong(59050))
+        ));
+    }
+}
@@ -0,0 +1,149 @@
+//! Loading the images Piet programs are drawn in.
+
+use super::{png, Error};
+
+/// An image decoded to 8-bit RGB.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Image {
+    pub(crate) width: usize,
+    pub(crate) height: usize,
+    /// Pixels in rows from top to bottom, each from left to right.
+    pub(crate) pixels: Vec<[u8; 3]>,
+}
+
+/// Decodes a PPM or PNG image, recognized by its first bytes.
+pub(crate) fn decode(bytes: &[u8]) -> Result<Image, Error> {
+    let invalid = |format| move |message| Error::InvalidImage { format, message };
+    if bytes.starts_with(png::SIGNATURE) {
+        png::decode(bytes).map_err(invalid("PNG"))
+    } else if bytes.starts_with(b"P3") || bytes.starts_with(b"P6") {
+        decode_ppm(bytes).map_err(invalid("PPM"))
+    } else {
+        Err(Error::UnknownFormat)
+    }
+}
+
+/// Decodes a PPM image, either in the plain (`P3`) or the raw (`P6`) format.
+fn decode_ppm(bytes: &[u8]) -> Result<Image, &'static str> {
+    let mut reader = PpmReader { bytes, position: 2 };
+    let width = reader.number()? as usize;
+    let height = reader.number()? as usize;
+    let max = reader.number()?;
+    if width == 0 || height == 0 {
+        return Err("image is empty");
+    }
+    if !(1..=65535).contains(&max) {
+        return Err("maximum value must be between 1 and 65535");
+    }
+    let len = width
+        .checked_mul(height)
+        .and_then(|pixels| pixels.checked_mul(3))
+        .ok_or("image is too large")?;
+    let scale = |value: u32| -> Result<u8, &'static str> {
+        if value > max {
+            return Err("sample is larger than the maximum value");
+        }
+        Ok(((value * 255 + max / 2) / max) as u8)
+    };
+    let samples = if bytes[1] == b'3' {
+        let mut samples = vec![];
+        while samples.len() < len {
+            samples.push(scale(reader.number()?)?);
+        }
+        samples
+    } else {
+        // a single whitespace character separates the header from the raster
+        let raster = bytes.get(reader.position + 1..).unwrap_or_default();
+        let sample_size = if max < 256 { 1 } else { 2 };
+        if raster.len() < len * sample_size {
+            return Err("image data is too short");
+        }
+        raster
+            .chunks(sample_size)
+            .take(len)
+            .map(|sample| {
+                scale(
+                    sample
+                        .iter()
+                        .fold(0, |value, &byte| value << 8 | byte as u32),
+                )
+            })
+            .collect::<Result<_, _>>()?
+    };
+    Ok(Image {
+        width,
+        height,
+        pixels: samples
+            .chunks(3)
+            .map(|rgb| [rgb[0], rgb[1], rgb[2]])
+            .collect(),
+    })
+}
+
+/// Reads the whitespace-separated numbers of a PPM header or plain raster.
+struct PpmReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl PpmReader<'_> {
+    fn number(&mut self) -> Result<u32, &'static str> {
+        loop {
+            match self.bytes.get(self.position) {
+                Some(byte) if byte.is_ascii_whitespace() => self.position += 1,
+                // comments run to the end of the line
+                Some(b'#') => {
+                    while self
+                        .bytes
+                        .get(self.position)
+                        .is_some_and(|&byte| byte != b'\n')
+                    {
+                        self.position += 1;
+                    }
+                }
+                Some(byte) if byte.is_ascii_digit() => break,
+                Some(_) => return Err("expected a number"),
+                None => return Err("unexpected end of file"),
+            }
+        }
+        let mut value: u32 = 0;
+        while let Some(&byte) = self
+            .bytes
+            .get(self.position)
+            .filter(|byte| byte.is_ascii_digit())
+        {
+            value = value
+                .checked_mul(10)
+                .and_then(|value| value.checked_add((byte - b'0') as u32))
+                .ok_or("number is too large")?;
+            self.position += 1;
+        }
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_ppm() {
+        let plain = b"P3\n# a comment\n2 1\n15\n15 0 0  0 8 15\n";
+        let mut raw = b"P6 2 1 255\n".to_vec();
+        raw.extend_from_slice(&[255, 0, 0, 0, 136, 255]);
+        for bytes in [&plain[..], &raw] {
+            let image = decode(bytes).unwrap();
+            assert_eq!((image.width, image.height), (2, 1));
+            assert_eq!(image.pixels, [[255, 0, 0], [0, 136, 255]]);
+        }
+
+        assert!(matches!(
+            decode(b"P3 1 1 255 0 0"),
+            Err(Error::InvalidImage {
+                format: "PPM",
+                message: "unexpected end of file"
+            })
+        ));
+        assert!(matches!(decode(b"GIF89a"), Err(Error::UnknownFormat)));
+    }
+}
@@ -0,0 +1,321 @@
+//! Decompression of zlib streams, just enough for PNG.
+//!
+//! Huffman codes are decoded one bit at a time from the counts of codes of each length, as in
+//! zlib's `puff`. It is not fast, but Piet images are small.
+
+/// Maximum length of a Huffman code in DEFLATE.
+const MAX_BITS: usize = 15;
+
+/// Base lengths of the length codes 257 to 285.
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+/// Extra bits of the length codes 257 to 285.
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+/// Base distances of the distance codes 0 to 29.
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+/// Extra bits of the distance codes 0 to 29.
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// Order in which the code lengths of the code length code are stored in a dynamic block.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// Decompresses a zlib stream and checks its Adler-32 checksum.
+pub(crate) fn decompress(data: &[u8]) -> Result<Vec<u8>, &'static str> {
+    let [cmf, flg, ..] = *data else {
+        return Err("truncated zlib stream");
+    };
+    if cmf & 0x0f != 8 || cmf >> 4 > 7 || (u16::from(cmf) << 8 | u16::from(flg)) % 31 != 0 {
+        return Err("invalid zlib header");
+    }
+    if flg & 0x20 != 0 {
+        return Err("zlib preset dictionaries are not supported");
+    }
+    let mut reader = BitReader {
+        data,
+        position: 2,
+        bits: 0,
+        count: 0,
+    };
+    let mut output = vec![];
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => stored(&mut reader, &mut output)?,
+            1 => {
+                let (lengths, distances) = fixed_codes();
+                compressed(&mut reader, &mut output, &lengths, &distances)?
+            }
+            2 => {
+                let (lengths, distances) = dynamic_codes(&mut reader)?;
+                compressed(&mut reader, &mut output, &lengths, &distances)?
+            }
+            _ => return Err("invalid DEFLATE block type"),
+        }
+        if last {
+            break;
+        }
+    }
+    let checksum = data
+        .get(reader.position..reader.position + 4)
+        .ok_or("truncated zlib stream")?;
+    if u32::from_be_bytes(checksum.try_into().unwrap()) != adler32(&output) {
+        return Err("zlib checksum mismatch");
+    }
+    Ok(output)
+}
+
+/// Computes the Adler-32 checksum that ends a zlib stream.
+pub(crate) fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += u32::from(byte);
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    b << 16 | a
+}
+
+/// Reads the bits of a DEFLATE stream, least significant first.
+struct BitReader<'a> {
+    data: &'a [u8],
+    /// Index of the next byte to load into `bits`.
+    position: usize,
+    bits: u32,
+    count: u32,
+}
+
+impl BitReader<'_> {
+    fn bits(&mut self, need: u32) -> Result<u32, &'static str> {
+        while self.count < need {
+            let byte = *self
+                .data
+                .get(self.position)
+                .ok_or("truncated DEFLATE stream")?;
+            self.position += 1;
+            self.bits |= u32::from(byte) << self.count;
+            self.count += 8;
+        }
+        let value = self.bits & ((1 << need) - 1);
+        self.bits >>= need;
+        self.count -= need;
+        Ok(value)
+    }
+
+    /// Discards the bits left in the current byte.
+    fn align(&mut self) {
+        self.bits = 0;
+        self.count = 0;
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&[u8], &'static str> {
+        let bytes = self
+            .data
+            .get(self.position..self.position + len)
+            .ok_or("truncated DEFLATE stream")?;
+        self.position += len;
+        Ok(bytes)
+    }
+}
+
+/// A canonical Huffman code.
+struct Huffman {
+    /// Number of codes of each length.
+    counts: [u16; MAX_BITS + 1],
+    /// Symbols ordered by their codes.
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Self, &'static str> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        // over-subscribed codes cannot be decoded; incomplete ones are allowed
+        let mut left = 1i32;
+        for &count in &counts[1..] {
+            left = left * 2 - i32::from(count);
+            if left < 0 {
+                return Err("invalid Huffman code");
+            }
+        }
+        let mut offsets = [0u16; MAX_BITS + 2];
+        for len in 1..=MAX_BITS {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+        let mut symbols = vec![0; offsets[MAX_BITS + 1] as usize];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[offsets[len as usize] as usize] = symbol as u16;
+                offsets[len as usize] += 1;
+            }
+        }
+        Ok(Self { counts, symbols })
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, &'static str> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for &count in &self.counts[1..] {
+            code |= reader.bits(1)? as i32;
+            let count = i32::from(count);
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("invalid Huffman code")
+    }
+}
+
+fn stored(reader: &mut BitReader, output: &mut Vec<u8>) -> Result<(), &'static str> {
+    reader.align();
+    let header = reader.bytes(4)?;
+    let len = u16::from_le_bytes([header[0], header[1]]);
+    let complement = u16::from_le_bytes([header[2], header[3]]);
+    if len != !complement {
+        return Err("invalid stored block length");
+    }
+    output.extend_from_slice(reader.bytes(len as usize)?);
+    Ok(())
+}
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+    let lengths = Huffman::new(&lengths).expect("the fixed code is valid");
+    let distances = Huffman::new(&[5; 30]).expect("the fixed code is valid");
+    (lengths, distances)
+}
+
+fn dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman), &'static str> {
+    let literals = reader.bits(5)? as usize + 257;
+    let distances = reader.bits(5)? as usize + 1;
+    let code_lengths = reader.bits(4)? as usize + 4;
+    if literals > 286 || distances > 30 {
+        return Err("invalid DEFLATE code lengths");
+    }
+    let mut lengths = [0u8; 19];
+    for &index in &CODE_LENGTH_ORDER[..code_lengths] {
+        lengths[index] = reader.bits(3)? as u8;
+    }
+    let code_length_code = Huffman::new(&lengths)?;
+
+    let mut lengths = vec![];
+    while lengths.len() < literals + distances {
+        let (value, repeat) = match code_length_code.decode(reader)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths.last().ok_or("invalid DEFLATE code lengths")?;
+                (previous, 3 + reader.bits(2)?)
+            }
+            17 => (0, 3 + reader.bits(3)?),
+            _ => (0, 11 + reader.bits(7)?),
+        };
+        if lengths.len() + repeat as usize > literals + distances {
+            return Err("invalid DEFLATE code lengths");
+        }
+        lengths.resize(lengths.len() + repeat as usize, value);
+    }
+    if lengths[256] == 0 {
+        return Err("DEFLATE block has no end code");
+    }
+    Ok((
+        Huffman::new(&lengths[..literals])?,
+        Huffman::new(&lengths[literals..])?,
+    ))
+}
+
+fn compressed(
+    reader: &mut BitReader,
+    output: &mut Vec<u8>,
+    lengths: &Huffman,
+    distances: &Huffman,
+) -> Result<(), &'static str> {
+    loop {
+        let symbol = lengths.decode(reader)? as usize;
+        match symbol {
+            0..=255 => output.push(symbol as u8),
+            256 => return Ok(()),
+            257..=285 => {
+                let index = symbol - 257;
+                let len = LENGTH_BASE[index] as usize
+                    + reader.bits(u32::from(LENGTH_EXTRA[index]))? as usize;
+                let index = distances.decode(reader)? as usize;
+                if index >= 30 {
+                    return Err("invalid DEFLATE distance");
+                }
+                let distance = DISTANCE_BASE[index] as usize
+                    + reader.bits(u32::from(DISTANCE_EXTRA[index]))? as usize;
+                if distance > output.len() {
+                    return Err("invalid DEFLATE distance");
+                }
+                // copied byte by byte, since the source may overlap the bytes being written
+                let start = output.len() - distance;
+                for i in 0..len {
+                    output.push(output[start + i]);
+                }
+            }
+            _ => return Err("invalid DEFLATE length"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decompress() {
+        // stored, fixed and dynamic blocks, as produced by Python's zlib
+        let stored = [
+            0x78, 0x01, 0x01, 0x05, 0x00, 0xfa, 0xff, 0x68, 0x65, 0x6c, 0x6c, 0x6f, 0x06, 0x2c,
+            0x02, 0x15,
+        ];
+        assert_eq!(decompress(&stored).unwrap(), b"hello");
+        let fixed = [
+            0x78, 0xda, 0x4b, 0x4c, 0x2a, 0x4a, 0x4c, 0x4e, 0x4c, 0x49, 0x04, 0x52, 0x0a, 0x89,
+            0xd8, 0xd9, 0x00, 0xee, 0x28, 0x0d, 0x3d,
+        ];
+        assert_eq!(
+            decompress(&fixed).unwrap(),
+            b"abracadabra abracadabra abracadabra"
+        );
+        let dynamic = [
+            0x78, 0xda, 0x4d, 0xcf, 0xc1, 0x0d, 0x00, 0x31, 0x08, 0x03, 0xc1, 0x96, 0x02, 0x09,
+            0x09, 0xf4, 0xdf, 0xd8, 0x81, 0x8d, 0xd0, 0xcd, 0xdb, 0x5a, 0xc9, 0x4b, 0x44, 0x55,
+            0x77, 0x3a, 0xc5, 0xe0, 0xd2, 0x73, 0x8f, 0x88, 0x95, 0xa4, 0x28, 0x6c, 0x3a, 0x58,
+            0xbd, 0xe4, 0x25, 0x60, 0x91, 0x60, 0x35, 0xb9, 0x8e, 0x91, 0x63, 0x35, 0xb9, 0x8e,
+            0x91, 0x61, 0x35, 0xb9, 0x8e, 0x91, 0x62, 0x35, 0xb9, 0x8e, 0x51, 0x60, 0x35, 0xb9,
+            0xf3, 0x7b, 0x62, 0x17, 0xab, 0xca, 0x7d, 0xed, 0x04, 0x2f, 0xf5,
+        ];
+        let expected: Vec<u8> = (0..60u8)
+            .flat_map(|i| vec![b'0' + i % 10; i as usize % 7 + 1])
+            .collect();
+        assert_eq!(decompress(&dynamic).unwrap(), expected);
+
+        let mut corrupted = fixed.to_vec();
+        *corrupted.last_mut().unwrap() ^= 1;
+        assert_eq!(decompress(&corrupted), Err("zlib checksum mismatch"));
+        assert_eq!(decompress(&fixed[..6]), Err("truncated DEFLATE stream"));
+        assert_eq!(decompress(b"\x78\x02"), Err("invalid zlib header"));
+    }
+}
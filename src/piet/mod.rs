@@ -0,0 +1,862 @@
+//! An implementation of [Piet].
+//!
+//! Programs are images in PPM (`P3` or `P6`) or PNG format. The image is divided into square
+//! codels of [`Config::codel_size`] pixels, and each codel takes the color of its top-left
+//! pixel. Color blocks and the exit codel of each block in every direction are found when the
+//! program is compiled, so running it only follows the precomputed exits.
+//!
+//! The parts left undefined by the specification behave as follows:
+//!
+//! - Pixels of any color other than the 20 Piet colors are treated as white.
+//! - Values on the stack are 64-bit integers that wrap around on overflow.
+//! - Division rounds towards negative infinity, so that it agrees with `mod`, whose result
+//!   has the sign of the divisor.
+//! - Commands that cannot be performed are ignored and leave the stack as it was. This
+//!   includes commands with too few values on the stack, division by zero, `roll` with a
+//!   negative or too large depth, input at EOF and `out(char)` of a value that is not a
+//!   Unicode scalar value.
+//! - Characters are read and written as UTF-8. `in(number)` skips anything before the next
+//!   decimal number.
+//! - Sliding through white follows the revised specification: no command is executed, and
+//!   the program ends when the pointer comes back to a white codel it has already turned at
+//!   with the same direction pointer.
+//!
+//! [Piet]: https://www.dangermouse.net/esoteric/piet.html
+
+use std::collections::HashSet;
+use std::io::{self, BufRead, Write};
+use std::time::Duration;
+use thiserror::Error;
+
+use crate::budget::{Budget, LimitExceeded};
+use crate::input::{read_char, read_number};
+use crate::{Language, Options};
+
+mod image;
+mod inflate;
+mod png;
+
+/// Error enum for Piet.
+#[derive(Error, Debug)]
+pub enum Error {
+    /// The source is neither a PPM nor a PNG image.
+    #[error("unrecognized image format, expected PPM or PNG")]
+    UnknownFormat,
+    /// The image could not be decoded.
+    #[error("invalid {format} image: {message}")]
+    InvalidImage {
+        /// Name of the image format.
+        format: &'static str,
+        /// What is wrong with the image.
+        message: &'static str,
+    },
+    /// The width or the height of the image is not a multiple of the codel size.
+    #[error("codel size {codel_size} does not divide the image size {width}x{height}")]
+    InvalidCodelSize {
+        /// The codel size from the configuration.
+        codel_size: usize,
+        /// Width of the image in pixels.
+        width: usize,
+        /// Height of the image in pixels.
+        height: usize,
+    },
+    /// The program ran past [`Config::max_steps`] or [`Config::timeout`].
+    /// Contains the number of steps executed so far.
+    #[error("execution limit exceeded after {0} steps")]
+    LimitExceeded(u64),
+    /// I/O error, which may occur during I/O operations.
+    #[error("unexpected I/O error")]
+    IoError(#[from] io::Error),
+}
+
+impl From<LimitExceeded> for Error {
+    fn from(LimitExceeded(steps): LimitExceeded) -> Self {
+        Error::LimitExceeded(steps)
+    }
+}
+
+/// Settings of the Piet interpreter.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Config {
+    /// Maximum number of steps to execute, counting each move to another block and each codel
+    /// slid through in white. `None` means unlimited.
+    pub max_steps: Option<u64>,
+    /// Wall-clock time limit of a single run. `None` means unlimited.
+    pub timeout: Option<Duration>,
+    /// Width and height of a codel in pixels. `None` detects it from the image, as the
+    /// largest size that every horizontal and vertical run of same-colored pixels is a
+    /// multiple of.
+    pub codel_size: Option<usize>,
+}
+
+/// Piet interpreter.
+pub fn run<I: BufRead, O: Write>(
+    source: &[u8],
+    input: &mut I,
+    output: &mut O,
+) -> Result<(), Error> {
+    run_with_config(source, input, output, &Config::default())
+}
+
+/// Piet interpreter with a custom configuration.
+pub fn run_with_config<I: BufRead, O: Write>(
+    source: &[u8],
+    input: &mut I,
+    output: &mut O,
+    config: &Config,
+) -> Result<(), Error> {
+    Program::compile_with_config(source, config)?.run(input, output)
+}
+
+/// The [`Language`] implementation for Piet.
+///
+/// Compiles with the default [`Config`], except for the limits and the codel size taken from
+/// [`Options`]. The source is the raw bytes of the image, so it should be given to
+/// [`compile_bytes`](Language::compile_bytes); [`compile`](Language::compile) only works
+/// for images that happen to be valid UTF-8, such as plain PPM.
+#[derive(Debug, Clone, Copy)]
+pub struct Piet;
+
+impl Language for Piet {
+    fn name(&self) -> &'static str {
+        "piet"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["png", "ppm"]
+    }
+
+    fn compile(
+        &self,
+        source: &str,
+        options: &Options,
+    ) -> Result<Box<dyn crate::Program>, crate::Error> {
+        self.compile_bytes(source.as_bytes(), options)
+    }
+
+    fn compile_bytes(
+        &self,
+        source: &[u8],
+        options: &Options,
+    ) -> Result<Box<dyn crate::Program>, crate::Error> {
+        let config = Config {
+            max_steps: options.max_steps,
+            timeout: options.timeout,
+            codel_size: options.codel_size,
+        };
+        let program = Program::compile_with_config(source, &config)
+            .map_err(|error| crate::Error::compile(self.name(), error))?;
+        Ok(Box::new(program))
+    }
+}
+
+/// The color of a codel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Color {
+    White,
+    Black,
+    /// One of the 18 colors that commands are made of. `hue` goes from red to magenta in the
+    /// order of the hue cycle, and `lightness` from light to dark.
+    Hue {
+        hue: u8,
+        lightness: u8,
+    },
+}
+
+impl Color {
+    fn from_rgb(rgb: [u8; 3]) -> Self {
+        // the light, normal and dark variants of a hue differ only in the levels they use
+        const LEVELS: [(u8, u8); 3] = [(0xff, 0xc0), (0xff, 0x00), (0xc0, 0x00)];
+        const HUES: [[bool; 3]; 6] = [
+            [true, false, false],
+            [true, true, false],
+            [false, true, false],
+            [false, true, true],
+            [false, false, true],
+            [true, false, true],
+        ];
+        match rgb {
+            [0xff, 0xff, 0xff] => return Color::White,
+            [0x00, 0x00, 0x00] => return Color::Black,
+            _ => (),
+        }
+        for (lightness, (high, low)) in LEVELS.into_iter().enumerate() {
+            for (hue, channels) in HUES.iter().enumerate() {
+                if rgb
+                    .iter()
+                    .zip(channels)
+                    .all(|(&value, &on)| value == if on { high } else { low })
+                {
+                    return Color::Hue {
+                        hue: hue as u8,
+                        lightness: lightness as u8,
+                    };
+                }
+            }
+        }
+        Color::White
+    }
+}
+
+/// A Piet command, named as in the specification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Command {
+    None,
+    Push,
+    Pop,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Mod,
+    Not,
+    Greater,
+    Pointer,
+    Switch,
+    Duplicate,
+    Roll,
+    InNumber,
+    InChar,
+    OutNumber,
+    OutChar,
+}
+
+/// The commands, indexed by the change in hue and then the change in lightness.
+const COMMANDS: [[Command; 3]; 6] = {
+    use Command::*;
+    [
+        [None, Push, Pop],
+        [Add, Subtract, Multiply],
+        [Divide, Mod, Not],
+        [Greater, Pointer, Switch],
+        [Duplicate, Roll, InNumber],
+        [InChar, OutNumber, OutChar],
+    ]
+};
+
+/// Column and row steps of the direction pointer: right, down, left and up.
+const DIRECTIONS: [(isize, isize); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+
+/// A color block, that is a connected area of codels of the same color.
+#[derive(Debug, Clone)]
+struct Block {
+    color: Color,
+    /// Number of codels in the block.
+    size: usize,
+    /// The codel the pointer leaves the block from, indexed by `dp * 2 + cc`. Unused for
+    /// white blocks.
+    exits: [usize; 8],
+}
+
+/// A Piet program, with its color blocks found.
+#[derive(Debug, Clone)]
+pub struct Program {
+    /// Width of the program in codels.
+    width: usize,
+    /// Height of the program in codels.
+    height: usize,
+    codel_size: usize,
+    /// The block of each codel, or `None` for black codels.
+    codels: Vec<Option<usize>>,
+    blocks: Vec<Block>,
+    config: Config,
+}
+
+impl Program {
+    /// Decodes the image with the default [`Config`].
+    pub fn compile(source: &[u8]) -> Result<Self, Error> {
+        Self::compile_with_config(source, &Config::default())
+    }
+
+    /// Decodes the image with a custom configuration.
+    pub fn compile_with_config(source: &[u8], config: &Config) -> Result<Self, Error> {
+        let image = image::decode(source)?;
+        let codel_size = config
+            .codel_size
+            .unwrap_or_else(|| detect_codel_size(&image));
+        if codel_size == 0 || image.width % codel_size != 0 || image.height % codel_size != 0 {
+            return Err(Error::InvalidCodelSize {
+                codel_size,
+                width: image.width,
+                height: image.height,
+            });
+        }
+        let (width, height) = (image.width / codel_size, image.height / codel_size);
+        let colors: Vec<Color> = (0..width * height)
+            .map(|i| {
+                let (x, y) = (i % width * codel_size, i / width * codel_size);
+                Color::from_rgb(image.pixels[y * image.width + x])
+            })
+            .collect();
+
+        let mut program = Self {
+            width,
+            height,
+            codel_size,
+            codels: vec![None; colors.len()],
+            blocks: vec![],
+            config: config.clone(),
+        };
+        for start in 0..colors.len() {
+            if colors[start] != Color::Black && program.codels[start].is_none() {
+                program.fill_block(&colors, start);
+            }
+        }
+        Ok(program)
+    }
+
+    /// Finds the block of same-colored codels around `start`, along with its exits.
+    fn fill_block(&mut self, colors: &[Color], start: usize) {
+        let id = self.blocks.len();
+        let color = colors[start];
+        let mut size = 0;
+        // the exits are the codels furthest along the DP and then along the CC
+        let mut furthest = [((isize::MIN, isize::MIN), start); 8];
+        let mut pending = vec![start];
+        self.codels[start] = Some(id);
+        while let Some(codel) = pending.pop() {
+            size += 1;
+            let (x, y) = ((codel % self.width) as isize, (codel / self.width) as isize);
+            for (i, exit) in furthest.iter_mut().enumerate() {
+                let (dp, cc) = (i / 2, i % 2);
+                // CC left points counterclockwise of the DP, and CC right clockwise
+                let (dx, dy) = DIRECTIONS[dp];
+                let (cx, cy) = DIRECTIONS[(dp + if cc == 0 { 3 } else { 1 }) % 4];
+                let key = (x * dx + y * dy, x * cx + y * cy);
+                if key > exit.0 {
+                    *exit = (key, codel);
+                }
+            }
+            for dp in 0..4 {
+                if let Some(next) = self.neighbor(codel, dp) {
+                    if colors[next] == color && self.codels[next].is_none() {
+                        self.codels[next] = Some(id);
+                        pending.push(next);
+                    }
+                }
+            }
+        }
+        self.blocks.push(Block {
+            color,
+            size,
+            exits: furthest.map(|(_, codel)| codel),
+        });
+    }
+
+    /// The codel next to `codel` in the direction `dp`, if it is inside the image.
+    fn neighbor(&self, codel: usize, dp: usize) -> Option<usize> {
+        let (dx, dy) = DIRECTIONS[dp];
+        let x = (codel % self.width).checked_add_signed(dx)?;
+        let y = (codel / self.width).checked_add_signed(dy)?;
+        (x < self.width && y < self.height).then_some(y * self.width + x)
+    }
+
+    /// The codel next to `codel` in the direction `dp`, if the pointer can move there.
+    fn step(&self, codel: usize, dp: usize) -> Option<usize> {
+        self.neighbor(codel, dp)
+            .filter(|&next| self.codels[next].is_some())
+    }
+
+    /// The configuration the program was compiled with.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Size of a codel in pixels, either from the configuration or detected from the image.
+    pub fn codel_size(&self) -> usize {
+        self.codel_size
+    }
+
+    /// Runs the program from the start.
+    pub fn run<I: BufRead, O: Write>(&self, input: &mut I, output: &mut O) -> Result<(), Error> {
+        let result = self.execute(input, output);
+        output.flush()?;
+        result
+    }
+
+    fn execute<I: BufRead, O: Write>(&self, input: &mut I, output: &mut O) -> Result<(), Error> {
+        let mut budget = Budget::new(self.config.max_steps, self.config.timeout);
+        let mut stack: Vec<i64> = vec![];
+        let (mut dp, mut cc) = (0, 0);
+        let mut codel = 0;
+        let Some(mut block) = self.codels[codel] else {
+            return Ok(());
+        };
+        loop {
+            budget.spend(1)?;
+            let current = &self.blocks[block];
+            let Color::Hue { hue, lightness } = current.color else {
+                match self.slide(codel, &mut dp, &mut cc, &mut budget)? {
+                    Some(next) => codel = next,
+                    None => return Ok(()),
+                }
+                block = self.codels[codel].unwrap();
+                continue;
+            };
+            let mut attempts = 0;
+            codel = loop {
+                if let Some(next) = self.step(current.exits[dp * 2 + cc], dp) {
+                    break next;
+                }
+                attempts += 1;
+                if attempts == 8 {
+                    return Ok(());
+                }
+                if attempts % 2 == 1 {
+                    cc ^= 1;
+                } else {
+                    dp = (dp + 1) % 4;
+                }
+            };
+            block = self.codels[codel].unwrap();
+            if let Color::Hue {
+                hue: next_hue,
+                lightness: next_lightness,
+            } = self.blocks[block].color
+            {
+                let command = COMMANDS[((next_hue + 6 - hue) % 6) as usize]
+                    [((next_lightness + 3 - lightness) % 3) as usize];
+                perform(
+                    command,
+                    current.size,
+                    &mut stack,
+                    (&mut dp, &mut cc),
+                    input,
+                    output,
+                )?;
+            }
+        }
+    }
+
+    /// Slides from a white codel in the direction of the DP until the pointer reaches a
+    /// colored codel, which is returned, or until it is trapped in the white block.
+    fn slide(
+        &self,
+        mut codel: usize,
+        dp: &mut usize,
+        cc: &mut usize,
+        budget: &mut Budget,
+    ) -> Result<Option<usize>, Error> {
+        let mut turns = HashSet::new();
+        loop {
+            match self.step(codel, *dp) {
+                Some(next) => {
+                    codel = next;
+                    if self.blocks[self.codels[codel].unwrap()].color != Color::White {
+                        return Ok(Some(codel));
+                    }
+                    budget.spend(1)?;
+                }
+                None => {
+                    if !turns.insert((codel, *dp)) {
+                        return Ok(None);
+                    }
+                    *cc ^= 1;
+                    *dp = (*dp + 1) % 4;
+                }
+            }
+        }
+    }
+}
+
+impl crate::Program for Program {
+    fn run(
+        &self,
+        mut input: &mut dyn BufRead,
+        mut output: &mut dyn Write,
+    ) -> Result<(), crate::Error> {
+        Program::run(self, &mut input, &mut output)
+            .map_err(|error| crate::Error::runtime(Piet.name(), error))
+    }
+}
+
+/// Performs a command when the pointer leaves a block of `size` codels.
+fn perform<I: BufRead, O: Write>(
+    command: Command,
+    size: usize,
+    stack: &mut Vec<i64>,
+    (dp, cc): (&mut usize, &mut usize),
+    input: &mut I,
+    output: &mut O,
+) -> Result<(), Error> {
+    match command {
+        Command::None => (),
+        Command::Push => stack.push(size as i64),
+        Command::Pop => {
+            stack.pop();
+        }
+        Command::Add => binary(stack, |a, b| Some(a.wrapping_add(b))),
+        Command::Subtract => binary(stack, |a, b| Some(a.wrapping_sub(b))),
+        Command::Multiply => binary(stack, |a, b| Some(a.wrapping_mul(b))),
+        Command::Divide => binary(stack, |a, b| {
+            (b != 0).then(|| {
+                let quotient = a.wrapping_div(b);
+                if a.wrapping_rem(b) != 0 && (a < 0) != (b < 0) {
+                    quotient - 1
+                } else {
+                    quotient
+                }
+            })
+        }),
+        Command::Mod => binary(stack, |a, b| {
+            (b != 0).then(|| {
+                let remainder = a.wrapping_rem(b);
+                if remainder != 0 && (remainder < 0) != (b < 0) {
+                    remainder + b
+                } else {
+                    remainder
+                }
+            })
+        }),
+        Command::Not => {
+            if let Some(top) = stack.last_mut() {
+                *top = (*top == 0) as i64;
+            }
+        }
+        Command::Greater => binary(stack, |a, b| Some((a > b) as i64)),
+        Command::Pointer => {
+            if let Some(turns) = stack.pop() {
+                *dp = (*dp + turns.rem_euclid(4) as usize) % 4;
+            }
+        }
+        Command::Switch => {
+            if let Some(toggles) = stack.pop() {
+                *cc ^= (toggles % 2 != 0) as usize;
+            }
+        }
+        Command::Duplicate => {
+            if let Some(&top) = stack.last() {
+                stack.push(top);
+            }
+        }
+        Command::Roll => {
+            if let [.., depth, rolls] = stack[..] {
+                let len = stack.len() - 2;
+                if (0..=len as i64).contains(&depth) {
+                    stack.truncate(len);
+                    if depth > 0 {
+                        let start = stack.len() - depth as usize;
+                        stack[start..].rotate_right(rolls.rem_euclid(depth) as usize);
+                    }
+                }
+            }
+        }
+        Command::InNumber => {
+            if let Some(value) = read_number(input)? {
+                stack.push(value);
+            }
+        }
+        Command::InChar => {
+            if let Some(c) = read_char(input)? {
+                stack.push(c as i64);
+            }
+        }
+        Command::OutNumber => {
+            if let Some(value) = stack.pop() {
+                write!(output, "{}", value)?;
+            }
+        }
+        Command::OutChar => {
+            let c = stack
+                .last()
+                .and_then(|&value| u32::try_from(value).ok())
+                .and_then(char::from_u32);
+            if let Some(c) = c {
+                stack.pop();
+                write!(output, "{}", c)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Replaces the top two values with `f(second, top)`, unless there are fewer than two values
+/// or `f` returns `None`.
+fn binary(stack: &mut Vec<i64>, f: impl FnOnce(i64, i64) -> Option<i64>) {
+    if let [.., a, b] = stack[..] {
+        if let Some(value) = f(a, b) {
+            stack.truncate(stack.len() - 2);
+            stack.push(value);
+        }
+    }
+}
+
+/// Finds the largest codel size that every run of same-colored pixels is a multiple of.
+fn detect_codel_size(image: &image::Image) -> usize {
+    fn gcd(a: usize, b: usize) -> usize {
+        if b == 0 {
+            a
+        } else {
+            gcd(b, a % b)
+        }
+    }
+    let pixel = |x: usize, y: usize| image.pixels[y * image.width + x];
+    let mut size = gcd(image.width, image.height);
+    for y in 0..image.height {
+        let mut run = 1;
+        for x in 1..image.width {
+            if pixel(x, y) == pixel(x - 1, y) {
+                run += 1;
+            } else {
+                size = gcd(size, run);
+                run = 1;
+            }
+        }
+    }
+    for x in 0..image.width {
+        let mut run = 1;
+        for y in 1..image.height {
+            if pixel(x, y) == pixel(x, y - 1) {
+                run += 1;
+            } else {
+                size = gcd(size, run);
+                run = 1;
+            }
+        }
+    }
+    size
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// RGB of a color written as its lightness (`l`, `n` or `d`) and hue (`r`, `y`, `g`, `c`,
+    /// `b` or `m`), or as `ww` for white and `kk` for black.
+    fn rgb(name: &str) -> [u8; 3] {
+        let [lightness, hue] = name.as_bytes() else {
+            panic!("invalid color `{}`", name);
+        };
+        let (high, low) = match lightness {
+            b'w' => return [0xff; 3],
+            b'k' => return [0x00; 3],
+            b'l' => (0xff, 0xc0),
+            b'n' => (0xff, 0x00),
+            _ => (0xc0, 0x00),
+        };
+        let channels = match hue {
+            b'r' => [1, 0, 0],
+            b'y' => [1, 1, 0],
+            b'g' => [0, 1, 0],
+            b'c' => [0, 1, 1],
+            b'b' => [0, 0, 1],
+            _ => [1, 0, 1],
+        };
+        channels.map(|on| if on == 1 { high } else { low })
+    }
+
+    /// Draws a plain PPM image from rows of color names, with codels of `scale` pixels.
+    fn ppm(rows: &[&str], scale: usize) -> Vec<u8> {
+        let width = rows[0].split_whitespace().count() * scale;
+        let mut image = format!("P3 {} {} 255\n", width, rows.len() * scale);
+        for row in rows {
+            for _ in 0..scale {
+                for name in row.split_whitespace() {
+                    let [r, g, b] = rgb(name);
+                    image += &format!("{} {} {}  ", r, g, b).repeat(scale);
+                }
+                image.push('\n');
+            }
+        }
+        image.into_bytes()
+    }
+
+    /// Draws a program that runs through a row of blocks, starting with a red block of
+    /// `first_size` codels. Each step gives a command and the size of the block the command
+    /// leads to. The last block is shaped so that the program ends there, and should be given
+    /// a size of 0.
+    fn chain(first_size: usize, steps: &[(Command, usize)]) -> Vec<u8> {
+        let name = |(hue, lightness): (usize, usize)| {
+            ["l", "n", "d"][lightness].to_string() + ["r", "y", "g", "c", "b", "m"][hue]
+        };
+        let mut color = (0, 1);
+        let mut middle = vec![name(color); first_size - 1];
+        for &(command, size) in steps {
+            let change = (0..18)
+                .find(|&i| COMMANDS[i / 3][i % 3] == command)
+                .unwrap();
+            color = ((color.0 + change / 3) % 6, (color.1 + change % 3) % 3);
+            middle.extend(vec![name(color); size]);
+        }
+        let last = name(color);
+        let mut top = vec![name((0, 1))];
+        top.extend(vec!["kk".to_string(); middle.len() - 1]);
+        top.extend([last.clone(), "kk".to_string()]);
+        let mut bottom = vec!["kk".to_string(); middle.len()];
+        bottom.extend([last.clone(), "kk".to_string()]);
+        middle.extend([last.clone(), last]);
+        ppm(&[&top.join(" "), &middle.join(" "), &bottom.join(" ")], 1)
+    }
+
+    fn run_image(image: &[u8], input: &str, config: &Config) -> Result<String, Error> {
+        let config = Config {
+            max_steps: Some(10_000),
+            ..config.clone()
+        };
+        let mut stdout: Vec<u8> = vec![];
+        run_with_config(image, &mut input.as_bytes(), &mut stdout, &config)?;
+        Ok(String::from_utf8(stdout).unwrap())
+    }
+
+    #[test]
+    fn test_piet() {
+        use Command::*;
+        let default = Config::default();
+        // pushes the size of the first block and prints it
+        let six = [
+            "nr kk kk kk kk kk lm kk",
+            "nr nr nr nr nr dr lm lm",
+            "kk kk kk kk kk kk lm kk",
+        ];
+        assert_eq!(run_image(&ppm(&six, 1), "", &default).unwrap(), "6");
+        assert_eq!(chain(6, &[(Push, 1), (OutNumber, 0)]), ppm(&six, 1));
+
+        // the codel size is detected, or taken from the configuration
+        let scaled = ppm(&six, 3);
+        assert_eq!(Program::compile(&scaled).unwrap().codel_size(), 3);
+        assert_eq!(run_image(&scaled, "", &default).unwrap(), "6");
+        let one = Config {
+            codel_size: Some(1),
+            ..Config::default()
+        };
+        assert_eq!(run_image(&scaled, "", &one).unwrap(), "54");
+        let two = Config {
+            codel_size: Some(2),
+            ..Config::default()
+        };
+        assert!(matches!(
+            run_image(&scaled, "", &two),
+            Err(Error::InvalidCodelSize {
+                codel_size: 2,
+                width: 24,
+                height: 9
+            })
+        ));
+
+        let cases: [(usize, &[_], &str, &str); 6] = [
+            // 8 * 8 + 8
+            (
+                8,
+                &[
+                    (Push, 1),
+                    (Duplicate, 1),
+                    (Multiply, 8),
+                    (Push, 1),
+                    (Add, 1),
+                    (OutChar, 0),
+                ],
+                "",
+                "H",
+            ),
+            // rolls [2, 1, 3] once to a depth of 3
+            (
+                2,
+                &[
+                    (Push, 1),
+                    (Push, 3),
+                    (Push, 3),
+                    (Push, 1),
+                    (Push, 1),
+                    (Roll, 1),
+                    (OutNumber, 1),
+                    (OutNumber, 1),
+                    (OutNumber, 0),
+                ],
+                "",
+                "123",
+            ),
+            // -7 / 2 and -7 % 2, rounding down
+            (
+                2,
+                &[
+                    (Push, 9),
+                    (Push, 1),
+                    (Subtract, 1),
+                    (Duplicate, 2),
+                    (Push, 1),
+                    (Divide, 1),
+                    (OutNumber, 2),
+                    (Push, 1),
+                    (Mod, 1),
+                    (OutNumber, 0),
+                ],
+                "",
+                "-41",
+            ),
+            // division by zero and too few values are ignored
+            (
+                2,
+                &[
+                    (Push, 1),
+                    (Push, 1),
+                    (Not, 1),
+                    (Divide, 1),
+                    (OutNumber, 1),
+                    (OutNumber, 1),
+                    (Add, 0),
+                ],
+                "",
+                "02",
+            ),
+            (2, &[(InChar, 1), (OutChar, 0)], "é", "é"),
+            (
+                2,
+                &[(InNumber, 1), (Duplicate, 1), (Add, 1), (OutNumber, 0)],
+                "x-21",
+                "-42",
+            ),
+        ];
+        for (first_size, steps, input, expected) in cases {
+            let image = chain(first_size, steps);
+            assert_eq!(run_image(&image, input, &default).unwrap(), expected);
+        }
+        // at EOF, input and output are both ignored
+        let echo = chain(2, &[(InChar, 1), (OutChar, 0)]);
+        assert_eq!(run_image(&echo, "", &default).unwrap(), "");
+
+        // any number of turns keeps the direction pointer in range
+        for (turns, expected) in [(-1, 3), (i64::MAX, 3), (i64::MIN, 0)] {
+            let (mut dp, mut cc) = (0, 0);
+            let (mut input, mut output) = (&b""[..], vec![]);
+            let pointers = (&mut dp, &mut cc);
+            perform(
+                Pointer,
+                1,
+                &mut vec![turns],
+                pointers,
+                &mut input,
+                &mut output,
+            )
+            .unwrap();
+            assert_eq!(dp, expected);
+        }
+    }
+
+    #[test]
+    fn test_piet_white() {
+        let default = Config::default();
+        // slides through white without a command between the two red blocks
+        let slide = [
+            "nr kk kk kk kk kk dr kk",
+            "nr dr ww ww ww ny dr dr",
+            "kk kk kk kk kk kk dr kk",
+        ];
+        assert_eq!(run_image(&ppm(&slide, 1), "", &default).unwrap(), "2");
+
+        // trapped in white, or starting on black
+        for rows in [&["ww ww", "ww ww"][..], &["kk nr"]] {
+            assert_eq!(run_image(&ppm(rows, 1), "", &default).unwrap(), "");
+        }
+
+        // bouncing between two blocks forever
+        let endless = ppm(&["nr ny"], 1);
+        assert!(matches!(
+            run_image(&endless, "", &default),
+            Err(Error::LimitExceeded(_))
+        ));
+    }
+}
@@ -0,0 +1,379 @@
+//! Decoding of PNG images.
+//!
+//! All standard color types, bit depths and Adam7 interlacing are supported. Alpha is ignored,
+//! and 16-bit samples are reduced to their high byte.
+
+use super::image::Image;
+use super::inflate;
+
+/// The eight bytes every PNG file starts with.
+pub(crate) const SIGNATURE: &[u8; 8] = b"\x89PNG\r\n\x1a\n";
+
+/// Starting column and row, and column and row steps of the seven Adam7 passes.
+const ADAM7: [(usize, usize, usize, usize); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+/// Fields of the `IHDR` chunk.
+struct Header {
+    width: usize,
+    height: usize,
+    bit_depth: u8,
+    color_type: u8,
+    interlaced: bool,
+}
+
+impl Header {
+    fn channels(&self) -> usize {
+        match self.color_type {
+            2 => 3,
+            4 => 2,
+            6 => 4,
+            _ => 1,
+        }
+    }
+
+    fn bits_per_pixel(&self) -> usize {
+        self.channels() * self.bit_depth as usize
+    }
+}
+
+/// Decodes a PNG image.
+pub(crate) fn decode(bytes: &[u8]) -> Result<Image, &'static str> {
+    let mut rest = bytes.strip_prefix(SIGNATURE).ok_or("missing signature")?;
+    let mut header = None;
+    let mut palette: &[u8] = &[];
+    let mut data = vec![];
+    loop {
+        if rest.len() < 12 {
+            return Err("truncated chunk");
+        }
+        let len = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+        if rest.len() - 12 < len {
+            return Err("truncated chunk");
+        }
+        let (kind, body) = (&rest[4..8], &rest[8..8 + len]);
+        let crc = u32::from_be_bytes(rest[8 + len..12 + len].try_into().unwrap());
+        if crc != crc32(&rest[4..8 + len]) {
+            return Err("chunk checksum mismatch");
+        }
+        rest = &rest[12 + len..];
+        match kind {
+            b"IHDR" => header = Some(parse_header(body)?),
+            b"PLTE" => palette = body,
+            b"IDAT" => data.extend_from_slice(body),
+            b"IEND" => break,
+            // ancillary chunks have a lowercase first letter and can be skipped
+            _ if kind[0].is_ascii_lowercase() => (),
+            _ => return Err("unknown critical chunk"),
+        }
+    }
+    let header = header.ok_or("missing IHDR chunk")?;
+    if header.color_type == 3 && (palette.is_empty() || !palette.len().is_multiple_of(3)) {
+        return Err("missing or invalid palette");
+    }
+    let data = inflate::decompress(&data)?;
+
+    let passes: &[_] = if header.interlaced {
+        &ADAM7
+    } else {
+        &[(0, 0, 1, 1)]
+    };
+    // the size of each reduced image, checked against the data before allocating the pixels
+    let mut sizes = vec![];
+    let mut expected: usize = 0;
+    for &(x0, y0, dx, dy) in passes {
+        let width = header.width.saturating_sub(x0).div_ceil(dx);
+        let height = header.height.saturating_sub(y0).div_ceil(dy);
+        let stride = width
+            .checked_mul(header.bits_per_pixel())
+            .ok_or("image is too large")?
+            .div_ceil(8);
+        // empty passes have no filter bytes either
+        let len = match width {
+            0 => 0,
+            _ => (stride + 1)
+                .checked_mul(height)
+                .ok_or("image is too large")?,
+        };
+        expected = expected.checked_add(len).ok_or("image is too large")?;
+        sizes.push((width, height, stride, len));
+    }
+    if data.len() < expected {
+        return Err("image data is too short");
+    }
+
+    let pixels = header
+        .width
+        .checked_mul(header.height)
+        .ok_or("image is too large")?;
+    let mut image = Image {
+        width: header.width,
+        height: header.height,
+        pixels: vec![[0; 3]; pixels],
+    };
+    let mut data = &data[..];
+    for (&(x0, y0, dx, dy), &(width, height, stride, len)) in passes.iter().zip(&sizes) {
+        if width == 0 || height == 0 {
+            continue;
+        }
+        let rows = unfilter(&data[..len], stride, header.bits_per_pixel().div_ceil(8))?;
+        data = &data[len..];
+        for (row, y) in rows.chunks(stride).zip((y0..).step_by(dy)) {
+            for (column, x) in (x0..).step_by(dx).take(width).enumerate() {
+                image.pixels[y * header.width + x] = pixel(&header, palette, row, column)?;
+            }
+        }
+    }
+    Ok(image)
+}
+
+fn parse_header(body: &[u8]) -> Result<Header, &'static str> {
+    let &[w0, w1, w2, w3, h0, h1, h2, h3, bit_depth, color_type, compression, filter, interlace] =
+        body
+    else {
+        return Err("invalid IHDR chunk");
+    };
+    let header = Header {
+        width: u32::from_be_bytes([w0, w1, w2, w3]) as usize,
+        height: u32::from_be_bytes([h0, h1, h2, h3]) as usize,
+        bit_depth,
+        color_type,
+        interlaced: interlace == 1,
+    };
+    let valid_depth = match color_type {
+        0 => matches!(bit_depth, 1 | 2 | 4 | 8 | 16),
+        3 => matches!(bit_depth, 1 | 2 | 4 | 8),
+        2 | 4 | 6 => matches!(bit_depth, 8 | 16),
+        _ => false,
+    };
+    if !valid_depth || compression != 0 || filter != 0 || interlace > 1 {
+        return Err("unsupported IHDR parameters");
+    }
+    if header.width == 0 || header.height == 0 {
+        return Err("image is empty");
+    }
+    Ok(header)
+}
+
+/// Reverses the filter of each scanline, returning the rows without their filter bytes.
+fn unfilter(data: &[u8], stride: usize, bytes_per_pixel: usize) -> Result<Vec<u8>, &'static str> {
+    let mut rows = Vec::with_capacity(data.len());
+    for line in data.chunks(stride + 1) {
+        let start = rows.len();
+        for (i, &byte) in line[1..].iter().enumerate() {
+            let left = if i >= bytes_per_pixel {
+                rows[start + i - bytes_per_pixel]
+            } else {
+                0
+            };
+            let up = if start > 0 {
+                rows[start - stride + i]
+            } else {
+                0
+            };
+            let up_left = if start > 0 && i >= bytes_per_pixel {
+                rows[start - stride + i - bytes_per_pixel]
+            } else {
+                0
+            };
+            let predicted = match line[0] {
+                0 => 0,
+                1 => left,
+                2 => up,
+                3 => ((u16::from(left) + u16::from(up)) / 2) as u8,
+                4 => paeth(left, up, up_left),
+                _ => return Err("invalid filter type"),
+            };
+            rows.push(byte.wrapping_add(predicted));
+        }
+    }
+    Ok(rows)
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = i16::from(a) + i16::from(b) - i16::from(c);
+    let (pa, pb, pc) = (
+        (p - i16::from(a)).abs(),
+        (p - i16::from(b)).abs(),
+        (p - i16::from(c)).abs(),
+    );
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// Converts the pixel at `column` of an unfiltered row to 8-bit RGB.
+fn pixel(
+    header: &Header,
+    palette: &[u8],
+    row: &[u8],
+    column: usize,
+) -> Result<[u8; 3], &'static str> {
+    let sample = |index: usize| -> u8 {
+        match header.bit_depth {
+            8 => row[index],
+            16 => row[index * 2],
+            depth => {
+                let bit = index * depth as usize;
+                let value = row[bit / 8] >> (8 - depth as usize - bit % 8) & ((1 << depth) - 1);
+                if header.color_type == 3 {
+                    value
+                } else {
+                    // scales a low-depth gray level to the full range, e.g. 1 to 255 at depth 1
+                    (u16::from(value) * 255 / ((1 << depth) - 1)) as u8
+                }
+            }
+        }
+    };
+    let channels = header.channels();
+    Ok(match header.color_type {
+        0 | 4 => [sample(column * channels); 3],
+        3 => {
+            let index = sample(column) as usize * 3;
+            palette
+                .get(index..index + 3)
+                .ok_or("palette index out of range")?
+                .try_into()
+                .unwrap()
+        }
+        _ => [
+            sample(column * channels),
+            sample(column * channels + 1),
+            sample(column * channels + 2),
+        ],
+    })
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                crc >> 1 ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a PNG file from its chunks.
+    fn png(chunks: &[(&[u8; 4], &[u8])]) -> Vec<u8> {
+        let mut bytes = SIGNATURE.to_vec();
+        for (kind, body) in chunks {
+            bytes.extend_from_slice(&(body.len() as u32).to_be_bytes());
+            let start = bytes.len();
+            bytes.extend_from_slice(*kind);
+            bytes.extend_from_slice(body);
+            let crc = crc32(&bytes[start..]);
+            bytes.extend_from_slice(&crc.to_be_bytes());
+        }
+        bytes
+    }
+
+    /// Wraps data in a zlib stream of stored blocks.
+    fn zlib(data: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![0x78, 0x01];
+        let chunks: Vec<_> = data.chunks(0xffff).collect();
+        for (i, chunk) in chunks.iter().enumerate() {
+            bytes.push((i + 1 == chunks.len()) as u8);
+            bytes.extend_from_slice(&(chunk.len() as u16).to_le_bytes());
+            bytes.extend_from_slice(&(!(chunk.len() as u16)).to_le_bytes());
+            bytes.extend_from_slice(chunk);
+        }
+        bytes.extend_from_slice(&inflate::adler32(data).to_be_bytes());
+        bytes
+    }
+
+    fn ihdr(width: u32, height: u32, bit_depth: u8, color_type: u8, interlace: u8) -> Vec<u8> {
+        let mut body = width.to_be_bytes().to_vec();
+        body.extend_from_slice(&height.to_be_bytes());
+        body.extend_from_slice(&[bit_depth, color_type, 0, 0, interlace]);
+        body
+    }
+
+    #[test]
+    fn test_decode() {
+        // 2x2 RGB with Sub and Paeth filters: red, green / blue, white
+        let data = [
+            1, 255, 0, 0, 1, 255, 0, //
+            4, 1, 0, 255, 255, 0, 0,
+        ];
+        let file = png(&[
+            (b"IHDR", &ihdr(2, 2, 8, 2, 0)),
+            (b"tEXt", b"Comment\0skipped"),
+            (b"IDAT", &zlib(&data)),
+            (b"IEND", b""),
+        ]);
+        let image = decode(&file).unwrap();
+        assert_eq!((image.width, image.height), (2, 2));
+        assert_eq!(
+            image.pixels,
+            [[255, 0, 0], [0, 255, 0], [0, 0, 255], [255, 255, 255]]
+        );
+
+        // 3x1 palette at 2 bits per pixel with the Up filter
+        let file = png(&[
+            (b"IHDR", &ihdr(3, 1, 2, 3, 0)),
+            (b"PLTE", &[0, 0, 0, 255, 192, 192, 0, 192, 0]),
+            (b"IDAT", &zlib(&[2, 0b0001_1000])),
+            (b"IEND", b""),
+        ]);
+        assert_eq!(
+            decode(&file).unwrap().pixels,
+            [[0, 0, 0], [255, 192, 192], [0, 192, 0]]
+        );
+
+        // 3x3 interlaced grayscale at 1 bit per pixel: a checkerboard in passes 1, 4, 5, 6 and 7
+        let data = [
+            0,
+            0b1000_0000,
+            0,
+            0b1000_0000,
+            0,
+            0b1100_0000,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0b0100_0000,
+        ];
+        let file = png(&[
+            (b"IHDR", &ihdr(3, 3, 1, 0, 1)),
+            (b"IDAT", &zlib(&data)),
+            (b"IEND", b""),
+        ]);
+        let (w, b) = ([255; 3], [0; 3]);
+        assert_eq!(decode(&file).unwrap().pixels, [w, b, w, b, w, b, w, b, w]);
+
+        // the sizes computed from a huge header do not overflow
+        let huge = png(&[
+            (b"IHDR", &ihdr(u32::MAX, u32::MAX, 16, 6, 1)),
+            (b"IDAT", &zlib(&[0; 16])),
+            (b"IEND", b""),
+        ]);
+        assert_eq!(decode(&huge).err(), Some("image is too large"));
+
+        let mut corrupted = png(&[(b"IHDR", &ihdr(1, 1, 8, 0, 0))]);
+        *corrupted.last_mut().unwrap() ^= 1;
+        assert_eq!(decode(&corrupted).err(), Some("chunk checksum mismatch"));
+    }
+}
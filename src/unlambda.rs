@@ -0,0 +1,604 @@
+//! An implementation of [Unlambda] 2.
+//!
+//! Supports all the builtins of Unlambda 2: `s`, `k`, `i`, `v`, `d`, `c`, `e`, `.x`, `r`, `@`,
+//! `?x` and `|`. Builtin names are case-insensitive, whitespace is ignored, and `#` starts a
+//! comment up to the end of the line. Characters are read and written as UTF-8.
+//!
+//! Deeply nested programs and values are common in Unlambda, so neither the parser nor the
+//! evaluator uses Rust recursion. Evaluation runs on an explicit continuation, a linked list of
+//! frames shared between the continuations that `c` captures, and dropping a value or a
+//! continuation also walks it without recursion. How deep a program can go is only limited by
+//! memory, and by [`Config::max_steps`].
+//!
+//! [Unlambda]: http://www.madore.org/~david/programs/unlambda/
+
+use std::io::{self, BufRead, Write};
+use std::rc::Rc;
+use std::time::Duration;
+use thiserror::Error;
+
+use crate::budget::{Budget, LimitExceeded};
+use crate::input::read_char;
+use crate::{Language, Options};
+
+/// Error enum for Unlambda.
+#[derive(Error, Debug)]
+pub enum Error {
+    /// The source contains a character that is not a builtin.
+    #[error("unexpected `{character}` at line {line}, column {column}")]
+    UnexpectedCharacter {
+        /// The offending character.
+        character: char,
+        /// 1-based line number.
+        line: usize,
+        /// 1-based column, counted in characters.
+        column: usize,
+    },
+    /// The source contains something after the end of the program.
+    #[error("unexpected code after the end of the program at line {line}, column {column}")]
+    TrailingCode {
+        /// 1-based line number.
+        line: usize,
+        /// 1-based column, counted in characters.
+        column: usize,
+    },
+    /// The source ends before the program is complete, such as with a missing operand of `` ` ``.
+    #[error("unexpected end of source")]
+    UnexpectedEnd,
+    /// The program ran past [`Config::max_steps`] or [`Config::timeout`].
+    /// Contains the number of steps executed so far.
+    #[error("execution limit exceeded after {0} steps")]
+    LimitExceeded(u64),
+    /// I/O error, which may occur during I/O operations.
+    #[error("unexpected I/O error")]
+    IoError(#[from] io::Error),
+}
+
+impl From<LimitExceeded> for Error {
+    fn from(LimitExceeded(steps): LimitExceeded) -> Self {
+        Error::LimitExceeded(steps)
+    }
+}
+
+/// Settings of the Unlambda interpreter.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Config {
+    /// Maximum number of function applications to execute. `None` means unlimited.
+    pub max_steps: Option<u64>,
+    /// Wall-clock time limit of a single run. `None` means unlimited.
+    pub timeout: Option<Duration>,
+}
+
+/// Unlambda interpreter.
+pub fn run<I: BufRead, O: Write>(source: &str, input: &mut I, output: &mut O) -> Result<(), Error> {
+    run_with_config(source, input, output, &Config::default())
+}
+
+/// Unlambda interpreter with a custom configuration.
+pub fn run_with_config<I: BufRead, O: Write>(
+    source: &str,
+    input: &mut I,
+    output: &mut O,
+    config: &Config,
+) -> Result<(), Error> {
+    Program::compile_with_config(source, config)?.run(input, output)
+}
+
+/// The [`Language`] implementation for Unlambda.
+///
+/// Compiles with the default [`Config`], except for the limits taken from [`Options`].
+#[derive(Debug, Clone, Copy)]
+pub struct Unlambda;
+
+impl Language for Unlambda {
+    fn name(&self) -> &'static str {
+        "unlambda"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["unl"]
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["unl"]
+    }
+
+    fn compile(
+        &self,
+        source: &str,
+        options: &Options,
+    ) -> Result<Box<dyn crate::Program>, crate::Error> {
+        let config = Config {
+            max_steps: options.max_steps,
+            timeout: options.timeout,
+        };
+        let program = Program::compile_with_config(source, &config)
+            .map_err(|error| crate::Error::compile(self.name(), error))?;
+        Ok(Box::new(program))
+    }
+}
+
+/// A builtin function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Builtin {
+    S,
+    K,
+    I,
+    V,
+    D,
+    C,
+    E,
+    /// `.x`
+    Print(char),
+    /// `r`
+    Newline,
+    /// `@`
+    Read,
+    /// `?x`
+    Compare(char),
+    /// `|`
+    Reprint,
+}
+
+/// A node of the parsed program.
+#[derive(Debug, Clone, Copy)]
+enum Node {
+    Builtin(Builtin),
+    /// `` ` ``, with the indices of the operator and the operand.
+    Apply(usize, usize),
+}
+
+/// A parsed Unlambda program.
+///
+/// The expression tree is stored in a flat list, so that it can be of any depth.
+#[derive(Debug, Clone)]
+pub struct Program {
+    nodes: Vec<Node>,
+    root: usize,
+    config: Config,
+}
+
+impl Program {
+    /// Parses the source code with the default [`Config`].
+    pub fn compile(source: &str) -> Result<Self, Error> {
+        Self::compile_with_config(source, &Config::default())
+    }
+
+    /// Parses the source code with a custom configuration.
+    pub fn compile_with_config(source: &str, config: &Config) -> Result<Self, Error> {
+        let mut nodes = vec![];
+        // the operator of each open `` ` ``, once it is parsed
+        let mut open: Vec<Option<usize>> = vec![];
+        let mut root = None;
+        let mut chars = source.chars();
+        let (mut line, mut column) = (1, 0);
+        let mut next = |chars: &mut std::str::Chars<'_>| {
+            let c = chars.next()?;
+            if c == '\n' {
+                (line, column) = (line + 1, 0);
+            } else {
+                column += 1;
+            }
+            Some((c, line, column))
+        };
+        while let Some((c, line, column)) = next(&mut chars) {
+            let builtin = match c.to_ascii_lowercase() {
+                c if c.is_whitespace() => continue,
+                '#' => {
+                    while next(&mut chars).is_some_and(|(c, ..)| c != '\n') {}
+                    continue;
+                }
+                _ if root.is_some() => return Err(Error::TrailingCode { line, column }),
+                '`' => {
+                    open.push(None);
+                    continue;
+                }
+                's' => Builtin::S,
+                'k' => Builtin::K,
+                'i' => Builtin::I,
+                'v' => Builtin::V,
+                'd' => Builtin::D,
+                'c' => Builtin::C,
+                'e' => Builtin::E,
+                'r' => Builtin::Newline,
+                '@' => Builtin::Read,
+                '|' => Builtin::Reprint,
+                '.' | '?' => {
+                    let (x, ..) = next(&mut chars).ok_or(Error::UnexpectedEnd)?;
+                    if c == '.' {
+                        Builtin::Print(x)
+                    } else {
+                        Builtin::Compare(x)
+                    }
+                }
+                _ => {
+                    return Err(Error::UnexpectedCharacter {
+                        character: c,
+                        line,
+                        column,
+                    })
+                }
+            };
+            nodes.push(Node::Builtin(builtin));
+            let mut node = nodes.len() - 1;
+            // complete every application that this node is the last operand of
+            loop {
+                match open.last_mut() {
+                    None => {
+                        root = Some(node);
+                        break;
+                    }
+                    Some(operator @ None) => {
+                        *operator = Some(node);
+                        break;
+                    }
+                    Some(Some(operator)) => {
+                        nodes.push(Node::Apply(*operator, node));
+                        node = nodes.len() - 1;
+                        open.pop();
+                    }
+                }
+            }
+        }
+        if !open.is_empty() {
+            return Err(Error::UnexpectedEnd);
+        }
+        Ok(Self {
+            nodes,
+            root: root.ok_or(Error::UnexpectedEnd)?,
+            config: config.clone(),
+        })
+    }
+
+    /// The configuration the program was compiled with.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Runs the program from the start.
+    pub fn run<I: BufRead, O: Write>(&self, input: &mut I, output: &mut O) -> Result<(), Error> {
+        let result = self.execute(input, output);
+        output.flush()?;
+        result
+    }
+
+    fn execute<I: BufRead, O: Write>(&self, input: &mut I, output: &mut O) -> Result<(), Error> {
+        let mut budget = Budget::new(self.config.max_steps, self.config.timeout);
+        // values of the builtins in the source, created once per run
+        let builtins: Vec<Option<Rc<Value>>> = self
+            .nodes
+            .iter()
+            .map(|node| match node {
+                Node::Builtin(builtin) => Some(Value::new(Function::Builtin(*builtin))),
+                Node::Apply(..) => None,
+            })
+            .collect();
+        let mut current_char: Option<char> = None;
+        let mut continuation: Option<Rc<Frame>> = None;
+        let push = |continuation: &mut Option<Rc<Frame>>, step: Step| {
+            let parent = continuation.take();
+            *continuation = Some(Rc::new(Frame { step, parent }));
+        };
+        let mut state = State::Evaluate(self.root);
+        loop {
+            state = match state {
+                State::Evaluate(node) => match self.nodes[node] {
+                    Node::Builtin(_) => State::Return(builtins[node].clone().expect("a builtin")),
+                    Node::Apply(operator, operand) => {
+                        push(&mut continuation, Step::EvaluateOperand(operand));
+                        State::Evaluate(operator)
+                    }
+                },
+                State::Return(value) => {
+                    let Some(frame) = continuation.take() else {
+                        return Ok(());
+                    };
+                    let step = match Rc::try_unwrap(frame) {
+                        Ok(mut frame) => {
+                            continuation = frame.parent.take();
+                            std::mem::replace(&mut frame.step, Step::EvaluateOperand(0))
+                        }
+                        Err(frame) => {
+                            continuation = frame.parent.clone();
+                            frame.step.clone()
+                        }
+                    };
+                    match step {
+                        // `d` delays the evaluation of its operand
+                        Step::EvaluateOperand(operand)
+                            if matches!(value.function, Function::Builtin(Builtin::D)) =>
+                        {
+                            State::Return(Value::new(Function::Promise(operand)))
+                        }
+                        Step::EvaluateOperand(operand) => {
+                            push(&mut continuation, Step::Apply(value));
+                            State::Evaluate(operand)
+                        }
+                        Step::Apply(function) => State::Apply(function, value),
+                        Step::ApplyS(y, z) => {
+                            push(&mut continuation, Step::Apply(value));
+                            State::Apply(y, z)
+                        }
+                        Step::ApplyTo(argument) => State::Apply(value, argument),
+                    }
+                }
+                State::Apply(function, argument) => {
+                    budget.spend(1)?;
+                    match &function.function {
+                        Function::Builtin(builtin) => match *builtin {
+                            Builtin::S => State::Return(Value::new(Function::S1(argument))),
+                            Builtin::K => State::Return(Value::new(Function::K1(argument))),
+                            Builtin::I => State::Return(argument),
+                            Builtin::V => State::Return(function),
+                            Builtin::D => State::Return(Value::new(Function::D1(argument))),
+                            Builtin::C => {
+                                let captured =
+                                    Value::new(Function::Continuation(continuation.clone()));
+                                State::Apply(argument, captured)
+                            }
+                            Builtin::E => return Ok(()),
+                            Builtin::Print(c) => {
+                                write!(output, "{}", c)?;
+                                State::Return(argument)
+                            }
+                            Builtin::Newline => {
+                                writeln!(output)?;
+                                State::Return(argument)
+                            }
+                            Builtin::Read => {
+                                current_char = read_char(input)?;
+                                let result = match current_char {
+                                    Some(_) => Builtin::I,
+                                    None => Builtin::V,
+                                };
+                                State::Apply(argument, Value::new(Function::Builtin(result)))
+                            }
+                            Builtin::Compare(c) => {
+                                let result = match current_char == Some(c) {
+                                    true => Builtin::I,
+                                    false => Builtin::V,
+                                };
+                                State::Apply(argument, Value::new(Function::Builtin(result)))
+                            }
+                            Builtin::Reprint => {
+                                let result = match current_char {
+                                    Some(c) => Builtin::Print(c),
+                                    None => Builtin::V,
+                                };
+                                State::Apply(argument, Value::new(Function::Builtin(result)))
+                            }
+                        },
+                        Function::K1(x) => State::Return(x.clone()),
+                        Function::S1(x) => {
+                            State::Return(Value::new(Function::S2(x.clone(), argument)))
+                        }
+                        Function::S2(x, y) => {
+                            push(&mut continuation, Step::ApplyS(y.clone(), argument.clone()));
+                            State::Apply(x.clone(), argument)
+                        }
+                        Function::Promise(operand) => {
+                            push(&mut continuation, Step::ApplyTo(argument));
+                            State::Evaluate(*operand)
+                        }
+                        Function::D1(x) => State::Apply(x.clone(), argument),
+                        Function::Continuation(target) => {
+                            continuation = target.clone();
+                            State::Return(argument)
+                        }
+                    }
+                }
+            };
+        }
+    }
+}
+
+impl crate::Program for Program {
+    fn run(
+        &self,
+        mut input: &mut dyn BufRead,
+        mut output: &mut dyn Write,
+    ) -> Result<(), crate::Error> {
+        Program::run(self, &mut input, &mut output)
+            .map_err(|error| crate::Error::runtime(Unlambda.name(), error))
+    }
+}
+
+/// What the evaluator does next.
+enum State {
+    /// Evaluate a node of the program.
+    Evaluate(usize),
+    /// Pass a value to the continuation.
+    Return(Rc<Value>),
+    /// Apply a function to an argument.
+    Apply(Rc<Value>, Rc<Value>),
+}
+
+/// A function, which is the only kind of value in Unlambda.
+#[derive(Debug)]
+struct Value {
+    function: Function,
+}
+
+impl Value {
+    fn new(function: Function) -> Rc<Self> {
+        Rc::new(Self { function })
+    }
+}
+
+#[derive(Debug)]
+enum Function {
+    Builtin(Builtin),
+    /// `` `kx ``
+    K1(Rc<Value>),
+    /// `` `sx ``
+    S1(Rc<Value>),
+    /// ``` ``sxy ```
+    S2(Rc<Value>, Rc<Value>),
+    /// `` `dF `` where `F` has not been evaluated, with the index of `F`.
+    Promise(usize),
+    /// `d` applied to a value, for example by `s`.
+    D1(Rc<Value>),
+    /// A continuation captured by `c`. `None` is the end of the program.
+    Continuation(Option<Rc<Frame>>),
+}
+
+/// A frame of a continuation, which is what to do with the value of the current computation.
+#[derive(Debug)]
+struct Frame {
+    step: Step,
+    parent: Option<Rc<Frame>>,
+}
+
+#[derive(Debug, Clone)]
+enum Step {
+    /// The value is an operator; evaluate the operand with this index.
+    EvaluateOperand(usize),
+    /// The value is an argument to apply this function to.
+    Apply(Rc<Value>),
+    /// The value is `` `xz `` for ```` ```sxyz ````; compute `` `yz `` and apply the value to it.
+    ApplyS(Rc<Value>, Rc<Value>),
+    /// The value is the forced promise; apply it to this argument.
+    ApplyTo(Rc<Value>),
+}
+
+/// Something to drop without recursion.
+enum Garbage {
+    Value(Rc<Value>),
+    Frame(Rc<Frame>),
+}
+
+impl Garbage {
+    /// Drops everything reachable only from the given garbage, one value at a time.
+    fn collect(mut garbage: Vec<Garbage>) {
+        while let Some(item) = garbage.pop() {
+            match item {
+                Garbage::Value(value) => {
+                    if let Ok(mut value) = Rc::try_unwrap(value) {
+                        value.take_children(&mut garbage);
+                    }
+                }
+                Garbage::Frame(frame) => {
+                    if let Ok(mut frame) = Rc::try_unwrap(frame) {
+                        frame.take_children(&mut garbage);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Value {
+    fn take_children(&mut self, garbage: &mut Vec<Garbage>) {
+        match std::mem::replace(&mut self.function, Function::Builtin(Builtin::I)) {
+            Function::K1(x) | Function::S1(x) | Function::D1(x) => garbage.push(Garbage::Value(x)),
+            Function::S2(x, y) => garbage.extend([Garbage::Value(x), Garbage::Value(y)]),
+            Function::Continuation(Some(frame)) => garbage.push(Garbage::Frame(frame)),
+            Function::Builtin(_) | Function::Promise(_) | Function::Continuation(None) => (),
+        }
+    }
+}
+
+impl Drop for Value {
+    fn drop(&mut self) {
+        let mut garbage = vec![];
+        self.take_children(&mut garbage);
+        Garbage::collect(garbage);
+    }
+}
+
+impl Frame {
+    fn take_children(&mut self, garbage: &mut Vec<Garbage>) {
+        if let Some(parent) = self.parent.take() {
+            garbage.push(Garbage::Frame(parent));
+        }
+        match std::mem::replace(&mut self.step, Step::EvaluateOperand(0)) {
+            Step::Apply(x) | Step::ApplyTo(x) => garbage.push(Garbage::Value(x)),
+            Step::ApplyS(x, y) => garbage.extend([Garbage::Value(x), Garbage::Value(y)]),
+            Step::EvaluateOperand(_) => (),
+        }
+    }
+}
+
+impl Drop for Frame {
+    fn drop(&mut self) {
+        let mut garbage = vec![];
+        self.take_children(&mut garbage);
+        Garbage::collect(garbage);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_str(source: &str, input: &str) -> Result<String, Error> {
+        let config = Config {
+            max_steps: Some(10_000_000),
+            ..Config::default()
+        };
+        let mut stdout: Vec<u8> = vec![];
+        run_with_config(source, &mut input.as_bytes(), &mut stdout, &config)?;
+        Ok(String::from_utf8(stdout).unwrap())
+    }
+
+    fn output(source: &str, input: &str) -> String {
+        run_str(source, input).unwrap()
+    }
+
+    #[test]
+    fn test_unlambda() {
+        assert_eq!(
+            output("`r```````````.H.e.l.l.o. .w.o.r.l.di # prints hello", ""),
+            "Hello world\n"
+        );
+        // `s` and `k`: ```skk is the identity
+        assert_eq!(output("````skk.xi", ""), "x");
+        // `d` delays its operand until the promise is applied, and `v` swallows everything
+        assert_eq!(output("`d`.xi", ""), "");
+        assert_eq!(output("``d`.xii", ""), "x");
+        assert_eq!(output("``v.xi", ""), "");
+        // `c` returns to the operator position, printing x twice
+        assert_eq!(output("``ci`.xi", ""), "xx");
+        // `e` ends the program, even in the middle of an evaluation
+        assert_eq!(output("`.a`ei", ""), "");
+        assert_eq!(output("``ei`.xi", ""), "");
+        // input with `@`, `?x` and `|`
+        assert_eq!(output("``@|i", "zy"), "z");
+        assert_eq!(output("``@|i", ""), "");
+        assert_eq!(output("```@?z.yi", "z"), "y");
+        assert_eq!(output("```@?z.yi", "a"), "");
+        assert_eq!(output("``@|i", "é"), "é");
+
+        // deep nesting on both sides of `` ` `` does not overflow the stack
+        let n = 200_000;
+        let left = format!("{}{}", "`".repeat(n), "i".repeat(n + 1));
+        assert_eq!(output(&left, ""), "");
+        let right = format!("{}`.xi", "`i".repeat(n));
+        assert_eq!(output(&right, ""), "x");
+        // a long chain of values built at run time, dropped at the end
+        let chain = format!("{}i", "`s".repeat(n));
+        assert_eq!(output(&chain, ""), "");
+    }
+
+    #[test]
+    fn test_unlambda_errors() {
+        assert!(matches!(
+            run_str("`.a\n `ix", ""),
+            Err(Error::UnexpectedCharacter {
+                character: 'x',
+                line: 2,
+                column: 4
+            })
+        ));
+        assert!(matches!(
+            run_str("`ii #comment\n `ii", ""),
+            Err(Error::TrailingCode { line: 2, column: 2 })
+        ));
+        assert!(matches!(run_str("`i", ""), Err(Error::UnexpectedEnd)));
+        assert!(matches!(run_str("`i.", ""), Err(Error::UnexpectedEnd)));
+        assert!(matches!(
+            run_str("```sii``sii", ""),
+            Err(Error::LimitExceeded(_))
+        ));
+    }
+}
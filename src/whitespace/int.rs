@@ -0,0 +1,382 @@
+//! Arbitrary-precision integers, just enough for Whitespace.
+//!
+//! Values that fit in an `i64` are stored inline and computed with checked `i64` arithmetic,
+//! falling back to a sign and a magnitude of 32-bit limbs only when a result overflows.
+
+use std::cmp::Ordering;
+use std::fmt;
+
+/// An arbitrary-precision integer.
+///
+/// The representation is canonical, so that the derived `Eq` and `Hash` compare values:
+/// `Big` is only used for values outside the range of `i64`, and its magnitude has no
+/// leading zero limbs.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum Int {
+    Small(i64),
+    Big {
+        negative: bool,
+        /// Little-endian limbs.
+        magnitude: Vec<u32>,
+    },
+}
+
+impl Int {
+    /// Builds an integer from its sign and its binary digits, most significant first.
+    pub(crate) fn from_bits(negative: bool, bits: impl IntoIterator<Item = bool>) -> Self {
+        let mut magnitude = vec![];
+        for bit in bits {
+            mul_add_small(&mut magnitude, 2, bit as u32);
+        }
+        Self::from_parts(negative, magnitude)
+    }
+
+    /// Parses a decimal integer with an optional sign.
+    pub(crate) fn parse(text: &str) -> Option<Self> {
+        let (negative, digits) = match text.as_bytes().first()? {
+            b'-' => (true, &text[1..]),
+            b'+' => (false, &text[1..]),
+            _ => (false, text),
+        };
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let mut magnitude = vec![];
+        for byte in digits.bytes() {
+            mul_add_small(&mut magnitude, 10, (byte - b'0') as u32);
+        }
+        Some(Self::from_parts(negative, magnitude))
+    }
+
+    fn from_parts(negative: bool, mut magnitude: Vec<u32>) -> Self {
+        trim(&mut magnitude);
+        if magnitude.len() <= 2 {
+            let value = magnitude
+                .iter()
+                .rev()
+                .fold(0u64, |value, &limb| value << 32 | limb as u64);
+            if !negative && value <= i64::MAX as u64 {
+                return Int::Small(value as i64);
+            }
+            if negative && value <= 1 << 63 {
+                return Int::Small((value as i64).wrapping_neg());
+            }
+        }
+        Int::Big {
+            negative,
+            magnitude,
+        }
+    }
+
+    /// The sign and the magnitude, whatever the representation.
+    fn parts(&self) -> (bool, Vec<u32>) {
+        match self {
+            Int::Small(value) => {
+                let abs = value.unsigned_abs();
+                let mut magnitude = vec![abs as u32, (abs >> 32) as u32];
+                trim(&mut magnitude);
+                (*value < 0, magnitude)
+            }
+            Int::Big {
+                negative,
+                magnitude,
+            } => (*negative, magnitude.clone()),
+        }
+    }
+
+    pub(crate) fn is_zero(&self) -> bool {
+        *self == Int::Small(0)
+    }
+
+    pub(crate) fn is_negative(&self) -> bool {
+        match self {
+            Int::Small(value) => *value < 0,
+            Int::Big { negative, .. } => *negative,
+        }
+    }
+
+    /// The value as a `usize`, if it is in range.
+    pub(crate) fn to_usize(&self) -> Option<usize> {
+        match self {
+            Int::Small(value) => usize::try_from(*value).ok(),
+            Int::Big { .. } => None,
+        }
+    }
+
+    /// The Unicode character with this value, if there is one.
+    pub(crate) fn to_char(&self) -> Option<char> {
+        match self {
+            Int::Small(value) => char::from_u32(u32::try_from(*value).ok()?),
+            Int::Big { .. } => None,
+        }
+    }
+
+    pub(crate) fn add(&self, other: &Int) -> Int {
+        if let (Int::Small(a), Int::Small(b)) = (self, other) {
+            if let Some(sum) = a.checked_add(*b) {
+                return Int::Small(sum);
+            }
+        }
+        let (a_negative, a) = self.parts();
+        let (b_negative, b) = other.parts();
+        if a_negative == b_negative {
+            return Self::from_parts(a_negative, add_magnitudes(&a, &b));
+        }
+        match compare_magnitudes(&a, &b) {
+            Ordering::Less => Self::from_parts(b_negative, sub_magnitudes(&b, &a)),
+            _ => Self::from_parts(a_negative, sub_magnitudes(&a, &b)),
+        }
+    }
+
+    pub(crate) fn neg(&self) -> Int {
+        match self {
+            Int::Small(value) => match value.checked_neg() {
+                Some(value) => Int::Small(value),
+                None => Self::from_parts(false, self.parts().1),
+            },
+            Int::Big {
+                negative,
+                magnitude,
+            } => Self::from_parts(!negative, magnitude.clone()),
+        }
+    }
+
+    pub(crate) fn sub(&self, other: &Int) -> Int {
+        if let (Int::Small(a), Int::Small(b)) = (self, other) {
+            if let Some(difference) = a.checked_sub(*b) {
+                return Int::Small(difference);
+            }
+        }
+        self.add(&other.neg())
+    }
+
+    pub(crate) fn mul(&self, other: &Int) -> Int {
+        if let (Int::Small(a), Int::Small(b)) = (self, other) {
+            if let Some(product) = a.checked_mul(*b) {
+                return Int::Small(product);
+            }
+        }
+        let (a_negative, a) = self.parts();
+        let (b_negative, b) = other.parts();
+        Self::from_parts(a_negative != b_negative, mul_magnitudes(&a, &b))
+    }
+
+    /// Division rounding towards negative infinity, and the remainder with the sign of the
+    /// divisor. Returns `None` if the divisor is zero.
+    pub(crate) fn div_mod(&self, other: &Int) -> Option<(Int, Int)> {
+        if other.is_zero() {
+            return None;
+        }
+        if let (Int::Small(a), Int::Small(b)) = (self, other) {
+            if let (Some(q), Some(r)) = (a.checked_div(*b), a.checked_rem(*b)) {
+                return Some(if r != 0 && (r < 0) != (*b < 0) {
+                    (Int::Small(q - 1), Int::Small(r + b))
+                } else {
+                    (Int::Small(q), Int::Small(r))
+                });
+            }
+        }
+        let (a_negative, a) = self.parts();
+        let (b_negative, b) = other.parts();
+        let (q, r) = div_rem_magnitudes(&a, &b);
+        let q = Self::from_parts(a_negative != b_negative, q);
+        let r = Self::from_parts(a_negative, r);
+        Some(if !r.is_zero() && a_negative != b_negative {
+            (q.sub(&Int::Small(1)), r.add(other))
+        } else {
+            (q, r)
+        })
+    }
+}
+
+impl fmt::Display for Int {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (negative, mut magnitude) = match self {
+            Int::Small(value) => return write!(f, "{}", value),
+            Int::Big {
+                negative,
+                magnitude,
+            } => (*negative, magnitude.clone()),
+        };
+        // base 10^9 digits, least significant first
+        let mut chunks = vec![];
+        while !magnitude.is_empty() {
+            chunks.push(div_rem_small(&mut magnitude, 1_000_000_000));
+        }
+        if negative {
+            write!(f, "-")?;
+        }
+        let mut chunks = chunks.iter().rev();
+        if let Some(first) = chunks.next() {
+            write!(f, "{}", first)?;
+        }
+        for chunk in chunks {
+            write!(f, "{:09}", chunk)?;
+        }
+        Ok(())
+    }
+}
+
+fn trim(magnitude: &mut Vec<u32>) {
+    while magnitude.last() == Some(&0) {
+        magnitude.pop();
+    }
+}
+
+/// `magnitude = magnitude * factor + addend`
+fn mul_add_small(magnitude: &mut Vec<u32>, factor: u32, addend: u32) {
+    let mut carry = addend as u64;
+    for limb in magnitude.iter_mut() {
+        let value = *limb as u64 * factor as u64 + carry;
+        *limb = value as u32;
+        carry = value >> 32;
+    }
+    if carry != 0 {
+        magnitude.push(carry as u32);
+    }
+}
+
+/// Divides in place and returns the remainder.
+fn div_rem_small(magnitude: &mut Vec<u32>, divisor: u32) -> u32 {
+    let mut remainder = 0u64;
+    for limb in magnitude.iter_mut().rev() {
+        let value = remainder << 32 | *limb as u64;
+        *limb = (value / divisor as u64) as u32;
+        remainder = value % divisor as u64;
+    }
+    trim(magnitude);
+    remainder as u32
+}
+
+fn compare_magnitudes(a: &[u32], b: &[u32]) -> Ordering {
+    a.len()
+        .cmp(&b.len())
+        .then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let (long, short) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    let mut result = Vec::with_capacity(long.len() + 1);
+    let mut carry = 0u64;
+    for (i, &limb) in long.iter().enumerate() {
+        let value = limb as u64 + short.get(i).copied().unwrap_or(0) as u64 + carry;
+        result.push(value as u32);
+        carry = value >> 32;
+    }
+    if carry != 0 {
+        result.push(carry as u32);
+    }
+    result
+}
+
+/// `a - b`, where `a >= b`.
+fn sub_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for (i, &limb) in a.iter().enumerate() {
+        let mut value = limb as i64 - b.get(i).copied().unwrap_or(0) as i64 - borrow;
+        borrow = (value < 0) as i64;
+        if value < 0 {
+            value += 1 << 32;
+        }
+        result.push(value as u32);
+    }
+    trim(&mut result);
+    result
+}
+
+fn mul_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = vec![0u32; a.len() + b.len()];
+    for (i, &x) in a.iter().enumerate() {
+        let mut carry = 0u64;
+        for (j, &y) in b.iter().enumerate() {
+            let value = result[i + j] as u64 + x as u64 * y as u64 + carry;
+            result[i + j] = value as u32;
+            carry = value >> 32;
+        }
+        result[i + b.len()] = carry as u32;
+    }
+    trim(&mut result);
+    result
+}
+
+/// Truncating division of magnitudes, where `b` is not zero.
+fn div_rem_magnitudes(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if let [divisor] = b {
+        let mut quotient = a.to_vec();
+        let remainder = div_rem_small(&mut quotient, *divisor);
+        let mut remainder = vec![remainder];
+        trim(&mut remainder);
+        return (quotient, remainder);
+    }
+    // binary long division, which is plenty for the numbers Whitespace programs use
+    let mut quotient = vec![0u32; a.len()];
+    let mut remainder: Vec<u32> = vec![];
+    for i in (0..a.len() * 32).rev() {
+        let bit = a[i / 32] >> (i % 32) & 1;
+        mul_add_small(&mut remainder, 2, bit);
+        if compare_magnitudes(&remainder, b) != Ordering::Less {
+            remainder = sub_magnitudes(&remainder, b);
+            quotient[i / 32] |= 1 << (i % 32);
+        }
+    }
+    trim(&mut quotient);
+    (quotient, remainder)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn int(text: &str) -> Int {
+        Int::parse(text).unwrap()
+    }
+
+    #[test]
+    fn test_int() {
+        let big = int("123456789012345678901234567890");
+        assert_eq!(big.to_string(), "123456789012345678901234567890");
+        assert_eq!(int("-9223372036854775808"), Int::Small(i64::MIN));
+        assert!(matches!(int("9223372036854775808"), Int::Big { .. }));
+        assert_eq!(int("+42"), Int::Small(42));
+        assert_eq!(Int::parse("4-2"), None);
+        assert_eq!(Int::from_bits(true, [true, false, true]), Int::Small(-5));
+
+        // overflowing and coming back into the range of i64
+        let max = Int::Small(i64::MAX);
+        let sum = max.add(&Int::Small(1));
+        assert_eq!(sum.to_string(), "9223372036854775808");
+        assert_eq!(sum.sub(&Int::Small(1)), max);
+        assert_eq!(
+            Int::Small(i64::MIN).neg().to_string(),
+            "9223372036854775808"
+        );
+        assert_eq!(
+            big.mul(&big).to_string(),
+            "15241578753238836750495351562536198787501905199875019052100"
+        );
+        assert_eq!(
+            big.mul(&big).div_mod(&big),
+            Some((big.clone(), Int::Small(0)))
+        );
+        assert_eq!(big.sub(&big.add(&Int::Small(1))), Int::Small(-1));
+
+        // division rounds down, and the remainder has the sign of the divisor
+        for (a, b, q, r) in [
+            ("7", "2", "3", "1"),
+            ("-7", "2", "-4", "1"),
+            ("7", "-2", "-4", "-1"),
+            ("-7", "-2", "3", "-1"),
+            ("-9223372036854775808", "-1", "9223372036854775808", "0"),
+            (
+                "-123456789012345678901234567890",
+                "1000000000000",
+                "-123456789012345679",
+                "98765432110",
+            ),
+        ] {
+            assert_eq!(int(a).div_mod(&int(b)), Some((int(q), int(r))));
+        }
+        assert_eq!(big.div_mod(&Int::Small(0)), None);
+    }
+}
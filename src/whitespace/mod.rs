@@ -0,0 +1,684 @@
+//! An implementation of [Whitespace] 0.3.
+//!
+//! Only spaces, tabs and line feeds are significant; every other character is a comment.
+//! Values on the stack and in the heap are integers of arbitrary precision, and heap
+//! addresses may be any integer, including negative ones.
+//!
+//! The source is compiled into bytecode where labels are resolved to instruction indices, so
+//! undefined and duplicate labels are reported before the program starts. Errors that stop
+//! the program while running report the line and column of the instruction that caused them.
+//!
+//! The parts left undefined by the specification behave as follows:
+//!
+//! - A number or a label may have no digits at all, and a number may even have no sign. A
+//!   number without digits is 0.
+//! - Retrieving a heap address that was never stored to gives 0.
+//! - `slide` with a count larger than the stack keeps only the top value, and a negative
+//!   count keeps the stack as it is.
+//! - Division rounds towards negative infinity, and modulo has the sign of the divisor.
+//! - Characters are read and written as UTF-8. Reading a character at EOF stores -1, while
+//!   reading a number at EOF stops the program with an error. A number is read from a whole
+//!   line, which must contain a decimal integer and nothing else besides whitespace.
+//! - Running past the last instruction ends the program like `end`.
+//!
+//! [Whitespace]: https://esolangs.org/wiki/Whitespace
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::time::Duration;
+use thiserror::Error;
+
+use crate::budget::{Budget, LimitExceeded};
+use crate::input::read_char;
+use crate::{Language, Options};
+
+mod int;
+
+use int::Int;
+
+/// Error enum for Whitespace.
+#[derive(Error, Debug)]
+pub enum Error {
+    /// The source ends in the middle of an instruction.
+    #[error("incomplete instruction at {0}")]
+    IncompleteInstruction(Position),
+    /// The source contains a sequence of whitespace that is not an instruction.
+    #[error("invalid instruction at {0}")]
+    InvalidInstruction(Position),
+    /// A jump or call refers to a label that is not marked anywhere.
+    #[error("undefined label `{label}` at {position}")]
+    UndefinedLabel {
+        /// The label, written with `S` for space and `T` for tab.
+        label: String,
+        /// Position of the jump or call.
+        position: Position,
+    },
+    /// The same label is marked twice.
+    #[error("label `{label}` marked again at {position}")]
+    DuplicateLabel {
+        /// The label, written with `S` for space and `T` for tab.
+        label: String,
+        /// Position of the second mark.
+        position: Position,
+    },
+    /// An instruction needed more values than the stack had, or `copy` referred to a value
+    /// that is not on the stack.
+    #[error("stack underflow at {0}")]
+    StackUnderflow(Position),
+    /// `return` was executed outside of a subroutine.
+    #[error("return outside of a subroutine at {0}")]
+    CallStackUnderflow(Position),
+    /// Division or modulo by zero.
+    #[error("division by zero at {0}")]
+    DivisionByZero(Position),
+    /// A value to be printed as a character is not a Unicode scalar value.
+    #[error("{value} is not a character, at {position}")]
+    InvalidCharacter {
+        /// The value, in decimal.
+        value: String,
+        /// Position of the instruction.
+        position: Position,
+    },
+    /// The line read as a number is not a decimal integer.
+    #[error("input {line:?} is not a number, at {position}")]
+    InvalidNumber {
+        /// The line, without the line terminator.
+        line: String,
+        /// Position of the instruction.
+        position: Position,
+    },
+    /// A number was read at EOF.
+    #[error("unexpected end of input at {0}")]
+    UnexpectedEof(Position),
+    /// The program ran past [`Config::max_steps`] or [`Config::timeout`].
+    /// Contains the number of steps executed so far.
+    #[error("execution limit exceeded after {0} steps")]
+    LimitExceeded(u64),
+    /// I/O error, which may occur during I/O operations.
+    #[error("unexpected I/O error")]
+    IoError(#[from] io::Error),
+}
+
+impl From<LimitExceeded> for Error {
+    fn from(LimitExceeded(steps): LimitExceeded) -> Self {
+        Error::LimitExceeded(steps)
+    }
+}
+
+/// Location of an instruction in the source, which is where its first character is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    /// 1-based line number.
+    pub line: usize,
+    /// 1-based column, counted in characters.
+    pub column: usize,
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+/// Settings of the Whitespace interpreter.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Config {
+    /// Maximum number of instructions to execute. `None` means unlimited.
+    pub max_steps: Option<u64>,
+    /// Wall-clock time limit of a single run. `None` means unlimited.
+    pub timeout: Option<Duration>,
+}
+
+/// Whitespace interpreter.
+pub fn run<I: BufRead, O: Write>(source: &str, input: &mut I, output: &mut O) -> Result<(), Error> {
+    run_with_config(source, input, output, &Config::default())
+}
+
+/// Whitespace interpreter with a custom configuration.
+pub fn run_with_config<I: BufRead, O: Write>(
+    source: &str,
+    input: &mut I,
+    output: &mut O,
+    config: &Config,
+) -> Result<(), Error> {
+    Program::compile_with_config(source, config)?.run(input, output)
+}
+
+/// The [`Language`] implementation for Whitespace.
+///
+/// Compiles with the default [`Config`], except for the limits taken from [`Options`].
+#[derive(Debug, Clone, Copy)]
+pub struct Whitespace;
+
+impl Language for Whitespace {
+    fn name(&self) -> &'static str {
+        "whitespace"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["ws"]
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["ws"]
+    }
+
+    fn compile(
+        &self,
+        source: &str,
+        options: &Options,
+    ) -> Result<Box<dyn crate::Program>, crate::Error> {
+        let config = Config {
+            max_steps: options.max_steps,
+            timeout: options.timeout,
+        };
+        let program = Program::compile_with_config(source, &config)
+            .map_err(|error| crate::Error::compile(self.name(), error))?;
+        Ok(Box::new(program))
+    }
+}
+
+/// A single instruction, with labels resolved to indices of instructions.
+#[derive(Debug, Clone)]
+enum Op {
+    Push(Int),
+    Dup,
+    /// Copies the value this many places below the top. `usize::MAX` if out of range.
+    Copy(usize),
+    Swap,
+    Discard,
+    /// Removes this many values below the top, saturating at `usize::MAX`.
+    Slide(usize),
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Store,
+    Retrieve,
+    Call(usize),
+    Jump(usize),
+    JumpZero(usize),
+    JumpNegative(usize),
+    Return,
+    End,
+    PrintChar,
+    PrintNumber,
+    ReadChar,
+    ReadNumber,
+}
+
+/// A Whitespace program compiled into bytecode.
+#[derive(Debug, Clone)]
+pub struct Program {
+    ops: Vec<Op>,
+    /// Source position of each op.
+    positions: Vec<Position>,
+    config: Config,
+}
+
+impl Program {
+    /// Compiles the source code with the default [`Config`].
+    pub fn compile(source: &str) -> Result<Self, Error> {
+        Self::compile_with_config(source, &Config::default())
+    }
+
+    /// Compiles the source code with a custom configuration.
+    pub fn compile_with_config(source: &str, config: &Config) -> Result<Self, Error> {
+        let (ops, positions) = Parser::new(source).parse()?;
+        Ok(Self {
+            ops,
+            positions,
+            config: config.clone(),
+        })
+    }
+
+    /// The configuration the program was compiled with.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Runs the program from the start.
+    pub fn run<I: BufRead, O: Write>(&self, input: &mut I, output: &mut O) -> Result<(), Error> {
+        let result = self.execute(input, output);
+        output.flush()?;
+        result
+    }
+
+    fn execute<I: BufRead, O: Write>(&self, input: &mut I, output: &mut O) -> Result<(), Error> {
+        let mut stack: Vec<Int> = vec![];
+        let mut heap: HashMap<Int, Int> = HashMap::new();
+        let mut calls: Vec<usize> = vec![];
+        let mut budget = Budget::new(self.config.max_steps, self.config.timeout);
+        let mut pc = 0;
+        while let Some(op) = self.ops.get(pc) {
+            budget.spend(1)?;
+            let position = self.positions[pc];
+            let pop = |stack: &mut Vec<Int>| stack.pop().ok_or(Error::StackUnderflow(position));
+            pc += 1;
+            match op {
+                Op::Push(value) => stack.push(value.clone()),
+                Op::Dup => {
+                    let top = stack.last().ok_or(Error::StackUnderflow(position))?;
+                    stack.push(top.clone());
+                }
+                Op::Copy(n) => {
+                    let index = stack
+                        .len()
+                        .checked_sub(n.saturating_add(1))
+                        .ok_or(Error::StackUnderflow(position))?;
+                    stack.push(stack[index].clone());
+                }
+                Op::Swap => {
+                    let b = pop(&mut stack)?;
+                    let a = pop(&mut stack)?;
+                    stack.extend([b, a]);
+                }
+                Op::Discard => {
+                    pop(&mut stack)?;
+                }
+                Op::Slide(n) => {
+                    let top = pop(&mut stack)?;
+                    stack.truncate(stack.len().saturating_sub(*n));
+                    stack.push(top);
+                }
+                Op::Add | Op::Sub | Op::Mul | Op::Div | Op::Mod => {
+                    let b = pop(&mut stack)?;
+                    let a = pop(&mut stack)?;
+                    stack.push(match op {
+                        Op::Add => a.add(&b),
+                        Op::Sub => a.sub(&b),
+                        Op::Mul => a.mul(&b),
+                        _ => {
+                            let (q, r) = a.div_mod(&b).ok_or(Error::DivisionByZero(position))?;
+                            if let Op::Div = op {
+                                q
+                            } else {
+                                r
+                            }
+                        }
+                    });
+                }
+                Op::Store => {
+                    let value = pop(&mut stack)?;
+                    let address = pop(&mut stack)?;
+                    heap.insert(address, value);
+                }
+                Op::Retrieve => {
+                    let address = pop(&mut stack)?;
+                    stack.push(heap.get(&address).cloned().unwrap_or(Int::Small(0)));
+                }
+                Op::Call(target) => {
+                    calls.push(pc);
+                    pc = *target;
+                }
+                Op::Jump(target) => pc = *target,
+                Op::JumpZero(target) => {
+                    if pop(&mut stack)?.is_zero() {
+                        pc = *target;
+                    }
+                }
+                Op::JumpNegative(target) => {
+                    if pop(&mut stack)?.is_negative() {
+                        pc = *target;
+                    }
+                }
+                Op::Return => pc = calls.pop().ok_or(Error::CallStackUnderflow(position))?,
+                Op::End => return Ok(()),
+                Op::PrintChar => {
+                    let value = pop(&mut stack)?;
+                    match value.to_char() {
+                        Some(c) => write!(output, "{}", c)?,
+                        None => {
+                            return Err(Error::InvalidCharacter {
+                                value: value.to_string(),
+                                position,
+                            })
+                        }
+                    }
+                }
+                Op::PrintNumber => {
+                    let value = pop(&mut stack)?;
+                    write!(output, "{}", value)?;
+                }
+                Op::ReadChar => {
+                    let address = pop(&mut stack)?;
+                    let value = match read_char(input)? {
+                        Some(c) => Int::Small(c as i64),
+                        None => Int::Small(-1),
+                    };
+                    heap.insert(address, value);
+                }
+                Op::ReadNumber => {
+                    let address = pop(&mut stack)?;
+                    let mut line = String::new();
+                    if input.read_line(&mut line)? == 0 {
+                        return Err(Error::UnexpectedEof(position));
+                    }
+                    let line = line.trim_end_matches(['\n', '\r']);
+                    let value = Int::parse(line.trim()).ok_or_else(|| Error::InvalidNumber {
+                        line: line.to_string(),
+                        position,
+                    })?;
+                    heap.insert(address, value);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl crate::Program for Program {
+    fn run(
+        &self,
+        mut input: &mut dyn BufRead,
+        mut output: &mut dyn Write,
+    ) -> Result<(), crate::Error> {
+        Program::run(self, &mut input, &mut output)
+            .map_err(|error| crate::Error::runtime(Whitespace.name(), error))
+    }
+}
+
+/// A significant character of the source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token {
+    Space,
+    Tab,
+    LineFeed,
+}
+
+/// Reads the instructions from the source and resolves labels.
+struct Parser {
+    tokens: Vec<(Token, Position)>,
+    index: usize,
+}
+
+impl Parser {
+    fn new(source: &str) -> Self {
+        let mut tokens = vec![];
+        let (mut line, mut column) = (1, 1);
+        for c in source.chars() {
+            let token = match c {
+                ' ' => Some(Token::Space),
+                '\t' => Some(Token::Tab),
+                '\n' => Some(Token::LineFeed),
+                _ => None,
+            };
+            if let Some(token) = token {
+                tokens.push((token, Position { line, column }));
+            }
+            if c == '\n' {
+                (line, column) = (line + 1, 1);
+            } else {
+                column += 1;
+            }
+        }
+        Self { tokens, index: 0 }
+    }
+
+    fn parse(mut self) -> Result<(Vec<Op>, Vec<Position>), Error> {
+        use Token::*;
+
+        let mut ops = vec![];
+        let mut positions = vec![];
+        let mut labels: HashMap<String, usize> = HashMap::new();
+        // jumps and calls to resolve after all labels are known
+        let mut jumps: Vec<(usize, String)> = vec![];
+        while let Some(&(_, position)) = self.tokens.get(self.index) {
+            let op = match (self.next(position)?, self.next(position)?) {
+                (Space, Space) => Op::Push(self.number(position)?),
+                (Space, LineFeed) => match self.next(position)? {
+                    Space => Op::Dup,
+                    Tab => Op::Swap,
+                    LineFeed => Op::Discard,
+                },
+                (Space, Tab) => match self.next(position)? {
+                    Space => Op::Copy(self.number(position)?.to_usize().unwrap_or(usize::MAX)),
+                    LineFeed => {
+                        let n = self.number(position)?;
+                        Op::Slide(match n.is_negative() {
+                            true => 0,
+                            false => n.to_usize().unwrap_or(usize::MAX),
+                        })
+                    }
+                    Tab => return Err(Error::InvalidInstruction(position)),
+                },
+                (Tab, Space) => match (self.next(position)?, self.next(position)?) {
+                    (Space, Space) => Op::Add,
+                    (Space, Tab) => Op::Sub,
+                    (Space, LineFeed) => Op::Mul,
+                    (Tab, Space) => Op::Div,
+                    (Tab, Tab) => Op::Mod,
+                    _ => return Err(Error::InvalidInstruction(position)),
+                },
+                (Tab, Tab) => match self.next(position)? {
+                    Space => Op::Store,
+                    Tab => Op::Retrieve,
+                    LineFeed => return Err(Error::InvalidInstruction(position)),
+                },
+                (Tab, LineFeed) => match (self.next(position)?, self.next(position)?) {
+                    (Space, Space) => Op::PrintChar,
+                    (Space, Tab) => Op::PrintNumber,
+                    (Tab, Space) => Op::ReadChar,
+                    (Tab, Tab) => Op::ReadNumber,
+                    _ => return Err(Error::InvalidInstruction(position)),
+                },
+                (LineFeed, first) => match (first, self.next(position)?) {
+                    (Space, Space) => {
+                        let label = self.label(position)?;
+                        if labels.insert(label.clone(), ops.len()).is_some() {
+                            return Err(Error::DuplicateLabel { label, position });
+                        }
+                        continue;
+                    }
+                    (Tab, LineFeed) => Op::Return,
+                    (LineFeed, LineFeed) => Op::End,
+                    (LineFeed, _) => return Err(Error::InvalidInstruction(position)),
+                    (first, second) => {
+                        jumps.push((ops.len(), self.label(position)?));
+                        match (first, second) {
+                            (Space, Tab) => Op::Call(0),
+                            (Space, LineFeed) => Op::Jump(0),
+                            (Tab, Space) => Op::JumpZero(0),
+                            _ => Op::JumpNegative(0),
+                        }
+                    }
+                },
+            };
+            ops.push(op);
+            positions.push(position);
+        }
+        for (index, label) in jumps {
+            let Some(&target) = labels.get(&label) else {
+                let position = positions[index];
+                return Err(Error::UndefinedLabel { label, position });
+            };
+            match &mut ops[index] {
+                Op::Call(t) | Op::Jump(t) | Op::JumpZero(t) | Op::JumpNegative(t) => *t = target,
+                _ => unreachable!("only jumps and calls refer to labels"),
+            }
+        }
+        Ok((ops, positions))
+    }
+
+    /// The next token of the instruction starting at `start`.
+    fn next(&mut self, start: Position) -> Result<Token, Error> {
+        let (token, _) = *self
+            .tokens
+            .get(self.index)
+            .ok_or(Error::IncompleteInstruction(start))?;
+        self.index += 1;
+        Ok(token)
+    }
+
+    /// Reads tabs and spaces up to a line feed.
+    fn digits(&mut self, start: Position) -> Result<Vec<Token>, Error> {
+        let mut digits = vec![];
+        loop {
+            match self.next(start)? {
+                Token::LineFeed => return Ok(digits),
+                digit => digits.push(digit),
+            }
+        }
+    }
+
+    fn number(&mut self, start: Position) -> Result<Int, Error> {
+        let sign = self.next(start)?;
+        if sign == Token::LineFeed {
+            return Ok(Int::Small(0));
+        }
+        let digits = self.digits(start)?;
+        Ok(Int::from_bits(
+            sign == Token::Tab,
+            digits.into_iter().map(|digit| digit == Token::Tab),
+        ))
+    }
+
+    fn label(&mut self, start: Position) -> Result<String, Error> {
+        Ok(self
+            .digits(start)?
+            .into_iter()
+            .map(|digit| if digit == Token::Space { 'S' } else { 'T' })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Turns `S`, `T` and `L` into space, tab and line feed, dropping everything else.
+    fn ws(code: &str) -> String {
+        code.chars()
+            .filter_map(|c| match c {
+                'S' => Some(' '),
+                'T' => Some('\t'),
+                'L' => Some('\n'),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn num(n: i64) -> String {
+        let sign = if n < 0 { "T" } else { "S" };
+        let bits = format!("{:b}", n.unsigned_abs())
+            .replace('0', "S")
+            .replace('1', "T");
+        format!("{}{}L", sign, bits)
+    }
+
+    fn run_str(code: &str, input: &str) -> Result<String, Error> {
+        let config = Config {
+            max_steps: Some(10_000),
+            ..Config::default()
+        };
+        let mut stdout: Vec<u8> = vec![];
+        run_with_config(code, &mut input.as_bytes(), &mut stdout, &config)?;
+        Ok(String::from_utf8(stdout).unwrap())
+    }
+
+    fn output(code: &str, input: &str) -> String {
+        run_str(&ws(code), input).unwrap()
+    }
+
+    #[test]
+    fn test_whitespace() {
+        let (push, printc, printn, end) = ("SS", "TLSS", "TLST", "LLL");
+        let hi = format!(
+            "{push}{} {printc} {push}{} {printc} {end}",
+            num(72),
+            num(105)
+        );
+        assert_eq!(output(&hi, ""), "Hi");
+
+        // a countdown with labels, `dup`, `sub`, `jz` and `jump`
+        let code = format!(
+            "{push}{three} LSS SL | SLS {printn} {push}{one} TSST SLS LTS TL LSL SL | LSS TL {end}",
+            three = num(3),
+            one = num(1),
+        );
+        assert_eq!(output(&code, ""), "321");
+
+        // `copy`, `slide` and `swap`
+        let (one, two, three) = (num(1), num(2), num(3));
+        let code = format!(
+            "{push}{one} {push}{two} {push}{three} STS{two} {printn} STL{one} {printn} {printn} \
+             {push}{one} {push}{two} SLT {printn} {printn}"
+        );
+        assert_eq!(output(&code, ""), "13112");
+
+        // arbitrary precision, and division rounding down
+        let big = format!("{push}{} SLS TSSL {printn}", num(1 << 62));
+        assert_eq!(output(&big, ""), "21267647932558653966460912964485513216");
+        let (minus_seven, two) = (num(-7), num(2));
+        let code =
+            format!("{push}{minus_seven} {push}{two} TSTS {printn} {push}{minus_seven} {push}{two} TSTT {printn}");
+        assert_eq!(output(&code, ""), "-41");
+
+        // the heap, number and character input, `call` and `return`
+        let (zero, one) = (num(0), num(1));
+        let code = format!(
+            "{push}{zero} TLTT {push}{zero} TTT LST TL {printn} {end} \
+             LSS TL SLS TSSS LTL"
+        );
+        assert_eq!(output(&code, " -21 \n"), "-42");
+        let code = format!(
+            "{push}{zero} TLTS {push}{one} TLTS {push}{zero} TTT {printn} {push}{one} TTT {printn}"
+        );
+        assert_eq!(output(&code, "é"), "233-1");
+        assert_eq!(output("SSTTSTTTTSTSTSTTTSL TTT TLST", ""), "0");
+    }
+
+    #[test]
+    fn test_whitespace_errors() {
+        let at = |line, column| Position { line, column };
+        // positions count every character, including comments
+        let code = format!("   \t\nadd{}", ws("TSSS"));
+        assert!(matches!(
+            run_str(&code, ""),
+            Err(Error::StackUnderflow(p)) if p == at(2, 4)
+        ));
+        let code = format!("x{}", ws("LSLTSL"));
+        assert!(matches!(
+            run_str(&code, ""),
+            Err(Error::UndefinedLabel { label, position }) if label == "TS" && position == at(1, 2)
+        ));
+        assert!(matches!(
+            run_str(&ws("LSSTL LSSTL"), ""),
+            Err(Error::DuplicateLabel { label, .. }) if label == "T"
+        ));
+        assert!(matches!(
+            run_str(&ws("SSST"), ""),
+            Err(Error::IncompleteInstruction(p)) if p == at(1, 1)
+        ));
+        assert!(matches!(
+            run_str(&ws("SSSL LTL"), ""),
+            Err(Error::CallStackUnderflow(p)) if p == at(2, 1)
+        ));
+        assert!(matches!(
+            run_str(&ws("SSSTL SSSL TSTT"), ""),
+            Err(Error::DivisionByZero(_))
+        ));
+        assert!(matches!(
+            run_str(&ws("STT"), ""),
+            Err(Error::InvalidInstruction(_))
+        ));
+        assert!(matches!(
+            run_str(&ws("SSTTL TLSS"), ""),
+            Err(Error::InvalidCharacter { value, .. }) if value == "-1"
+        ));
+        assert!(matches!(
+            run_str(&ws("SSSL TLTT"), "12a\n"),
+            Err(Error::InvalidNumber { line, .. }) if line == "12a"
+        ));
+        assert!(matches!(
+            run_str(&ws("SSSL TLTT"), ""),
+            Err(Error::UnexpectedEof(_))
+        ));
+        assert!(matches!(
+            run_str(&ws("LSSL LSLL"), ""),
+            Err(Error::LimitExceeded(_))
+        ));
+    }
+}
@@ -0,0 +1,2 @@
+copies input to output until EOF
+,[.,]
@@ -0,0 +1,2 @@
+reads two bytes after the bang and one from the actual input
+,>,>,#<<.>.>.!hi
\ No newline at end of file
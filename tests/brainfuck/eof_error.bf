@@ -0,0 +1,2 @@
+fails when reading past the end of input
+,[.,]
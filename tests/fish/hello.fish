@@ -0,0 +1,2 @@
+>"!dlrow ,olleH"v
+                >l?!;o
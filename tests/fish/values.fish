@@ -0,0 +1 @@
+*+n;
@@ -0,0 +1,28 @@
+use std::process::{Command, Stdio};
+
+fn esobox() -> Command {
+    test_bin::get_test_bin("esobox")
+}
+
+#[test]
+fn integration_hello_bf93() {
+    for lang in ["befunge93", "bf93"] {
+        let output = esobox()
+            .args([lang, "tests/befunge93/hello.b93"])
+            .stdin(Stdio::null())
+            .output()
+            .expect("Failed to run process");
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"Hello, World!");
+    }
+
+    let output = esobox()
+        .args(["bf93", "tests/befunge93/div_zero.b93"])
+        .stdin(Stdio::null())
+        .output()
+        .expect("Failed to run process");
+    let stderr = String::from_utf8(output.stderr).expect("Output is not valid UTF-8");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr.starts_with("error: befunge93 program terminated with an error\n"));
+    assert!(stderr.contains("division by zero at (2, 0)"));
+}
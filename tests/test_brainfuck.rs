@@ -1,3 +1,4 @@
+use std::io::Write;
 use std::process::{Command, Stdio};
 
 fn esobox() -> Command {
@@ -19,3 +20,252 @@ fn integration_print_bf() {
     assert_eq!(stdout, "brainfuck");
     assert!(stderr.is_empty());
 }
+
+#[test]
+fn integration_errors_bf() {
+    let mut child = esobox()
+        .args(["bf", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to run process");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"+[\n-]]")
+        .expect("Failed to write source");
+    let output = child.wait_with_output().expect("Failed to run process");
+    let stderr = String::from_utf8(output.stderr).expect("Output is not valid UTF-8");
+    assert_eq!(output.status.code(), Some(4));
+    assert!(stderr.starts_with("error: failed to compile brainfuck program\n"));
+    assert!(stderr.contains("unmatched bracket `]` at line 2, column 3"));
+
+    let output = esobox()
+        .args(["bf", "tests/brainfuck/print_bf.bf", "--max-steps", "10"])
+        .stdin(Stdio::null())
+        .output()
+        .expect("Failed to run process");
+    let stderr = String::from_utf8(output.stderr).expect("Output is not valid UTF-8");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr.contains("execution limit exceeded"));
+
+    for timeout in ["-1", "nan", "1e30"] {
+        let output = esobox()
+            .args(["bf", "tests/brainfuck/print_bf.bf", "--timeout", timeout])
+            .stdin(Stdio::null())
+            .output()
+            .expect("Failed to run process");
+        assert_eq!(output.status.code(), Some(2), "--timeout {}", timeout);
+    }
+
+    let output = esobox()
+        .args(["bf", "tests/brainfuck/nonexistent.bf"])
+        .stdin(Stdio::null())
+        .output()
+        .expect("Failed to run process");
+    assert_eq!(output.status.code(), Some(3));
+}
+
+/// Transpiles test programs with esobox, builds them with `compiler`, and checks their output.
+fn check_transpiled(target: &str, compiler: &str, compiler_args: &[&str]) {
+    if Command::new(compiler).arg("--version").output().is_err() {
+        eprintln!("skipping: {} not found", compiler);
+        return;
+    }
+    let dir = std::env::temp_dir().join(format!(
+        "esobox-transpile-{}-{}",
+        target,
+        std::process::id()
+    ));
+    std::fs::create_dir_all(&dir).expect("Failed to create temp dir");
+    let cases: [(&str, &[&str], &str, i32); 4] = [
+        ("tests/brainfuck/print_bf.bf", &[], "brainfuck", 0),
+        ("tests/brainfuck/cat.bf", &["--eof", "zero"], "esobox", 0),
+        ("tests/brainfuck/cat.bf", &["--eof", "error"], "esobox", 1),
+        (
+            "tests/brainfuck/dialect.bf",
+            &["--tape-dump", "--inline-input"],
+            "hie",
+            0,
+        ),
+    ];
+    for (i, (source, args, expected, status)) in cases.into_iter().enumerate() {
+        let output = esobox()
+            .args(["transpile", "bf", source, "--target", target])
+            .args(args)
+            .output()
+            .expect("Failed to run process");
+        assert!(output.status.success());
+        let src_file = dir.join(format!("case{}.{}", i, target));
+        let exe_file = dir.join(format!("case{}.out", i));
+        std::fs::write(&src_file, output.stdout).expect("Failed to write source");
+        let build = Command::new(compiler)
+            .args(compiler_args)
+            .arg("-o")
+            .arg(&exe_file)
+            .arg(&src_file)
+            .status()
+            .expect("Failed to run compiler");
+        assert!(build.success());
+        let mut child = Command::new(&exe_file)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("Failed to run compiled program");
+        // programs that do not read input may exit before it is written
+        child.stdin.take().unwrap().write_all(b"esobox").ok();
+        let output = child.wait_with_output().expect("Failed to run process");
+        assert_eq!(output.stdout, expected.as_bytes());
+        assert_eq!(output.status.code(), Some(status));
+    }
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn integration_transpile_c_bf() {
+    check_transpiled("c", "cc", &["-O1"]);
+}
+
+#[test]
+fn integration_transpile_rust_bf() {
+    check_transpiled("rust", "rustc", &["-O"]);
+}
+
+#[test]
+fn integration_debug_bf() {
+    let mut child = esobox()
+        .args(["debug", "bf", "tests/brainfuck/print_bf.bf", "-b", "229"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to run process");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"c\nw 1\nc\nt 1\nunwatch 1\nd 229\nc\nq\n")
+        .expect("Failed to write commands");
+    let output = child.wait_with_output().expect("Failed to run process");
+    let stdout = String::from_utf8(output.stdout).expect("Output is not valid UTF-8");
+    assert!(output.status.success());
+    assert!(stdout.contains("breakpoint at offset 229\nat offset 229, line 5, column 7\n"));
+    assert!(stdout.contains("cell 1 changed from 0 to 255\n"));
+    assert!(stdout.contains("0: 0  [1: 255]  2: 253\n"));
+    assert!(stdout.contains("brainfuck\nprogram finished after"));
+}
+
+#[test]
+fn integration_profile_bf() {
+    let output = esobox()
+        .args(["profile", "bf", "tests/brainfuck/print_bf.bf", "--annotate"])
+        .stdin(Stdio::null())
+        .output()
+        .expect("Failed to run process");
+    let stdout = String::from_utf8(output.stdout).expect("Output is not valid UTF-8");
+    let stderr = String::from_utf8(output.stderr).expect("Output is not valid UTF-8");
+    assert!(output.status.success());
+    assert_eq!(stdout, "brainfuck");
+    assert!(stderr.contains("executed 92376 commands\n"));
+    assert!(stderr.contains("15254             210  line 7, column 8: [->]\n"));
+    assert!(stderr.contains("         15254 | +[[-<]-[->]<-]<.<<<<."));
+}
+
+#[test]
+fn integration_fmt_minify_bf() {
+    for command in ["fmt", "minify"] {
+        let output = esobox()
+            .args([command, "bf", "tests/brainfuck/print_bf.bf"])
+            .output()
+            .expect("Failed to run process");
+        assert!(output.status.success());
+        let mut child = esobox()
+            .args(["bf", "-"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("Failed to run process");
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(&output.stdout)
+            .expect("Failed to write source");
+        let output = child.wait_with_output().expect("Failed to run process");
+        assert_eq!(output.stdout, b"brainfuck");
+    }
+
+    let output = esobox()
+        .args(["minify", "bf", "tests/brainfuck/print_bf.bf"])
+        .output()
+        .expect("Failed to run process");
+    let stdout = String::from_utf8(output.stdout).expect("Output is not valid UTF-8");
+    assert!(stdout.starts_with(">>>>"));
+    assert!(stdout.ends_with("<++.\n"));
+}
+
+#[test]
+fn integration_lint_bf() {
+    let output = esobox()
+        .args(["lint", "bf", "tests/brainfuck/dialect.bf"])
+        .output()
+        .expect("Failed to run process");
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8(output.stdout).expect("Output is not valid UTF-8");
+    assert!(stdout.starts_with("tests/brainfuck/dialect.bf:2:6: warning: `#` is ignored"));
+    assert_eq!(stdout.lines().count(), 2);
+
+    let output = esobox()
+        .args([
+            "lint",
+            "bf",
+            "tests/brainfuck/dialect.bf",
+            "--format",
+            "json",
+        ])
+        .args(["--tape-dump", "--inline-input"])
+        .output()
+        .expect("Failed to run process");
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"[]\n");
+
+    let output = esobox()
+        .args([
+            "lint",
+            "bf",
+            "tests/brainfuck/print_bf.bf",
+            "--format",
+            "json",
+        ])
+        .output()
+        .expect("Failed to run process");
+    let stdout = String::from_utf8(output.stdout).expect("Output is not valid UTF-8");
+    assert!(stdout.starts_with(r#"[{"kind":"unreachable-loop","offset":0,"#));
+}
+
+#[test]
+fn integration_test_corpus() {
+    let output = esobox()
+        .args(["test", "tests"])
+        .output()
+        .expect("Failed to run process");
+    let stdout = String::from_utf8(output.stdout).expect("Output is not valid UTF-8");
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.contains("PASS tests/brainfuck/print_bf.bf\n"));
+    assert!(stdout.ends_with(" passed, 0 failed\n"));
+
+    let output = esobox()
+        .args(["test", "tests/nonexistent"])
+        .output()
+        .expect("Failed to run process");
+    assert_eq!(output.status.code(), Some(3));
+
+    let output = esobox()
+        .args(["test", "tests", "--timeout", "-1"])
+        .output()
+        .expect("Failed to run process");
+    assert_eq!(output.status.code(), Some(2));
+}
@@ -0,0 +1,38 @@
+use std::process::{Command, Stdio};
+
+fn esobox() -> Command {
+    test_bin::get_test_bin("esobox")
+}
+
+#[test]
+fn integration_hello_fish() {
+    for lang in ["fish", "><>"] {
+        let output = esobox()
+            .args([lang, "tests/fish/hello.fish"])
+            .stdin(Stdio::null())
+            .output()
+            .expect("Failed to run process");
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"Hello, world!");
+    }
+
+    let output = esobox()
+        .args(["fish", "tests/fish/fishy.fish"])
+        .stdin(Stdio::null())
+        .output()
+        .expect("Failed to run process");
+    let stderr = String::from_utf8(output.stderr).expect("Output is not valid UTF-8");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(output.stdout, b"1");
+    assert!(stderr.starts_with("error: fish program terminated with an error\n"));
+    assert!(stderr.contains("something smells fishy... (stack underflow at (2, 0))"));
+
+    // initial stack values, which may be negative
+    let output = esobox()
+        .args(["fish", "tests/fish/values.fish", "-v", "3", "-4.5", "2"])
+        .stdin(Stdio::null())
+        .output()
+        .expect("Failed to run process");
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"-6");
+}
@@ -0,0 +1,38 @@
+use std::process::{Command, Stdio};
+
+fn esobox() -> Command {
+    test_bin::get_test_bin("esobox")
+}
+
+#[test]
+fn integration_hello_bf98() {
+    for lang in ["funge98", "befunge98", "bf98"] {
+        let output = esobox()
+            .args([lang, "tests/funge98/hello.b98"])
+            .stdin(Stdio::null())
+            .output()
+            .expect("Failed to run process");
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"Hello, World!");
+    }
+
+    // `q` sets the exit code without reporting an error
+    let output = esobox()
+        .args(["bf98", "tests/funge98/quit.b98"])
+        .stdin(Stdio::null())
+        .output()
+        .expect("Failed to run process");
+    assert_eq!(output.status.code(), Some(7));
+    assert!(output.stderr.is_empty());
+
+    for (args, flags) in [(&[][..], "0 "), (&["--no-sandbox"][..], "6 ")] {
+        let output = esobox()
+            .args(["bf98", "tests/funge98/sysinfo.b98"])
+            .args(args)
+            .stdin(Stdio::null())
+            .output()
+            .expect("Failed to run process");
+        assert!(output.status.success());
+        assert_eq!(output.stdout, flags.as_bytes());
+    }
+}
@@ -0,0 +1,40 @@
+use std::process::{Command, Stdio};
+
+fn esobox() -> Command {
+    test_bin::get_test_bin("esobox")
+}
+
+#[test]
+fn integration_hello_mb() {
+    for lang in ["malbolge", "mb"] {
+        let output = esobox()
+            .args([lang, "tests/malbolge/hello.mb"])
+            .stdin(Stdio::null())
+            .output()
+            .expect("Failed to run process");
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"Hello, world.");
+    }
+
+    let output = esobox()
+        .args(["mb", "tests/malbolge/invalid.mb"])
+        .stdin(Stdio::null())
+        .output()
+        .expect("Failed to run process");
+    let stderr = String::from_utf8(output.stderr).expect("Output is not valid UTF-8");
+    assert_eq!(output.status.code(), Some(4));
+    assert!(stderr.starts_with("error: failed to compile malbolge program\n"));
+    assert!(stderr.contains("`b` at line 2, column 2 is not a valid instruction at address 2"));
+
+    // the largest word at EOF depends on the memory model
+    for (args, expected) in [(&[][..], 168), (&["--wide-words"][..], 144)] {
+        let output = esobox()
+            .args(["mb", "tests/malbolge/echo.mb"])
+            .args(args)
+            .stdin(Stdio::null())
+            .output()
+            .expect("Failed to run process");
+        assert!(output.status.success());
+        assert_eq!(output.stdout, [expected]);
+    }
+}
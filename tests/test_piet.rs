@@ -0,0 +1,55 @@
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+fn esobox() -> Command {
+    test_bin::get_test_bin("esobox")
+}
+
+#[test]
+fn integration_hi_png() {
+    for file in ["tests/piet/hi.png", "tests/piet/raw.ppm"] {
+        let output = esobox()
+            .args(["piet", file])
+            .stdin(Stdio::null())
+            .output()
+            .expect("Failed to run process");
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"Hi\n");
+    }
+
+    // binary images can be piped in as well
+    let image = fs::read("tests/piet/hi.png").unwrap();
+    let mut process = esobox()
+        .args(["piet", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Failed to run process");
+    process.stdin.take().unwrap().write_all(&image).unwrap();
+    let output = process.wait_with_output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"Hi\n");
+
+    for (codel_size, expected) in [("2", Some(0)), ("3", Some(4))] {
+        let output = esobox()
+            .args(["piet", "tests/piet/codel.ppm", "--codel-size", codel_size])
+            .stdin(Stdio::null())
+            .output()
+            .expect("Failed to run process");
+        assert_eq!(output.status.code(), expected);
+    }
+}
+
+#[test]
+fn integration_invalid_utf8() {
+    // text languages still reject sources that are not UTF-8, as a compile error
+    let output = esobox()
+        .args(["bf", "tests/piet/hi.png"])
+        .stdin(Stdio::null())
+        .output()
+        .expect("Failed to run process");
+    let stderr = String::from_utf8(output.stderr).expect("Output is not valid UTF-8");
+    assert_eq!(output.status.code(), Some(4));
+    assert!(stderr.contains("caused by: source is not valid UTF-8\n"));
+}
@@ -0,0 +1,35 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+fn esobox() -> Command {
+    test_bin::get_test_bin("esobox")
+}
+
+#[test]
+fn integration_hello_unl() {
+    for lang in ["unlambda", "unl"] {
+        let output = esobox()
+            .args([lang, "tests/unlambda/hello.unl"])
+            .stdin(Stdio::null())
+            .output()
+            .expect("Failed to run process");
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"Hello world\n");
+    }
+
+    // deeply nested programs run without overflowing the native stack
+    let depth = 1_000_000;
+    let source = format!("{}`.xi", "`i".repeat(depth));
+    let mut process = esobox()
+        .args(["unl", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Failed to run process");
+    let mut stdin = process.stdin.take().unwrap();
+    stdin.write_all(source.as_bytes()).unwrap();
+    drop(stdin);
+    let output = process.wait_with_output().expect("Failed to run process");
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"x");
+}
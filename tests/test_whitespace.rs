@@ -0,0 +1,28 @@
+use std::process::{Command, Stdio};
+
+fn esobox() -> Command {
+    test_bin::get_test_bin("esobox")
+}
+
+#[test]
+fn integration_hello_ws() {
+    for lang in ["whitespace", "ws"] {
+        let output = esobox()
+            .args([lang, "tests/whitespace/hello.ws"])
+            .stdin(Stdio::null())
+            .output()
+            .expect("Failed to run process");
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"Hello, World!");
+    }
+
+    let output = esobox()
+        .args(["ws", "tests/whitespace/underflow.ws"])
+        .stdin(Stdio::null())
+        .output()
+        .expect("Failed to run process");
+    let stderr = String::from_utf8(output.stderr).expect("Output is not valid UTF-8");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr.starts_with("error: whitespace program terminated with an error\n"));
+    assert!(stderr.contains("stack underflow at line 2, column 1"));
+}